@@ -1,26 +1,58 @@
-use std::f64;
+use std::{f64, sync::OnceLock};
 
 use async_trait::async_trait;
 use chrono::{Datelike, Timelike};
 use net::{
-    chunk::Chunk,
-    connection::Connection,
-    nbt,
+    chunk::{AIR, Chunk},
+    generator::{ChunkGenerator, PRESETS, Superflat},
     packets::{
         play::{
             self,
-            clientbound::{ChunkData, GameEvent, LightData, LoginData},
+            clientbound::{GameEvent, LoginData},
         },
         status::clientbound::{Players, TextComponent},
     },
-    registries,
+    registries::{blocks, entity_types::EntityType},
     server::{self, DimensionData, Error, Server},
+    world::{Dimension, Player, World},
 };
 use uuid::Uuid;
 
+/// Entity ids for the phantoms, reserved so they don't collide with players'.
+static PHANTOM_IDS: OnceLock<Vec<i32>> = OnceLock::new();
+
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    Server::new(Callbacks).listen("0.0.0.0:25565").await?;
+    let superflat = Superflat::from_preset(PRESETS[0].1).unwrap();
+    let overworld = Dimension::new("minecraft:overworld", DimensionData::new(0, 32))
+        .with_generator(superflat.clone())
+        .with_flat(true);
+    let server = Server::new(Callbacks, overworld);
+    let world = server.world();
+    PHANTOM_IDS.get_or_init(|| {
+        phantom_positions()
+            .map(|_| world.allocate_entity_id())
+            .collect()
+    });
+
+    // The clock stands on the same ground as the generated chunks around it
+    let mut chunk = superflat.generate(0, 0, 0, 2, world.biomes());
+    let demo = Chunk::demo(0, 2);
+    for y in demo.y_range() {
+        for z in 0..16 {
+            for x in 0..16 {
+                if demo.block(x, y, z) != AIR {
+                    chunk.set_block_id(x, y, z, demo.block(x, y, z));
+                }
+            }
+        }
+    }
+    let overworld = world.spawn_dimension();
+    overworld.insert_chunk(0, 0, chunk);
+    // Load the chunks the digital clock is drawn in
+    overworld.load_chunk(1, 1);
+    overworld.load_chunk(0, 1);
+    server.listen("0.0.0.0:25565").await?;
     Ok(())
 }
 
@@ -42,106 +74,74 @@ impl server::Callbacks for Callbacks {
         }
     }
 
-    fn dimension_data(&self) -> DimensionData {
-        DimensionData { height: 32 }
-    }
-
-    async fn on_login(&self, conn: &mut Connection) -> Result<(), Error> {
+    async fn on_login(&self, world: &World, player: &Player) -> Result<(), Error> {
         // https://minecraft.wiki/w/Java_Edition_protocol/FAQ#%E2%80%A6my_player_isn't_spawning!
-        conn.send(play::clientbound::Packet::Login {
-            entity_id: 1,
-            data: LoginData {
-                game_mode: 3,
-                is_flat: true,
-                enforces_secure_chat: true,
-            },
-        })
-        .await?;
-        conn.send(play::clientbound::Packet::GameEvent {
-            event: GameEvent::StartChunks,
-            value: 0.0,
-        })
-        .await?;
-
-        let chunk = Chunk::demo(2);
-        conn.send(play::clientbound::Packet::LevelChunkWithLight {
-            chunk_x: 0,
-            chunk_z: 0,
-            data: ChunkData {
-                heightmaps: nbt!({
-                    WORLD_SURFACE: [-1i64; 22],
-                    MOTION_BLOCKING: [-1i64; 22],
-                }),
-                data: chunk.chunk_data(),
-            },
-            light: LightData { subchunk_count: 2 },
-        })
-        .await?;
-
-        let empty_chunk = Chunk::empty(2);
-        for chunk_x in -1..=1 {
-            for chunk_z in -1..=1 {
-                if (chunk_x, chunk_z) == (0, 0) {
-                    continue;
-                }
-                conn.send(play::clientbound::Packet::LevelChunkWithLight {
-                    chunk_x,
-                    chunk_z,
-                    data: ChunkData {
-                        heightmaps: nbt!({
-                            WORLD_SURFACE: [-1i64; 22],
-                            MOTION_BLOCKING: [-1i64; 22],
-                        }),
-                        data: empty_chunk.chunk_data(),
-                    },
-                    light: LightData { subchunk_count: 2 },
+        player
+            .send(play::clientbound::Packet::Login {
+                entity_id: player.entity_id(),
+                data: LoginData {
+                    dimension_names: world.dimension_names(),
+                    spawn: world.spawn_dimension().spawn_info(3),
+                    enforces_secure_chat: true,
+                },
+            })
+            .await?;
+        player
+            .send(play::clientbound::Packet::GameEvent {
+                event: GameEvent::StartChunks,
+                value: 0.0,
+            })
+            .await?;
+
+        for (&entity_id, (x, y, _pitch, _yaw)) in
+            PHANTOM_IDS.get().unwrap().iter().zip(phantom_positions())
+        {
+            player
+                .send(play::clientbound::Packet::AddEntity {
+                    entity_id,
+                    entity_uuid: Uuid::new_v4(),
+                    entity_type: EntityType::Phantom.protocol_id(),
+                    x: 8.0 - x,
+                    y: 7.75 + y,
+                    z: 15.5,
+                    pitch: 0,
+                    yaw: 0,
+                    head_yaw: 0,
+                    data: 0,
+                    velocity_x: 0,
+                    velocity_y: 0,
+                    velocity_z: 0,
                 })
                 .await?;
-            }
         }
 
-        for (i, (x, y, _pitch, _yaw)) in phantom_positions().enumerate() {
-            conn.send(play::clientbound::Packet::AddEntity {
-                entity_id: i as i32 + 10,
-                entity_uuid: Uuid::new_v4(),
-                entity_type: registries::entity_type("minecraft:phantom")
-                    .unwrap()
-                    .protocol_id,
-                x: 8.0 - x,
-                y: 7.75 + y,
-                z: 15.5,
-                pitch: 0,
-                yaw: 0,
-                head_yaw: 0,
-                data: 0,
-                velocity_x: 0,
-                velocity_y: 0,
-                velocity_z: 0,
+        player
+            .send(play::clientbound::Packet::PlayerPosition {
+                teleport_id: 0,
+                x: 8.0,
+                y: 6.38,
+                z: 2.0,
+                velocity_x: 0.0,
+                velocity_y: 0.0,
+                velocity_z: 0.0,
+                yaw: 0.0,
+                pitch: 0.0,
+                flags: 0,
             })
             .await?;
-        }
-
-        conn.send(play::clientbound::Packet::PlayerPosition {
-            teleport_id: 0,
-            x: 8.0,
-            y: 6.38,
-            z: 2.0,
-            velocity_x: 0.0,
-            velocity_y: 0.0,
-            velocity_z: 0.0,
-            yaw: 0.0,
-            pitch: 0.0,
-            flags: 0,
-        })
-        .await?;
 
         Ok(())
     }
 
-    async fn on_tick(&self, conn: &mut Connection) -> Result<(), Error> {
-        for (i, (x, y, pitch, yaw)) in phantom_positions().enumerate() {
-            conn.send(play::clientbound::Packet::EntityPositionSync {
-                entity_id: i as i32 + 10,
+    async fn on_world_tick(&self, world: &World, _tick: u64) -> Result<(), Error> {
+        let overworld = world.spawn_dimension();
+        draw_digital_clock(overworld);
+
+        for (&entity_id, (x, y, pitch, yaw)) in
+            PHANTOM_IDS.get().unwrap().iter().zip(phantom_positions())
+        {
+            overworld.broadcast(play::clientbound::Packet::EntityPositionSync {
+                entity_id,
                 x: 8.0 - x,
                 y: 7.75 + y,
                 z: 15.5,
@@ -151,14 +151,45 @@ impl server::Callbacks for Callbacks {
                 yaw,
                 pitch,
                 on_ground: false,
-            })
-            .await?;
+            });
         }
 
         Ok(())
     }
 }
 
+/// Writes the time in glowstone on the wall behind the analog clock. The
+/// player faces south, so the text runs towards negative x.
+fn draw_digital_clock(overworld: &Dimension) {
+    const FONT: [[&str; 5]; 11] = [
+        ["###", "# #", "# #", "# #", "###"],
+        ["  #", "  #", "  #", "  #", "  #"],
+        ["###", "  #", "###", "#  ", "###"],
+        ["###", "  #", "###", "  #", "###"],
+        ["# #", "# #", "###", "  #", "  #"],
+        ["###", "#  ", "###", "  #", "###"],
+        ["###", "#  ", "###", "# #", "###"],
+        ["###", "  #", "  #", "  #", "  #"],
+        ["###", "# #", "###", "# #", "###"],
+        ["###", "# #", "###", "  #", "###"],
+        ["   ", " # ", "   ", " # ", "   "],
+    ];
+    let glowstone = blocks::Glowstone.state_id();
+
+    let text = chrono::Local::now().format("%H:%M:%S").to_string();
+    let mut x = 8 + 15;
+    for c in text.chars() {
+        let glyph = FONT[c.to_digit(10).unwrap_or(10) as usize];
+        for (row, line) in glyph.iter().enumerate() {
+            for (column, pixel) in line.chars().enumerate() {
+                let block = if pixel == '#' { glowstone } else { AIR };
+                overworld.set_block(x - column as i32, 18 - row as i32, 24, block);
+            }
+        }
+        x -= 4;
+    }
+}
+
 fn phantom_positions() -> impl Iterator<Item = (f64, f64, f32, f32)> {
     let now = chrono::Local::now().time();
     let second_progress = now.num_seconds_from_midnight() as f64 / 60.0 % 1.0;
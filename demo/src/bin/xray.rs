@@ -1,62 +1,53 @@
-use std::collections::BTreeMap;
-
 use async_trait::async_trait;
 use net::{
-    chunk::{Block, Chunk},
-    connection::Connection,
-    nbt,
     packets::{
         play::{
             self,
-            clientbound::{ChunkData, GameEvent, LightData, LoginData},
+            clientbound::{GameEvent, LoginData},
         },
         status::clientbound::{Players, TextComponent},
     },
-    server::{self, DimensionData, Error, Server},
+    registries::blocks,
+    server::{self, Error, Server},
+    volume::{Axis, BlockPalette, ChunkGrid, DataType, Endianness, Volume, VolumeFormat},
+    world::{Dimension, Player, World},
 };
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     let bytes = include_bytes!("../../data/teapot_50.bin");
-    let mut data = vec![0f32; 256 * 256 * 256];
-    bytemuck::cast_slice_mut::<f32, u8>(&mut data)[..bytes.len()].copy_from_slice(bytes);
-
-    let mut chunks = BTreeMap::new();
-    for chunk_z in -1..17 {
-        for chunk_x in -1..17 {
-            let chunk = Chunk::from_fn(16, |x, y, z| {
-                let x = x as i32 + 16 * chunk_x;
-                let y = y as i32;
-                let z = z as i32 + 16 * chunk_z;
-                if !(0..178).contains(&x) || !(0..256).contains(&z) {
-                    return Block::Air;
-                }
-
-                let index = ((256 - y) * 256 + z) * 178 + x;
-                let value = data[index as usize];
-
-                if value > 0.4 {
-                    Block::GrayConcrete
-                } else if value > 0.2 {
-                    Block::GrayStainedGlass
-                } else {
-                    Block::Air
-                }
-            });
-            chunks.insert((-chunk_x, chunk_z), chunk);
-        }
+    let volume = Volume::from_raw(
+        bytes,
+        &VolumeFormat {
+            sizes: [178, 256, 256],
+            data_type: DataType::F32,
+            endianness: Endianness::Little,
+            axes: [Axis::PosX, Axis::PosZ, Axis::NegY],
+        },
+    )
+    .expect("Teapot scan should be 178x256x256 floats");
+
+    let concrete = blocks::GrayConcrete.state_id();
+    let glass = blocks::GrayStainedGlass.state_id();
+    let palette = BlockPalette::new()
+        .with_band(0.2, glass)
+        .with_band(0.4, concrete);
+    let ChunkGrid {
+        chunks,
+        dimension_data,
+    } = volume.to_chunks(&palette).expect("Teapot should fit");
+
+    let teapot = Dimension::new("minecraft:overworld", dimension_data).with_flat(true);
+    for ((chunk_x, chunk_z), chunk) in chunks {
+        teapot.insert_chunk(chunk_x, chunk_z, chunk);
     }
-
-    Server::new(Callbacks { chunks })
-        .listen("0.0.0.0:25565")
-        .await?;
+    let server = Server::new(Callbacks, teapot);
+    server.listen("0.0.0.0:25565").await?;
 
     Ok(())
 }
 
-struct Callbacks {
-    chunks: BTreeMap<(i32, i32), Chunk>,
-}
+struct Callbacks;
 
 #[async_trait]
 impl server::Callbacks for Callbacks {
@@ -73,72 +64,46 @@ impl server::Callbacks for Callbacks {
         }
     }
 
-    fn dimension_data(&self) -> DimensionData {
-        DimensionData { height: 256 }
-    }
-
-    async fn on_login(&self, conn: &mut Connection) -> Result<(), Error> {
+    async fn on_login(&self, world: &World, player: &Player) -> Result<(), Error> {
         // https://minecraft.wiki/w/Java_Edition_protocol/FAQ#%E2%80%A6my_player_isn't_spawning!
-        conn.send(play::clientbound::Packet::Login {
-            entity_id: 1,
-            data: LoginData {
-                game_mode: 3,
-                is_flat: true,
-                enforces_secure_chat: true,
-            },
-        })
-        .await?;
-        conn.send(play::clientbound::Packet::GameEvent {
-            event: GameEvent::StartChunks,
-            value: 0.0,
-        })
-        .await?;
-        conn.send(play::clientbound::Packet::SetChunkCacheCenter {
-            chunk_x: -8,
-            chunk_z: 8,
-        })
-        .await?;
-
-        for (&(chunk_x, chunk_z), chunk) in &self.chunks {
-            conn.send(play::clientbound::Packet::LevelChunkWithLight {
-                chunk_x,
-                chunk_z,
-                data: ChunkData {
-                    heightmaps: nbt!({
-                        WORLD_SURFACE: [-1i64; 22],
-                        MOTION_BLOCKING: [-1i64; 22],
-                    }),
-                    data: chunk.chunk_data(),
+        player
+            .send(play::clientbound::Packet::Login {
+                entity_id: player.entity_id(),
+                data: LoginData {
+                    dimension_names: world.dimension_names(),
+                    spawn: world.spawn_dimension().spawn_info(3),
+                    enforces_secure_chat: true,
                 },
-                light: LightData { subchunk_count: 16 },
             })
             .await?;
-        }
-
-        conn.send(play::clientbound::Packet::PlayerPosition {
-            teleport_id: 0,
-            x: 0.0,
-            y: 160.0,
-            z: 220.0,
-            velocity_x: 0.0,
-            velocity_y: 0.0,
-            velocity_z: 0.0,
-            yaw: 120.0,
-            pitch: 0.0,
-            flags: 0,
-        })
-        .await?;
-        conn.send(play::clientbound::Packet::PlayerAbilities {
-            flags: 0xF,
-            flying_speed: 0.2,
-            fov_modifier: 0.1,
-        })
-        .await?;
-
-        Ok(())
-    }
+        player
+            .send(play::clientbound::Packet::GameEvent {
+                event: GameEvent::StartChunks,
+                value: 0.0,
+            })
+            .await?;
+        player
+            .send(play::clientbound::Packet::PlayerPosition {
+                teleport_id: 0,
+                x: 89.0,
+                y: 160.0,
+                z: 200.0,
+                velocity_x: 0.0,
+                velocity_y: 0.0,
+                velocity_z: 0.0,
+                yaw: 180.0,
+                pitch: 0.0,
+                flags: 0,
+            })
+            .await?;
+        player
+            .send(play::clientbound::Packet::PlayerAbilities {
+                flags: 0xF,
+                flying_speed: 0.2,
+                fov_modifier: 0.1,
+            })
+            .await?;
 
-    async fn on_tick(&self, _conn: &mut Connection) -> Result<(), Error> {
         Ok(())
     }
 }
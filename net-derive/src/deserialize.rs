@@ -73,7 +73,7 @@ fn expand_struct(ident: &Ident, data_struct: &DataStruct) -> Result<TokenStream>
     let fields = data_struct
         .fields
         .iter()
-        .map(|field| expand_field(field))
+        .map(expand_field)
         .collect::<Result<Vec<_>>>()?;
 
     let module = Ident::new(&format!("_deserialize_{ident}"), Span::call_site());
@@ -126,7 +126,7 @@ fn expand_variant(ident: &Ident, variant: &Variant) -> Result<TokenStream> {
             let fields = fields_named
                 .named
                 .iter()
-                .map(|field| expand_field(field))
+                .map(expand_field)
                 .collect::<Result<Vec<_>>>()?;
             Ok(quote_spanned! { variant.span() =>
                 #packet_id => Ok(#ident::#variant_ident { #(#fields)* }),
@@ -59,5 +59,6 @@ fn is_primitive(ident: &Ident) -> bool {
             | "varlong"
             | "string"
             | "uuid"
+            | "position"
     )
 }
@@ -8,16 +8,16 @@ use syn::{
 use crate::{is_primitive, parse_attrs};
 
 pub fn expand(input: &DeriveInput) -> Result<TokenStream> {
-    for attr in parse_attrs(&input.attrs) {
+    if let Some(attr) = parse_attrs(&input.attrs).next() {
         let (ident, _value) = attr?;
         return Err(Error::new(ident.span(), "Unknown packet attribute"));
     }
 
     let ident = &input.ident;
     let generics = &input.generics;
-    if generics.where_clause.is_some() {
+    if let Some(where_clause) = &generics.where_clause {
         return Err(Error::new(
-            generics.where_clause.as_ref().unwrap().span(),
+            where_clause.span(),
             "Where clauses are not supported",
         ));
     }
@@ -70,7 +70,7 @@ fn expand_struct(
     let fields = data_struct
         .fields
         .iter()
-        .map(|field| expand_field(field))
+        .map(expand_field)
         .collect::<Result<Vec<_>>>()?;
 
     let module = Ident::new(&format!("_serialize_{ident}"), Span::call_site());
@@ -123,7 +123,7 @@ fn expand_variant(ident: &Ident, variant: &Variant) -> Result<TokenStream> {
             let fields = fields_named
                 .named
                 .iter()
-                .map(|field| expand_field(field))
+                .map(expand_field)
                 .collect::<Result<Vec<_>>>()?;
             Ok(quote_spanned! { variant.span() =>
                 #ident::#variant_ident { #(#field_names),* } => {
@@ -0,0 +1,939 @@
+//! Generates code from the output of `generate_registries.sh`: typed
+//! blocks, items and entity types and the block state, light and heightmap
+//! tables from the reports, and the synchronised registries from the data pack, embedded
+//! so nothing has to be parsed or searched at runtime.
+
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+const REPORTS_DIR: &str = "../target/registries/generated/reports";
+const DATA_DIR: &str = "../target/registries/generated/data";
+
+/// The registries the client expects from the server, in the order vanilla
+/// sends them. `dimension_type` is left out since the server sends its own
+/// dimensions.
+const SYNCHRONIZED_REGISTRIES: &[&str] = &[
+    "worldgen/biome",
+    "chat_type",
+    "trim_pattern",
+    "trim_material",
+    "wolf_variant",
+    "painting_variant",
+    "damage_type",
+    "banner_pattern",
+    "enchantment",
+    "jukebox_song",
+    "instrument",
+];
+
+/// Block classes, the `type` of a block's definition in the report, whose
+/// blocks don't fill their space, so light passes through them unchanged.
+/// Classes that aren't listed here or in `FILTERING_BLOCKS` are full opaque
+/// cubes.
+const TRANSPARENT_BLOCKS: &[&str] = &[
+    "air",
+    "amethyst_cluster",
+    "anvil",
+    "attached_stem",
+    "azalea",
+    "bamboo_sapling",
+    "bamboo_stalk",
+    "banner",
+    "barrier",
+    "base_coral_fan",
+    "base_coral_plant",
+    "base_coral_wall_fan",
+    "bed",
+    "beetroot",
+    "bell",
+    "big_dripleaf",
+    "big_dripleaf_stem",
+    "brewing_stand",
+    "button",
+    "cactus",
+    "cake",
+    "calibrated_sculk_sensor",
+    "campfire",
+    "candle",
+    "candle_cake",
+    "carpet",
+    "carrot",
+    "cauldron",
+    "cave_vines",
+    "cave_vines_plant",
+    "ceiling_hanging_sign",
+    "chain",
+    "chest",
+    "chorus_flower",
+    "chorus_plant",
+    "cocoa",
+    "comparator",
+    "coral_fan",
+    "coral_plant",
+    "coral_wall_fan",
+    "crop",
+    "daylight_detector",
+    "dead_bush",
+    "decorated_pot",
+    "detector_rail",
+    "dirt_path",
+    "door",
+    "double_plant",
+    "dragon_egg",
+    "enchantment_table",
+    "end_gateway",
+    "end_portal",
+    "end_portal_frame",
+    "end_rod",
+    "ender_chest",
+    "eyeblossom",
+    "farm",
+    "fence",
+    "fence_gate",
+    "fire",
+    "flower",
+    "flower_pot",
+    "frogspawn",
+    "fungus",
+    "glow_lichen",
+    "grindstone",
+    "hanging_moss",
+    "hanging_roots",
+    "heavy_core",
+    "honey",
+    "hopper",
+    "iron_bars",
+    "ladder",
+    "lantern",
+    "lava_cauldron",
+    "layered_cauldron",
+    "lectern",
+    "lever",
+    "light",
+    "lightning_rod",
+    "mangrove_propagule",
+    "mossy_carpet",
+    "moving_piston",
+    "mushroom",
+    "nether_portal",
+    "nether_sprouts",
+    "nether_wart",
+    "pink_petals",
+    "piglinwallskull",
+    "piston_head",
+    "pitcher_crop",
+    "player_head",
+    "player_wall_head",
+    "pointed_dripstone",
+    "potato",
+    "powered_rail",
+    "pressure_plate",
+    "rail",
+    "redstone_torch",
+    "redstone_wall_torch",
+    "redstone_wire",
+    "repeater",
+    "roots",
+    "sapling",
+    "scaffolding",
+    "sculk_sensor",
+    "sculk_shrieker",
+    "sculk_vein",
+    "sea_pickle",
+    "skull",
+    "slab",
+    "small_dripleaf",
+    "sniffer_egg",
+    "snow_layer",
+    "soul_fire",
+    "spore_blossom",
+    "stained_glass",
+    "stained_glass_pane",
+    "stair",
+    "standing_sign",
+    "stem",
+    "stonecutter",
+    "sugar_cane",
+    "sweet_berry_bush",
+    "tall_flower",
+    "tall_grass",
+    "torch",
+    "torchflower_crop",
+    "transparent",
+    "trapdoor",
+    "trapped_chest",
+    "tripwire",
+    "tripwire_hook",
+    "turtle_egg",
+    "twisting_vines",
+    "twisting_vines_plant",
+    "vine",
+    "wall",
+    "wall_banner",
+    "wall_hanging_sign",
+    "wall_sign",
+    "wall_skull",
+    "wall_torch",
+    "waterlily",
+    "waterlogged_transparent",
+    "weathering_copper_door",
+    "weathering_copper_grate",
+    "weathering_copper_slab",
+    "weathering_copper_stair",
+    "weathering_copper_trap_door",
+    "weeping_vines",
+    "weeping_vines_plant",
+    "weighted_pressure_plate",
+    "wither_rose",
+    "wither_skull",
+    "wither_wall_skull",
+    "wool_carpet",
+];
+
+/// Block classes that dim light passing through them by 1, either because
+/// they hold a fluid or because they fill their space without hiding what's
+/// behind them.
+const FILTERING_BLOCKS: &[&str] = &[
+    "beacon",
+    "bubble_column",
+    "cherry_leaves",
+    "frosted_ice",
+    "half_transparent",
+    "ice",
+    "kelp",
+    "kelp_plant",
+    "leaves",
+    "liquid",
+    "mangrove_leaves",
+    "mangrove_roots",
+    "powder_snow",
+    "seagrass",
+    "slime",
+    "spawner",
+    "tall_seagrass",
+    "trial_spawner",
+    "vault",
+    "web",
+];
+
+/// Block classes without collision, which don't block motion unless they
+/// hold a fluid. Vanilla counts cobwebs here too.
+const PASSABLE_BLOCKS: &[&str] = &[
+    "air",
+    "attached_stem",
+    "bamboo_sapling",
+    "base_coral_fan",
+    "base_coral_plant",
+    "base_coral_wall_fan",
+    "beetroot",
+    "button",
+    "carpet",
+    "carrot",
+    "cave_vines",
+    "cave_vines_plant",
+    "coral_fan",
+    "coral_plant",
+    "coral_wall_fan",
+    "crop",
+    "dead_bush",
+    "detector_rail",
+    "double_plant",
+    "end_gateway",
+    "end_portal",
+    "eyeblossom",
+    "fire",
+    "flower",
+    "frogspawn",
+    "fungus",
+    "glow_lichen",
+    "hanging_moss",
+    "hanging_roots",
+    "lever",
+    "light",
+    "mangrove_propagule",
+    "mossy_carpet",
+    "mushroom",
+    "nether_portal",
+    "nether_sprouts",
+    "nether_wart",
+    "pink_petals",
+    "pitcher_crop",
+    "potato",
+    "powered_rail",
+    "rail",
+    "redstone_torch",
+    "redstone_wall_torch",
+    "redstone_wire",
+    "roots",
+    "sapling",
+    "sculk_vein",
+    "small_dripleaf",
+    "snow_layer",
+    "soul_fire",
+    "spore_blossom",
+    "stem",
+    "structure_void",
+    "sugar_cane",
+    "sweet_berry_bush",
+    "tall_flower",
+    "tall_grass",
+    "torch",
+    "torchflower_crop",
+    "tripwire",
+    "tripwire_hook",
+    "twisting_vines",
+    "twisting_vines_plant",
+    "vine",
+    "wall_torch",
+    "web",
+    "weeping_vines",
+    "weeping_vines_plant",
+    "wither_rose",
+    "wool_carpet",
+];
+
+/// Block classes left out of the `MOTION_BLOCKING_NO_LEAVES` heightmap.
+const LEAVES_BLOCKS: &[&str] = &["cherry_leaves", "leaves", "mangrove_leaves"];
+
+fn main() {
+    println!("cargo::rerun-if-changed={REPORTS_DIR}");
+    println!("cargo::rerun-if-changed={DATA_DIR}");
+
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    fs::write(out_dir.join("registry_data.rs"), registry_data()).unwrap();
+
+    let blocks: BTreeMap<String, Block> =
+        serde_json::from_str(&read_report("blocks.json")).unwrap();
+    let block_properties = block_properties(&blocks);
+    fs::write(
+        out_dir.join("blocks.rs"),
+        typed_blocks(&blocks, &block_properties),
+    )
+    .unwrap();
+    fs::write(
+        out_dir.join("block_states.rs"),
+        block_states(&blocks, &block_properties)
+            + &light_tables(&blocks)
+            + &heightmap_tables(&blocks),
+    )
+    .unwrap();
+
+    let registries: BTreeMap<String, Registry> =
+        serde_json::from_str(&read_report("registries.json")).unwrap();
+    fs::write(
+        out_dir.join("items.rs"),
+        id_enum("Item", &registries["minecraft:item"]),
+    )
+    .unwrap();
+    fs::write(
+        out_dir.join("entity_types.rs"),
+        id_enum("EntityType", &registries["minecraft:entity_type"]),
+    )
+    .unwrap();
+    fs::write(
+        out_dir.join("block_entity_types.rs"),
+        id_enum(
+            "BlockEntityType",
+            &registries["minecraft:block_entity_type"],
+        ),
+    )
+    .unwrap();
+}
+
+fn registry_data() -> String {
+    let data_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(DATA_DIR);
+    let mut namespaces = read_dir(&data_dir)
+        .into_iter()
+        .filter(|path| path.is_dir())
+        .collect::<Vec<_>>();
+    namespaces.sort();
+
+    let mut code =
+        String::from("pub(crate) const REGISTRY_DATA: &[(&str, &[(&str, &str)])] = &[\n");
+    for registry in SYNCHRONIZED_REGISTRIES {
+        // Sorted by id, like the registries vanilla loads from data packs
+        let mut entries = Vec::new();
+        for namespace in &namespaces {
+            let namespace_name = namespace.file_name().unwrap().to_string_lossy();
+            let registry_dir = namespace.join(registry);
+            for path in json_files(&registry_dir) {
+                let name = path
+                    .strip_prefix(&registry_dir)
+                    .unwrap()
+                    .with_extension("")
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                entries.push((format!("{namespace_name}:{name}"), path));
+            }
+        }
+        entries.sort();
+
+        code += &format!("    ({registry:?}, &[\n");
+        for (id, path) in entries {
+            let path = path.canonicalize().unwrap();
+            code += &format!("        ({id:?}, include_str!({:?})),\n", path.display());
+        }
+        code += "    ]),\n";
+    }
+    code += "];\n";
+    code
+}
+
+fn read_dir(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| Some(entry.ok()?.path()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Every `.json` file under `dir`, which may not exist.
+fn json_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for path in read_dir(dir) {
+        if path.is_dir() {
+            files.extend(json_files(&path));
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            files.push(path);
+        }
+    }
+    files
+}
+
+fn read_report(name: &str) -> String {
+    fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join(REPORTS_DIR)
+            .join(name),
+    )
+    .unwrap_or_else(|err| panic!("Can't read {name}, run generate_registries.sh: {err}"))
+}
+
+#[derive(Deserialize)]
+struct Block {
+    definition: BlockDefinition,
+    #[serde(default)]
+    properties: BTreeMap<String, Vec<String>>,
+    states: Vec<BlockState>,
+}
+
+#[derive(Deserialize)]
+struct BlockDefinition {
+    /// The block's class, like `minecraft:slab`.
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Deserialize)]
+struct BlockState {
+    id: i32,
+    #[serde(default)]
+    properties: BTreeMap<String, String>,
+    #[serde(default)]
+    default: bool,
+}
+
+#[derive(Deserialize)]
+struct Registry {
+    entries: BTreeMap<String, RegistryEntry>,
+}
+
+#[derive(Deserialize)]
+struct RegistryEntry {
+    protocol_id: i32,
+}
+
+/// How a property is represented in the generated code.
+enum PropertyKind {
+    Bool,
+    Int,
+    /// An enum in `properties`, by name.
+    Enum(String),
+}
+
+/// A block's properties with the step each takes through its state ids.
+struct Property<'a> {
+    name: &'a str,
+    values: &'a [String],
+    kind: PropertyKind,
+    stride: i32,
+}
+
+/// Each block's properties, and the enums in `properties` for each set of
+/// values that isn't a bool or an int.
+struct BlockProperties<'a> {
+    blocks: BTreeMap<&'a str, Vec<Property<'a>>>,
+    enums: String,
+}
+
+fn block_properties(blocks: &BTreeMap<String, Block>) -> BlockProperties<'_> {
+    // Properties with the same name can have different values on different
+    // blocks, like `facing`. The values used by the most blocks get the
+    // plain name and the rest are numbered.
+    let mut value_sets = BTreeMap::<&str, BTreeMap<&[String], usize>>::new();
+    for block in blocks.values() {
+        for (name, values) in &block.properties {
+            if property_kind(values).is_none() {
+                *value_sets
+                    .entry(name)
+                    .or_default()
+                    .entry(values)
+                    .or_default() += 1;
+            }
+        }
+    }
+    let mut enum_names = BTreeMap::<(&str, &[String]), String>::new();
+    let mut enums = String::new();
+    for (name, sets) in &value_sets {
+        let mut sets = sets.iter().collect::<Vec<_>>();
+        sets.sort_by_key(|&(values, count)| (std::cmp::Reverse(*count), *values));
+        for (i, (values, _)) in sets.into_iter().enumerate() {
+            let enum_name = match i {
+                0 => pascal_case(name),
+                i => format!("{}{}", pascal_case(name), i + 1),
+            };
+            enums += &format!(
+                "    /// `{name}`: {}\n",
+                values
+                    .iter()
+                    .map(|value| format!("`{value}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            enums += "    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]\n";
+            enums += &format!("    pub enum {enum_name} {{\n");
+            for value in values.iter() {
+                enums += &format!("        {},\n", pascal_case(value));
+            }
+            enums += "    }\n\n";
+            enums += &format!("    impl {enum_name} {{\n");
+            enums += "        /// The value as it appears in saves and reports.\n";
+            enums += "        pub const fn as_str(self) -> &'static str {\n";
+            enums += "            match self {\n";
+            for value in values.iter() {
+                enums += &format!(
+                    "                Self::{} => {value:?},\n",
+                    pascal_case(value)
+                );
+            }
+            enums += "            }\n        }\n    }\n\n";
+            enum_names.insert((name, values), enum_name);
+        }
+    }
+
+    let mut properties_by_block = BTreeMap::new();
+    for (id, block) in blocks {
+        let base = block.states.iter().map(|state| state.id).min().unwrap();
+        let properties = block
+            .properties
+            .iter()
+            .map(|(name, values)| {
+                let kind = match property_kind(values) {
+                    Some(kind) => kind,
+                    None => {
+                        PropertyKind::Enum(enum_names[&(name.as_str(), values.as_slice())].clone())
+                    }
+                };
+                // The first state with a different value is one step along
+                let first = &block.states[0].properties[name];
+                let stride = block
+                    .states
+                    .iter()
+                    .find(|state| &state.properties[name] != first)
+                    .map_or(1, |state| state.id - block.states[0].id);
+                Property {
+                    name,
+                    values,
+                    kind,
+                    stride,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Check that ids really are the cartesian product of the properties
+        for state in &block.states {
+            let offset: i32 = properties
+                .iter()
+                .map(|property| {
+                    let value = &state.properties[property.name];
+                    let index = property.values.iter().position(|v| v == value);
+                    index.unwrap() as i32 * property.stride
+                })
+                .sum();
+            assert_eq!(base + offset, state.id, "Unexpected state order for {id}");
+        }
+        properties_by_block.insert(id.as_str(), properties);
+    }
+
+    BlockProperties {
+        blocks: properties_by_block,
+        enums,
+    }
+}
+
+/// A struct per block with a field per property.
+fn typed_blocks(blocks: &BTreeMap<String, Block>, block_properties: &BlockProperties) -> String {
+    let mut code = String::new();
+    for (id, block) in blocks {
+        let struct_name = pascal_case(id.trim_start_matches("minecraft:"));
+        let base = block.states.iter().map(|state| state.id).min().unwrap();
+        let default = block
+            .states
+            .iter()
+            .find(|state| state.default)
+            .unwrap_or(&block.states[0]);
+        let properties = &block_properties.blocks[id.as_str()];
+
+        code += &format!("/// `{id}`\n");
+        code += "#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]\n";
+        if properties.is_empty() {
+            code += &format!("pub struct {struct_name};\n\n");
+        } else {
+            code += &format!("pub struct {struct_name} {{\n");
+            for property in properties {
+                let ty = match &property.kind {
+                    PropertyKind::Bool => "bool".to_owned(),
+                    PropertyKind::Int => "u8".to_owned(),
+                    PropertyKind::Enum(enum_name) => format!("properties::{enum_name}"),
+                };
+                code += &format!("    pub {}: {ty},\n", field_name(property.name));
+            }
+            code += "}\n\n";
+        }
+
+        code += &format!("impl {struct_name} {{\n");
+        code += &format!("    pub const ID: &str = {id:?};\n\n");
+        if !properties.is_empty() {
+            code += "    /// Integer properties out of range panic.\n";
+        }
+        code += "    pub const fn state_id(&self) -> i32 {\n";
+        code += &format!("        {base}");
+        for property in properties {
+            let field = field_name(property.name);
+            let index = match &property.kind {
+                PropertyKind::Bool => {
+                    let true_index = property.values.iter().position(|v| v == "true").unwrap();
+                    format!(
+                        "(if self.{field} {{ {true_index} }} else {{ {} }})",
+                        1 - true_index
+                    )
+                }
+                PropertyKind::Int => {
+                    let arms = property
+                        .values
+                        .iter()
+                        .enumerate()
+                        .map(|(i, value)| format!("{value} => {i}, "))
+                        .collect::<String>();
+                    format!(
+                        "(match self.{field} {{ {arms}_ => panic!(\"{} out of range\") }})",
+                        property.name
+                    )
+                }
+                PropertyKind::Enum(_) => format!("(self.{field} as i32)"),
+            };
+            match property.stride {
+                1 => code += &format!("\n            + {index}"),
+                stride => code += &format!("\n            + {index} * {stride}"),
+            }
+        }
+        code += "\n    }\n}\n\n";
+
+        if !properties.is_empty() {
+            code += &format!("impl Default for {struct_name} {{\n");
+            code += "    fn default() -> Self {\n        Self {\n";
+            for property in properties {
+                let value = &default.properties[property.name];
+                let value = match &property.kind {
+                    PropertyKind::Bool | PropertyKind::Int => value.clone(),
+                    PropertyKind::Enum(enum_name) => {
+                        format!("properties::{enum_name}::{}", pascal_case(value))
+                    }
+                };
+                code += &format!("            {}: {value},\n", field_name(property.name));
+            }
+            code += "        }\n    }\n}\n\n";
+        }
+    }
+
+    code += "pub mod properties {\n";
+    code += &block_properties.enums;
+    code += "}\n";
+    code
+}
+
+/// Tables for looking blocks up by id or by state id: `BLOCKS` sorted by
+/// id, and the index in it of each state's block.
+fn block_states(blocks: &BTreeMap<String, Block>, block_properties: &BlockProperties) -> String {
+    let mut state_blocks = Vec::new();
+    let mut code = String::from("static BLOCKS: &[Block] = &[\n");
+    for (i, (id, block)) in blocks.iter().enumerate() {
+        let base = block.states.iter().map(|state| state.id).min().unwrap();
+        let default = block
+            .states
+            .iter()
+            .find(|state| state.default)
+            .unwrap_or(&block.states[0]);
+        for state in &block.states {
+            let index = state.id as usize;
+            if state_blocks.len() <= index {
+                state_blocks.resize(index + 1, None);
+            }
+            state_blocks[index] = Some(i);
+        }
+
+        code += &format!(
+            "    Block {{\n        id: {id:?},\n        first_state_id: {base},\n        default_state_id: {},\n        properties: &[",
+            default.id
+        );
+        for property in &block_properties.blocks[id.as_str()] {
+            code += &format!(
+                "\n            Property {{\n                name: {:?},\n                values: &{:?},\n                stride: {},\n            }},",
+                property.name, property.values, property.stride
+            );
+        }
+        if !block_properties.blocks[id.as_str()].is_empty() {
+            code += "\n        ";
+        }
+        code += "],\n    },\n";
+    }
+    code += "];\n\n";
+
+    code += "static STATE_BLOCKS: &[u16] = &[\n";
+    for (state_id, block) in state_blocks.iter().enumerate() {
+        let block = block.unwrap_or_else(|| panic!("No block has state {state_id}"));
+        code += &format!("    {block},\n");
+    }
+    code += "];\n";
+    code
+}
+
+/// `LIGHT_EMISSION` and `LIGHT_DAMPENING`, indexed by state id. The report
+/// doesn't include either, so they're worked out from each block's class
+/// and properties, with vanilla's light levels for the blocks that glow.
+fn light_tables(blocks: &BTreeMap<String, Block>) -> String {
+    let mut light = Vec::new();
+    for (id, block) in blocks {
+        let kind = block.definition.kind.trim_start_matches("minecraft:");
+        for state in &block.states {
+            let index = state.id as usize;
+            if light.len() <= index {
+                light.resize(index + 1, (0, 0));
+            }
+            light[index] = (
+                light_emission(id.trim_start_matches("minecraft:"), kind, state),
+                light_dampening(kind, state),
+            );
+        }
+    }
+
+    let mut code = String::from("\nstatic LIGHT_EMISSION: &[u8] = &[\n");
+    for (emission, _) in &light {
+        code += &format!("    {emission},\n");
+    }
+    code += "];\n\nstatic LIGHT_DAMPENING: &[u8] = &[\n";
+    for (_, dampening) in &light {
+        code += &format!("    {dampening},\n");
+    }
+    code += "];\n";
+    code
+}
+
+/// The light level a block state gives off.
+fn light_emission(name: &str, kind: &str, state: &BlockState) -> u8 {
+    let property = |name| state.properties.get(name).map(String::as_str);
+    let count = |name| property(name).and_then(|n| n.parse::<u8>().ok());
+    let lit = property("lit") == Some("true");
+    match name {
+        "beacon"
+        | "conduit"
+        | "end_gateway"
+        | "end_portal"
+        | "fire"
+        | "glowstone"
+        | "jack_o_lantern"
+        | "lantern"
+        | "lava"
+        | "ochre_froglight"
+        | "pearlescent_froglight"
+        | "sea_lantern"
+        | "shroomlight"
+        | "verdant_froglight" => 15,
+        "campfire" | "copper_bulb" | "redstone_lamp" | "waxed_copper_bulb" if lit => 15,
+        "end_rod" | "torch" | "wall_torch" => 14,
+        "cave_vines" | "cave_vines_plant" if property("berries") == Some("true") => 14,
+        "blast_furnace" | "furnace" | "smoker" if lit => 13,
+        "exposed_copper_bulb" | "waxed_exposed_copper_bulb" if lit => 12,
+        "nether_portal" => 11,
+        "crying_obsidian" | "soul_fire" | "soul_lantern" | "soul_torch" | "soul_wall_torch" => 10,
+        "soul_campfire" if lit => 10,
+        "redstone_ore" | "deepslate_redstone_ore" if lit => 9,
+        "weathered_copper_bulb" | "waxed_weathered_copper_bulb" if lit => 8,
+        "enchanting_table" | "ender_chest" | "glow_lichen" => 7,
+        "redstone_torch" | "redstone_wall_torch" if lit => 7,
+        "sculk_catalyst" => 6,
+        "amethyst_cluster" => 5,
+        "large_amethyst_bud" => 4,
+        "oxidized_copper_bulb" | "waxed_oxidized_copper_bulb" if lit => 4,
+        "magma_block" => 3,
+        "medium_amethyst_bud" => 2,
+        "brewing_stand" | "brown_mushroom" | "dragon_egg" | "end_portal_frame" | "sculk_sensor"
+        | "small_amethyst_bud" => 1,
+        "light" => count("level").unwrap_or(15),
+        "respawn_anchor" => [0, 3, 7, 11, 15][count("charges").unwrap_or(0).min(4) as usize],
+        "sea_pickle" if property("waterlogged") == Some("true") => {
+            3 * (count("pickles").unwrap_or(1) + 1)
+        }
+        _ if kind == "candle" && lit => 3 * count("candles").unwrap_or(1),
+        _ if kind == "candle_cake" && lit => 3,
+        _ => 0,
+    }
+}
+
+/// How much light is lost entering a block state: none for blocks light
+/// passes through, 1 for those that dim it and 15 for opaque ones.
+fn light_dampening(kind: &str, state: &BlockState) -> u8 {
+    let property = |name| state.properties.get(name).map(String::as_str);
+    let full = match kind {
+        "slab" | "weathering_copper_slab" => property("type") == Some("double"),
+        "snow_layer" => property("layers") == Some("8"),
+        _ => false,
+    };
+    if full {
+        15
+    } else if FILTERING_BLOCKS.contains(&kind) || property("waterlogged") == Some("true") {
+        1
+    } else if TRANSPARENT_BLOCKS.contains(&kind) {
+        0
+    } else {
+        15
+    }
+}
+
+/// `MOTION_BLOCKING` and `LEAVES`, indexed by state id, for the client's
+/// heightmaps. Like light, they come from each block's class.
+fn heightmap_tables(blocks: &BTreeMap<String, Block>) -> String {
+    let mut flags = Vec::new();
+    for block in blocks.values() {
+        let kind = block.definition.kind.trim_start_matches("minecraft:");
+        for state in &block.states {
+            let index = state.id as usize;
+            if flags.len() <= index {
+                flags.resize(index + 1, (false, false));
+            }
+            let waterlogged = state
+                .properties
+                .get("waterlogged")
+                .is_some_and(|w| w == "true");
+            flags[index] = (
+                !PASSABLE_BLOCKS.contains(&kind) || waterlogged,
+                LEAVES_BLOCKS.contains(&kind),
+            );
+        }
+    }
+
+    let mut code = String::from("\nstatic MOTION_BLOCKING: &[bool] = &[\n");
+    for (motion_blocking, _) in &flags {
+        code += &format!("    {motion_blocking},\n");
+    }
+    code += "];\n\nstatic LEAVES: &[bool] = &[\n";
+    for (_, leaves) in &flags {
+        code += &format!("    {leaves},\n");
+    }
+    code += "];\n";
+    code
+}
+
+/// `None` for properties that need an enum.
+fn property_kind(values: &[String]) -> Option<PropertyKind> {
+    if values.iter().all(|v| v == "true" || v == "false") {
+        Some(PropertyKind::Bool)
+    } else if values.iter().all(|v| v.parse::<u8>().is_ok()) {
+        Some(PropertyKind::Int)
+    } else {
+        None
+    }
+}
+
+/// An enum of a registry's entries with their protocol ids as
+/// discriminants.
+fn id_enum(enum_name: &str, registry: &Registry) -> String {
+    let mut entries = registry.entries.iter().collect::<Vec<_>>();
+    entries.sort_by_key(|(_, entry)| entry.protocol_id);
+    for (i, (id, entry)) in entries.iter().enumerate() {
+        assert_eq!(entry.protocol_id, i as i32, "Protocol ids skip {id}");
+    }
+
+    let mut code = String::new();
+    code += "#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]\n";
+    code += "#[repr(i32)]\n";
+    code += &format!("pub enum {enum_name} {{\n");
+    for (id, entry) in &entries {
+        code += &format!("    /// `{id}`\n");
+        code += &format!(
+            "    {} = {},\n",
+            pascal_case(id.trim_start_matches("minecraft:")),
+            entry.protocol_id
+        );
+    }
+    code += "}\n\n";
+
+    code += &format!("impl {enum_name} {{\n");
+    code += "    /// Every entry, indexed by protocol id.\n";
+    code += "    pub const ALL: &[Self] = &[\n";
+    for (id, _) in &entries {
+        code += &format!(
+            "        Self::{},\n",
+            pascal_case(id.trim_start_matches("minecraft:"))
+        );
+    }
+    code += "    ];\n\n";
+    code += "    pub const fn protocol_id(self) -> i32 {\n        self as i32\n    }\n\n";
+    code += "    pub fn from_protocol_id(protocol_id: i32) -> Option<Self> {\n";
+    code += "        Self::ALL.get(usize::try_from(protocol_id).ok()?).copied()\n    }\n\n";
+    code += "    pub const fn id(self) -> &'static str {\n        match self {\n";
+    for (id, _) in &entries {
+        code += &format!(
+            "            Self::{} => {id:?},\n",
+            pascal_case(id.trim_start_matches("minecraft:"))
+        );
+    }
+    code += "        }\n    }\n\n";
+    code += "    pub fn from_id(id: &str) -> Option<Self> {\n        match id {\n";
+    for (id, _) in &entries {
+        code += &format!(
+            "            {id:?} => Some(Self::{}),\n",
+            pascal_case(id.trim_start_matches("minecraft:"))
+        );
+    }
+    code += "            _ => None,\n        }\n    }\n}\n";
+    code
+}
+
+fn pascal_case(name: &str) -> String {
+    name.split(['_', '/', '.'])
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or(String::new(), |first| {
+                first.to_ascii_uppercase().to_string() + chars.as_str()
+            })
+        })
+        .collect()
+}
+
+fn field_name(property: &str) -> String {
+    match property {
+        "type" => "r#type".to_owned(),
+        property => property.to_owned(),
+    }
+}
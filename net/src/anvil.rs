@@ -0,0 +1,744 @@
+//! https://minecraft.wiki/w/Region_file_format
+//! https://minecraft.wiki/w/Chunk_format
+
+mod lz4;
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use flate2::{
+    read::{GzDecoder, ZlibDecoder},
+    write::{GzEncoder, ZlibEncoder},
+};
+use serde::{Deserialize, Serialize};
+use snafu::prelude::*;
+
+use crate::{
+    chunk::{AIR, BlockEntity, Chunk, paletted_container},
+    nbt::{self, LongArray, Tag},
+    registries::{
+        self, Biomes, BlockEntityTypeNotFound, BlockStateNotFound,
+        block_entity_types::BlockEntityType,
+    },
+};
+
+const SECTOR_SIZE: usize = 4096;
+/// Chunks per side of a region.
+const REGION_SIZE: i32 = 32;
+/// The data version of 1.21.4 saves.
+pub const DATA_VERSION: i32 = 4189;
+
+/// How a chunk is compressed in a region file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Compression {
+    Gzip = 1,
+    /// Vanilla's default.
+    #[default]
+    Zlib = 2,
+    Uncompressed = 3,
+    Lz4 = 4,
+}
+
+/// The chunks of a `.mca` file, decompressed but still in their NBT form.
+#[derive(Clone, Debug, Default)]
+pub struct Region {
+    /// Keyed by `(x, z)` within the region.
+    chunks: BTreeMap<(u8, u8), RegionChunk>,
+}
+
+#[derive(Clone, Debug)]
+struct RegionChunk {
+    /// When the chunk was last saved, in seconds since the epoch.
+    timestamp: u32,
+    nbt: Tag,
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(transparent)]
+    IOError { source: io::Error },
+    #[snafu(transparent)]
+    NbtError { source: nbt::Error },
+    #[snafu(transparent)]
+    NbtDeError { source: nbt::de::Error },
+    #[snafu(transparent)]
+    NbtSerError { source: nbt::ser::Error },
+    #[snafu(transparent)]
+    Lz4Error { source: lz4::Error },
+    #[snafu(transparent)]
+    BlockStateNotFound { source: BlockStateNotFound },
+    #[snafu(transparent)]
+    BlockEntityTypeNotFound { source: BlockEntityTypeNotFound },
+    #[snafu(display("Region file header is truncated"))]
+    TruncatedHeader,
+    #[snafu(display("Chunk {x}, {z} is outside the region file"))]
+    TruncatedChunk { x: u8, z: u8 },
+    #[snafu(display("Chunk {x}, {z} is stored in a separate .mcc file"))]
+    ExternalChunk { x: u8, z: u8 },
+    #[snafu(display("Chunk {x}, {z} needs more than 255 sectors"))]
+    ChunkTooLarge { x: u8, z: u8 },
+    #[snafu(display("Unknown chunk compression: {compression}"))]
+    UnknownCompression { compression: u8 },
+    #[snafu(display("Chunk NBT should be a compound"))]
+    NotACompound,
+    #[snafu(display("Block entity is missing its id or position"))]
+    InvalidBlockEntity,
+    #[snafu(display("Invalid block state id: {state_id}"))]
+    InvalidBlockState { state_id: i32 },
+    #[snafu(display("Invalid block entity type: {type_id}"))]
+    InvalidBlockEntityType { type_id: i32 },
+    #[snafu(display("Invalid biome id: {biome_id}"))]
+    InvalidBiome { biome_id: i32 },
+}
+
+impl TryFrom<u8> for Compression {
+    type Error = Error;
+
+    fn try_from(compression: u8) -> Result<Self, Error> {
+        Ok(match compression {
+            1 => Compression::Gzip,
+            2 => Compression::Zlib,
+            3 => Compression::Uncompressed,
+            4 => Compression::Lz4,
+            _ => return UnknownCompressionSnafu { compression }.fail(),
+        })
+    }
+}
+
+impl Compression {
+    fn compress(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+            Compression::Zlib => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+            Compression::Uncompressed => data.to_vec(),
+            Compression::Lz4 => lz4::compress(data),
+        }
+    }
+
+    fn decompress(self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut out = Vec::new();
+        match self {
+            Compression::Gzip => {
+                GzDecoder::new(data).read_to_end(&mut out)?;
+            }
+            Compression::Zlib => {
+                ZlibDecoder::new(data).read_to_end(&mut out)?;
+            }
+            Compression::Uncompressed => out.extend_from_slice(data),
+            Compression::Lz4 => out = lz4::decompress(data)?,
+        }
+        Ok(out)
+    }
+}
+
+impl Region {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::read(&fs::read(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>, compression: Compression) -> Result<(), Error> {
+        fs::write(path, self.write(compression)?)?;
+        Ok(())
+    }
+
+    pub fn read(bytes: &[u8]) -> Result<Self, Error> {
+        ensure!(bytes.len() >= 2 * SECTOR_SIZE, TruncatedHeaderSnafu);
+        let int = |i: usize| u32::from_be_bytes(bytes[i..i + 4].try_into().unwrap());
+
+        let mut chunks = BTreeMap::new();
+        for index in 0..(REGION_SIZE * REGION_SIZE) as usize {
+            let location = int(4 * index);
+            if location == 0 {
+                continue;
+            }
+            let (x, z) = ((index % 32) as u8, (index / 32) as u8);
+            let start = (location >> 8) as usize * SECTOR_SIZE;
+            let sectors = (location & 0xFF) as usize;
+
+            ensure!(
+                start + 5 <= bytes.len() && sectors > 0,
+                TruncatedChunkSnafu { x, z }
+            );
+            // The length includes the compression byte
+            let len = int(start) as usize;
+            ensure!(
+                len >= 1 && start + 4 + len <= bytes.len(),
+                TruncatedChunkSnafu { x, z }
+            );
+            let compression = bytes[start + 4];
+            ensure!(compression & 0x80 == 0, ExternalChunkSnafu { x, z });
+            let data = &bytes[start + 5..start + 4 + len];
+
+            let data = Compression::try_from(compression)?.decompress(data)?;
+            let (_, nbt) = Tag::from_file_bytes(&data)?;
+            let timestamp = int(SECTOR_SIZE + 4 * index);
+            chunks.insert((x, z), RegionChunk { timestamp, nbt });
+        }
+
+        Ok(Self { chunks })
+    }
+
+    /// Fails for chunks that don't fit in 255 sectors, which vanilla would
+    /// move to a separate `.mcc` file.
+    pub fn write(&self, compression: Compression) -> Result<Vec<u8>, Error> {
+        let mut bytes = vec![0; 2 * SECTOR_SIZE];
+        for (&(x, z), chunk) in &self.chunks {
+            let data = compression.compress(&chunk.nbt.to_file_bytes(""));
+            let start = bytes.len();
+            bytes.extend((data.len() as u32 + 1).to_be_bytes());
+            bytes.push(compression as u8);
+            bytes.extend_from_slice(&data);
+            bytes.resize(bytes.len().next_multiple_of(SECTOR_SIZE), 0);
+
+            let index = x as usize + z as usize * 32;
+            let sectors = (bytes.len() - start) / SECTOR_SIZE;
+            ensure!(sectors <= 0xFF, ChunkTooLargeSnafu { x, z });
+            let location = ((start / SECTOR_SIZE) as u32) << 8 | sectors as u32;
+            bytes[4 * index..4 * index + 4].copy_from_slice(&location.to_be_bytes());
+            bytes[SECTOR_SIZE + 4 * index..SECTOR_SIZE + 4 * index + 4]
+                .copy_from_slice(&chunk.timestamp.to_be_bytes());
+        }
+        Ok(bytes)
+    }
+
+    /// The raw NBT of a chunk. Chunk coordinates are taken modulo 32, so
+    /// they can be absolute or relative to the region.
+    pub fn chunk_nbt(&self, chunk_x: i32, chunk_z: i32) -> Option<&Tag> {
+        self.chunks
+            .get(&local(chunk_x, chunk_z))
+            .map(|chunk| &chunk.nbt)
+    }
+
+    pub fn set_chunk_nbt(&mut self, chunk_x: i32, chunk_z: i32, nbt: Tag) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as u32;
+        self.chunks
+            .insert(local(chunk_x, chunk_z), RegionChunk { timestamp, nbt });
+    }
+
+    /// Loads a chunk, keeping the `subchunk_count` sections from `min_y` up,
+    /// with biome ids from `biomes`.
+    pub fn chunk(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        min_y: i32,
+        subchunk_count: u8,
+        biomes: &Biomes,
+    ) -> Result<Option<Chunk>, Error> {
+        self.chunk_nbt(chunk_x, chunk_z)
+            .map(|nbt| chunk_from_nbt(nbt.clone(), min_y, subchunk_count, biomes))
+            .transpose()
+    }
+
+    /// Stores a chunk whose biome ids are from `biomes`.
+    pub fn set_chunk(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk: &Chunk,
+        biomes: &Biomes,
+    ) -> Result<(), Error> {
+        let nbt = chunk_to_nbt(chunk, chunk_x, chunk_z, biomes)?;
+        self.set_chunk_nbt(chunk_x, chunk_z, nbt);
+        Ok(())
+    }
+
+    /// The chunks in the region, by absolute chunk coordinates, once the
+    /// region's position is known from its file name.
+    pub fn chunk_positions(
+        &self,
+        region_x: i32,
+        region_z: i32,
+    ) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.chunks.keys().map(move |&(x, z)| {
+            (
+                region_x * REGION_SIZE + x as i32,
+                region_z * REGION_SIZE + z as i32,
+            )
+        })
+    }
+}
+
+/// The name of the region file containing a chunk, like `r.0.-1.mca`.
+pub fn region_file_name(chunk_x: i32, chunk_z: i32) -> String {
+    format!(
+        "r.{}.{}.mca",
+        chunk_x.div_euclid(REGION_SIZE),
+        chunk_z.div_euclid(REGION_SIZE)
+    )
+}
+
+fn local(chunk_x: i32, chunk_z: i32) -> (u8, u8) {
+    (
+        chunk_x.rem_euclid(REGION_SIZE) as u8,
+        chunk_z.rem_euclid(REGION_SIZE) as u8,
+    )
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ChunkNbt {
+    #[serde(rename = "DataVersion")]
+    data_version: i32,
+    #[serde(rename = "xPos")]
+    x_pos: i32,
+    #[serde(rename = "zPos")]
+    z_pos: i32,
+    /// The lowest section.
+    #[serde(rename = "yPos", default)]
+    y_pos: i32,
+    #[serde(rename = "Status")]
+    status: String,
+    #[serde(rename = "LastUpdate", default)]
+    last_update: i64,
+    sections: Vec<SectionNbt>,
+    /// False makes vanilla relight the chunk when it loads.
+    #[serde(rename = "isLightOn", default)]
+    is_light_on: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SectionNbt {
+    #[serde(rename = "Y")]
+    y: i8,
+    block_states: Option<PaletteNbt<BlockStateNbt>>,
+    biomes: Option<PaletteNbt<String>>,
+}
+
+/// Like the network's indirect palette, but without a global palette to fall
+/// back to and omitting the data for single-valued sections.
+#[derive(Debug, Deserialize, Serialize)]
+struct PaletteNbt<T> {
+    palette: Vec<T>,
+    data: Option<LongArray>,
+}
+
+/// A block state as saved in palettes, also used by structure files.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub(crate) struct BlockStateNbt {
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(
+        rename = "Properties",
+        default,
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub properties: BTreeMap<String, String>,
+}
+
+impl BlockStateNbt {
+    pub fn state_id(&self) -> Result<i32, BlockStateNotFound> {
+        registries::block_state_with_properties(&self.name, &self.properties).map(|state| state.id)
+    }
+}
+
+/// Converts a chunk to the NBT stored in region files, naming its biomes
+/// with `biomes`.
+pub fn chunk_to_nbt(
+    chunk: &Chunk,
+    chunk_x: i32,
+    chunk_z: i32,
+    biomes: &Biomes,
+) -> Result<Tag, Error> {
+    let min_section = chunk.min_y().div_euclid(16);
+    let sections = (min_section..min_section + chunk.subchunk_count() as i32)
+        .map(|section_y| {
+            let mut blocks = Vec::with_capacity(16 * 16 * 16);
+            for y in 0..16 {
+                for z in 0..16 {
+                    for x in 0..16 {
+                        blocks.push(chunk.block(x, section_y * 16 + y, z));
+                    }
+                }
+            }
+            let mut section_biomes = Vec::with_capacity(4 * 4 * 4);
+            for y in 0..4 {
+                for z in 0..4 {
+                    for x in 0..4 {
+                        section_biomes.push(chunk.biome(x * 4, section_y * 16 + y * 4, z * 4));
+                    }
+                }
+            }
+
+            Ok(SectionNbt {
+                y: section_y as i8,
+                block_states: Some(to_palette(&blocks, 4, |state_id| {
+                    let state = registries::block_state_by_id(state_id)
+                        .context(InvalidBlockStateSnafu { state_id })?;
+                    Ok(BlockStateNbt {
+                        name: state.block_id().to_owned(),
+                        properties: state
+                            .properties()
+                            .map(|(name, value)| (name.to_owned(), value.to_owned()))
+                            .collect(),
+                    })
+                })?),
+                biomes: Some(to_palette(&section_biomes, 0, |biome_id| {
+                    biomes
+                        .id(biome_id)
+                        .map(str::to_owned)
+                        .context(InvalidBiomeSnafu { biome_id })
+                })?),
+            })
+        })
+        .collect::<Result<_, Error>>()?;
+
+    let Tag::Compound(mut compound) = nbt::to_tag(&ChunkNbt {
+        data_version: DATA_VERSION,
+        x_pos: chunk_x,
+        z_pos: chunk_z,
+        y_pos: min_section,
+        status: "minecraft:full".to_owned(),
+        last_update: 0,
+        sections,
+        is_light_on: false,
+    })?
+    else {
+        unreachable!("Structs serialize to compounds");
+    };
+
+    let block_entities = chunk
+        .block_entities()
+        .map(|((x, y, z), block_entity)| {
+            let type_id = block_entity.type_id;
+            let id = BlockEntityType::from_protocol_id(type_id)
+                .context(InvalidBlockEntityTypeSnafu { type_id })?
+                .id();
+            let mut fields = vec![
+                ("id".to_owned(), Tag::from(id)),
+                ("x".to_owned(), Tag::Int(chunk_x * 16 + x as i32)),
+                ("y".to_owned(), Tag::Int(y)),
+                ("z".to_owned(), Tag::Int(chunk_z * 16 + z as i32)),
+                ("keepPacked".to_owned(), Tag::Byte(0)),
+            ];
+            if let Tag::Compound(data) = &block_entity.data {
+                fields.extend(data.iter().cloned());
+            }
+            Ok(Tag::Compound(fields))
+        })
+        .collect::<Result<_, Error>>()?;
+    compound.push(("block_entities".to_owned(), Tag::List(block_entities)));
+    compound.push(("Heightmaps".to_owned(), chunk.heightmaps()));
+
+    Ok(Tag::Compound(compound))
+}
+
+/// Converts the NBT from a region file to a chunk with `subchunk_count`
+/// sections from `min_y` up. Sections outside of those are dropped, and
+/// missing ones are air. Biomes get their ids from `biomes`, and those it
+/// doesn't have are loaded as plains.
+pub fn chunk_from_nbt(
+    mut nbt: Tag,
+    min_y: i32,
+    subchunk_count: u8,
+    biomes: &Biomes,
+) -> Result<Chunk, Error> {
+    ensure!(matches!(nbt, Tag::Compound(_)), NotACompoundSnafu);
+    let block_entities = nbt.remove("block_entities");
+    let nbt: ChunkNbt = nbt::from_tag(nbt)?;
+
+    let mut blocks = vec![AIR; subchunk_count as usize * 16 * 16 * 16];
+    let mut section_biomes = BTreeMap::new();
+    for section in nbt.sections {
+        let section_y = section.y as i32 - min_y.div_euclid(16);
+        if !(0..subchunk_count as i32).contains(&section_y) {
+            continue;
+        }
+
+        if let Some(block_states) = section.block_states {
+            let palette = block_states
+                .palette
+                .iter()
+                .map(BlockStateNbt::state_id)
+                .collect::<Result<Vec<_>, _>>()?;
+            let values = from_palette(&palette, block_states.data, 4, 16 * 16 * 16);
+            let start = section_y as usize * 16 * 16 * 16;
+            blocks[start..start + values.len()].copy_from_slice(&values);
+        }
+
+        if let Some(palette_biomes) = section.biomes {
+            let palette = palette_biomes
+                .palette
+                .iter()
+                .map(|id| biomes.protocol_id(id).unwrap_or_else(|_| biomes.plains()))
+                .collect::<Vec<_>>();
+            section_biomes.insert(
+                section.y as i32,
+                from_palette(&palette, palette_biomes.data, 0, 4 * 4 * 4),
+            );
+        }
+    }
+
+    let mut chunk = Chunk::from_fn(min_y, subchunk_count, |x, y, z| {
+        blocks[((y - min_y) as usize * 16 + z as usize) * 16 + x as usize]
+    });
+    for (section_y, section_biomes) in section_biomes {
+        for (i, &biome) in section_biomes.iter().enumerate() {
+            let (x, y, z) = (i % 4, i / 16, i / 4 % 4);
+            chunk.set_biome(
+                x as u8 * 4,
+                section_y * 16 + y as i32 * 4,
+                z as u8 * 4,
+                biome,
+            );
+        }
+    }
+
+    let block_entities = match block_entities {
+        Some(Tag::List(block_entities)) => block_entities,
+        _ => Vec::new(),
+    };
+    for mut fields in block_entities {
+        let (Some(Tag::String(id)), Some(Tag::Int(x)), Some(Tag::Int(y)), Some(Tag::Int(z))) = (
+            fields.remove("id"),
+            fields.remove("x"),
+            fields.remove("y"),
+            fields.remove("z"),
+        ) else {
+            return InvalidBlockEntitySnafu.fail();
+        };
+        fields.remove("keepPacked");
+
+        if chunk.y_range().contains(&y) {
+            chunk.set_block_entity(
+                x.rem_euclid(16) as u8,
+                y,
+                z.rem_euclid(16) as u8,
+                BlockEntity::new(&id, fields)?,
+            );
+        }
+    }
+
+    chunk.take_changes();
+    Ok(chunk)
+}
+
+/// Builds a palette in order of first use, packing the indices with at least
+/// `min_bits` bits.
+fn to_palette<T>(
+    values: &[i32],
+    min_bits: u32,
+    f: impl Fn(i32) -> Result<T, Error>,
+) -> Result<PaletteNbt<T>, Error> {
+    let mut palette = Vec::new();
+    let indices = values
+        .iter()
+        .map(|value| match palette.iter().position(|v| v == value) {
+            Some(index) => index as u64,
+            None => {
+                palette.push(*value);
+                palette.len() as u64 - 1
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let data = (palette.len() > 1).then(|| {
+        let bits = paletted_container::bits_for(palette.len()).max(min_bits);
+        LongArray(paletted_container::pack(bits, indices))
+    });
+    Ok(PaletteNbt {
+        palette: palette.into_iter().map(f).collect::<Result<_, _>>()?,
+        data,
+    })
+}
+
+fn from_palette(palette: &[i32], data: Option<LongArray>, min_bits: u32, len: usize) -> Vec<i32> {
+    match (palette, data) {
+        ([], _) => vec![0; len],
+        ([value], _) | ([value, ..], None) => vec![*value; len],
+        (palette, Some(LongArray(longs))) => {
+            let bits = paletted_container::bits_for(palette.len()).max(min_bits);
+            paletted_container::unpack(bits, &longs, len)
+                .into_iter()
+                .map(|index| palette.get(index as usize).copied().unwrap_or(palette[0]))
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        anvil::{Compression, Error, Region, chunk_from_nbt, chunk_to_nbt, lz4, region_file_name},
+        chunk::{AIR, BlockEntity, Chunk},
+        nbt::Tag,
+        registries::{self, Biomes, blocks},
+    };
+
+    fn generated_chunk(chunk_x: i32, chunk_z: i32) -> Chunk {
+        let count = registries::block_state_count() as i32;
+        let mut chunk = Chunk::from_fn(-16, 2, |x, y, z| match y {
+            -16..-12 => {
+                (x as i32 * 7 + z as i32 * 13 + y + chunk_x * 3 + chunk_z).rem_euclid(count)
+            }
+            -12 => blocks::Stone.state_id(),
+            _ => AIR,
+        });
+        chunk.set_biome(0, 4, 0, 1);
+        chunk.set_block_entity(
+            (chunk_x & 15) as u8,
+            -11,
+            (chunk_z & 15) as u8,
+            BlockEntity::new(
+                "minecraft:sign",
+                Tag::Compound(vec![("is_waxed".to_owned(), Tag::Byte(1))]),
+            )
+            .unwrap(),
+        );
+        chunk
+    }
+
+    fn assert_same(a: &Chunk, b: &Chunk) {
+        for y in a.y_range() {
+            for z in 0..16 {
+                for x in 0..16 {
+                    assert_eq!(a.block(x, y, z), b.block(x, y, z), "{x} {y} {z}");
+                    assert_eq!(a.biome(x, y, z), b.biome(x, y, z));
+                }
+            }
+        }
+        assert!(a.block_entities().eq(b.block_entities()));
+    }
+
+    #[test]
+    fn test_chunk_nbt_round_trip() {
+        let biomes = Biomes::default();
+        let chunk = generated_chunk(-3, 5);
+        let nbt = chunk_to_nbt(&chunk, -3, 5, &biomes).unwrap();
+        assert_same(
+            &chunk,
+            &chunk_from_nbt(nbt.clone(), -16, 2, &biomes).unwrap(),
+        );
+
+        // Only the upper section
+        let upper = chunk_from_nbt(nbt, 0, 1, &biomes).unwrap();
+        assert_eq!(upper.biome(0, 4, 0), 1);
+        assert_eq!(upper.block(0, 0, 0), AIR);
+        assert_eq!(upper.block_entities().count(), 0);
+    }
+
+    #[test]
+    fn test_biome_ids() {
+        let saved = Biomes::new(vec!["minecraft:plains".to_owned(), "a:b".to_owned()]);
+        let loaded = Biomes::new(vec!["a:b".to_owned(), "minecraft:plains".to_owned()]);
+        let mut chunk = Chunk::empty(0, 1);
+        chunk.set_biome(4, 0, 0, 1);
+        let nbt = chunk_to_nbt(&chunk, 0, 0, &saved).unwrap();
+
+        // Biomes are stored by name, so they get the loading server's ids
+        let chunk = chunk_from_nbt(nbt.clone(), 0, 1, &loaded).unwrap();
+        assert_eq!(chunk.biome(0, 0, 0), 1);
+        assert_eq!(chunk.biome(4, 0, 0), 0);
+        let chunk = chunk_from_nbt(nbt, 0, 1, &Biomes::new(vec!["c:d".to_owned()])).unwrap();
+        assert_eq!(chunk.biome(4, 0, 0), 0);
+    }
+
+    #[test]
+    fn test_region_round_trip() {
+        for compression in [
+            Compression::Gzip,
+            Compression::Zlib,
+            Compression::Uncompressed,
+            Compression::Lz4,
+        ] {
+            let mut region = Region::new();
+            let positions = [(-32, 0), (-31, 0), (-1, 31)];
+            for (chunk_x, chunk_z) in positions {
+                region
+                    .set_chunk(
+                        chunk_x,
+                        chunk_z,
+                        &generated_chunk(chunk_x, chunk_z),
+                        &Biomes::default(),
+                    )
+                    .unwrap();
+            }
+
+            let bytes = region.write(compression).unwrap();
+            assert_eq!(bytes.len() % 4096, 0);
+            let region = Region::read(&bytes).unwrap();
+            assert_eq!(
+                region.chunk_positions(-1, 0).collect::<Vec<_>>(),
+                [(-32, 0), (-31, 0), (-1, 31)]
+            );
+            for (chunk_x, chunk_z) in positions {
+                let chunk = region
+                    .chunk(chunk_x, chunk_z, -16, 2, &Biomes::default())
+                    .unwrap()
+                    .unwrap();
+                assert_same(&chunk, &generated_chunk(chunk_x, chunk_z));
+            }
+            assert!(
+                region
+                    .chunk(-32, 5, -16, 2, &Biomes::default())
+                    .unwrap()
+                    .is_none()
+            );
+        }
+    }
+
+    #[test]
+    fn test_unknown_biome() {
+        let mut chunk = Chunk::empty(0, 1);
+        chunk.set_biome(0, 0, 0, 1000);
+        assert!(matches!(
+            chunk_to_nbt(&chunk, 0, 0, &Biomes::default()),
+            Err(Error::InvalidBiome { biome_id: 1000 })
+        ));
+    }
+
+    #[test]
+    fn test_chunk_too_large() {
+        let mut region = Region::new();
+        let data = vec![0; 256 * 4096];
+        region.set_chunk_nbt(
+            1,
+            2,
+            Tag::Compound(vec![("data".to_owned(), Tag::ByteArray(data))]),
+        );
+        assert!(matches!(
+            region.write(Compression::Uncompressed),
+            Err(Error::ChunkTooLarge { x: 1, z: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_lz4_blocks() {
+        let data = (0..200_000u32).map(|i| (i / 7) as u8).collect::<Vec<_>>();
+        let compressed = lz4::compress(&data);
+        assert_eq!(&compressed[..8], b"LZ4Block");
+        assert!(compressed.len() < data.len());
+        assert_eq!(lz4::decompress(&compressed).unwrap(), data);
+
+        let mut corrupted = compressed.clone();
+        corrupted[17] ^= 1;
+        assert!(lz4::decompress(&corrupted).is_err());
+    }
+
+    #[test]
+    fn test_region_file_name() {
+        assert_eq!(region_file_name(0, 0), "r.0.0.mca");
+        assert_eq!(region_file_name(31, -1), "r.0.-1.mca");
+        assert_eq!(region_file_name(-33, 64), "r.-2.2.mca");
+    }
+}
@@ -0,0 +1,92 @@
+//! The framing of lz4-java's `LZ4BlockOutputStream`, which vanilla uses for
+//! LZ4 compressed chunks. Each block is a header followed by up to 64 KiB of
+//! raw or LZ4 compressed data, and an empty block ends the stream.
+
+use snafu::prelude::*;
+use twox_hash::XxHash32;
+
+const MAGIC: &[u8; 8] = b"LZ4Block";
+const HEADER_LEN: usize = MAGIC.len() + 1 + 3 * 4;
+const BLOCK_SIZE: usize = 64 * 1024;
+/// `log2(BLOCK_SIZE) - 10`, stored in the low bits of the token.
+const COMPRESSION_LEVEL: u8 = 6;
+const METHOD_RAW: u8 = 0x10;
+const METHOD_LZ4: u8 = 0x20;
+const CHECKSUM_SEED: u32 = 0x9747b28c;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("LZ4 block is truncated"))]
+    Truncated,
+    #[snafu(display("Invalid LZ4 block header"))]
+    InvalidHeader,
+    #[snafu(display("LZ4 block checksum mismatch"))]
+    ChecksumMismatch,
+    #[snafu(transparent)]
+    Decompress {
+        source: lz4_flex::block::DecompressError,
+    },
+}
+
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for block in data.chunks(BLOCK_SIZE) {
+        let compressed = lz4_flex::block::compress(block);
+        let (method, payload) = if compressed.len() < block.len() {
+            (METHOD_LZ4, compressed.as_slice())
+        } else {
+            (METHOD_RAW, block)
+        };
+        write_header(
+            &mut out,
+            method,
+            payload.len(),
+            block.len(),
+            checksum(block),
+        );
+        out.extend_from_slice(payload);
+    }
+    write_header(&mut out, METHOD_RAW, 0, 0, 0);
+    out
+}
+
+pub fn decompress(mut data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    // Streams written before the end marker was added just stop
+    while !data.is_empty() {
+        ensure!(data.len() >= HEADER_LEN, TruncatedSnafu);
+        ensure!(data.starts_with(MAGIC), InvalidHeaderSnafu);
+        let token = data[8];
+        let int = |i: usize| u32::from_le_bytes(data[i..i + 4].try_into().unwrap()) as usize;
+        let (compressed_len, len, check) = (int(9), int(13), int(17) as u32);
+        data = &data[HEADER_LEN..];
+        if len == 0 {
+            break;
+        }
+
+        ensure!(data.len() >= compressed_len, TruncatedSnafu);
+        let (payload, rest) = data.split_at(compressed_len);
+        data = rest;
+        let block = match token & 0xF0 {
+            METHOD_RAW if compressed_len == len => payload.to_vec(),
+            METHOD_LZ4 => lz4_flex::block::decompress(payload, len)?,
+            _ => return InvalidHeaderSnafu.fail(),
+        };
+        ensure!(checksum(&block) == check, ChecksumMismatchSnafu);
+        out.extend_from_slice(&block);
+    }
+    Ok(out)
+}
+
+fn write_header(out: &mut Vec<u8>, method: u8, compressed_len: usize, len: usize, check: u32) {
+    out.extend_from_slice(MAGIC);
+    out.push(method | COMPRESSION_LEVEL);
+    out.extend((compressed_len as u32).to_le_bytes());
+    out.extend((len as u32).to_le_bytes());
+    out.extend(check.to_le_bytes());
+}
+
+/// lz4-java masks the hash to 28 bits.
+fn checksum(block: &[u8]) -> u32 {
+    XxHash32::oneshot(CHECKSUM_SEED, block) & 0x0FFF_FFFF
+}
@@ -0,0 +1,241 @@
+use md5::Md5;
+use rsa::{Pkcs1v15Encrypt, RsaPrivateKey, pkcs8::EncodePublicKey};
+use sha1::{Digest, Sha1};
+use snafu::prelude::*;
+use uuid::{Builder, Uuid};
+
+use crate::packets::login::clientbound::GameProfile;
+
+/// https://minecraft.wiki/w/Mojang_API#Verify_login_session_on_server
+pub const MOJANG_SESSION_SERVER: &str = "https://sessionserver.mojang.com";
+
+const KEY_BITS: usize = 1024;
+
+/// Holds the server's RSA keypair and verifies players against a session
+/// server during the login encryption handshake.
+pub struct Authenticator {
+    private_key: RsaPrivateKey,
+    public_key: Vec<u8>,
+    session_server: String,
+    http: reqwest::Client,
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(transparent)]
+    RsaError { source: rsa::Error },
+    #[snafu(display("Failed to encode public key: {source}"))]
+    PublicKeyError { source: rsa::pkcs8::spki::Error },
+    #[snafu(display("Verify token does not match"))]
+    VerifyTokenMismatch,
+    #[snafu(transparent)]
+    HttpError { source: reqwest::Error },
+    #[snafu(display("Failed to verify username!"))]
+    NotAuthenticated,
+}
+
+impl Authenticator {
+    /// Generates a fresh keypair. `session_server` is the base URL that
+    /// `/session/minecraft/hasJoined` is resolved against.
+    pub fn new(session_server: impl Into<String>) -> Result<Self, Error> {
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), KEY_BITS)?;
+        let public_key = private_key
+            .to_public_key()
+            .to_public_key_der()
+            .context(PublicKeySnafu)?
+            .into_vec();
+        Ok(Self {
+            private_key,
+            public_key,
+            session_server: session_server.into(),
+            http: reqwest::Client::new(),
+        })
+    }
+
+    /// The public key in X.509 SubjectPublicKeyInfo DER form, as sent in
+    /// the login `Hello` packet.
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok(self.private_key.decrypt(Pkcs1v15Encrypt, data)?)
+    }
+
+    /// Decrypts the client's verify token and checks it against the one we
+    /// sent.
+    pub fn check_verify_token(&self, expected: &[u8], encrypted: &[u8]) -> Result<(), Error> {
+        ensure!(
+            self.decrypt(encrypted)? == expected,
+            VerifyTokenMismatchSnafu
+        );
+        Ok(())
+    }
+
+    pub async fn has_joined(
+        &self,
+        username: &str,
+        shared_secret: &[u8],
+    ) -> Result<GameProfile, Error> {
+        let server_id = server_hash("", shared_secret, &self.public_key);
+        let url = format!(
+            "{}/session/minecraft/hasJoined",
+            self.session_server.trim_end_matches('/'),
+        );
+        let response = self
+            .http
+            .get(url)
+            .query(&[("username", username), ("serverId", &server_id)])
+            .send()
+            .await?
+            .error_for_status()?;
+        ensure!(
+            response.status() == reqwest::StatusCode::OK,
+            NotAuthenticatedSnafu
+        );
+        Ok(response.json().await?)
+    }
+}
+
+/// The profile vanilla servers in offline mode assign to `username`.
+pub fn offline_profile(username: String) -> GameProfile {
+    GameProfile {
+        uuid: offline_uuid(&username),
+        username,
+        properties: Vec::new(),
+    }
+}
+
+/// Version 3 UUID of `OfflinePlayer:<username>`, matching Java's
+/// `UUID.nameUUIDFromBytes`, so offline players keep their identity across
+/// reconnects.
+pub fn offline_uuid(username: &str) -> Uuid {
+    let hash = Md5::new()
+        .chain_update(b"OfflinePlayer:")
+        .chain_update(username.as_bytes())
+        .finalize();
+    Builder::from_md5_bytes(hash.into()).into_uuid()
+}
+
+/// Minecraft's non-standard hex digest: the SHA-1 hash is read as a signed
+/// two's complement integer and printed without leading zeros.
+///
+/// https://minecraft.wiki/w/Minecraft_Wiki:Projects/wiki.vg_merge/Protocol_Encryption#Client
+pub fn server_hash(server_id: &str, shared_secret: &[u8], public_key: &[u8]) -> String {
+    let mut hash: [u8; 20] = Sha1::new()
+        .chain_update(server_id.as_bytes())
+        .chain_update(shared_secret)
+        .chain_update(public_key)
+        .finalize()
+        .into();
+
+    let negative = hash[0] & 0x80 != 0;
+    if negative {
+        let mut carry = true;
+        for byte in hash.iter_mut().rev() {
+            *byte = !*byte;
+            if carry {
+                (*byte, carry) = byte.overflowing_add(1);
+            }
+        }
+    }
+
+    let digits = hash.iter().map(|b| format!("{b:02x}")).collect::<String>();
+    let digits = digits.trim_start_matches('0');
+    if negative {
+        format!("-{digits}")
+    } else {
+        digits.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+    use uuid::uuid;
+
+    use crate::auth::{Authenticator, offline_uuid, server_hash};
+
+    #[test]
+    fn test_server_hash() {
+        fn test(name: &str, expected: &str) {
+            assert_eq!(server_hash(name, &[], &[]), expected);
+        }
+
+        test("Notch", "4ed1f46bbe04bc756bcb17c0c7ce3e4632f06a48");
+        test("jeb_", "-7c9d5b0044c130109a5d7b5fb5c317c02b4e28c1");
+        test("simon", "88e16a1019277b15d58faf0541e11910eb756f6");
+    }
+
+    #[test]
+    fn test_offline_uuid() {
+        assert_eq!(
+            offline_uuid("Notch"),
+            uuid!("b50ad385-829d-3141-a216-7e7d7539ba7f")
+        );
+        assert_eq!(
+            offline_uuid("jeb_"),
+            uuid!("a762f560-4fce-3236-812a-b80efff0b62b")
+        );
+    }
+
+    /// Serves a single canned HTTP response and returns the request line.
+    async fn serve_once(listener: TcpListener, response: &'static str) -> String {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        while !request.ends_with(b"\r\n\r\n") {
+            let mut buf = [0; 1024];
+            let n = stream.read(&mut buf).await.unwrap();
+            request.extend_from_slice(&buf[..n]);
+        }
+        stream.write_all(response.as_bytes()).await.unwrap();
+        let request = String::from_utf8(request).unwrap();
+        request.lines().next().unwrap().to_owned()
+    }
+
+    #[tokio::test]
+    async fn test_has_joined() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let body = r#"{"id":"069a79f444e94726a5befca90e38aaf5","name":"Notch","properties":[{"name":"textures","value":"e30=","signature":"c2ln"}]}"#;
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len(),
+        );
+        let server = tokio::spawn(serve_once(listener, response.leak()));
+
+        let authenticator = Authenticator::new(format!("http://{addr}/")).unwrap();
+        let profile = authenticator.has_joined("Notch", &[0; 16]).await.unwrap();
+        assert_eq!(profile.uuid, uuid!("069a79f4-44e9-4726-a5be-fca90e38aaf5"));
+        assert_eq!(profile.username, "Notch");
+        assert_eq!(profile.properties.len(), 1);
+        assert_eq!(profile.properties[0].name, "textures");
+        assert_eq!(profile.properties[0].signature.as_deref(), Some("c2ln"));
+
+        let request_line = server.await.unwrap();
+        let server_id = server_hash("", &[0; 16], authenticator.public_key());
+        assert_eq!(
+            request_line,
+            format!(
+                "GET /session/minecraft/hasJoined?username=Notch&serverId={server_id} HTTP/1.1"
+            ),
+        );
+    }
+
+    #[tokio::test]
+    async fn test_has_not_joined() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(serve_once(
+            listener,
+            "HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n",
+        ));
+
+        let authenticator = Authenticator::new(format!("http://{addr}")).unwrap();
+        assert!(authenticator.has_joined("Notch", &[0; 16]).await.is_err());
+        server.await.unwrap();
+    }
+}
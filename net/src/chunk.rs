@@ -1,72 +1,280 @@
+pub mod heightmap;
+pub mod paletted_container;
+
+pub use heightmap::Heightmap;
+pub use paletted_container::{PalettedContainer, Strategy};
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Range,
+};
+
 use crate::{
-    packets::serialize::{Serialize, Serializer},
-    registries::{self, BlockState},
+    nbt::Tag,
+    packets::{
+        play::clientbound::{BlockChange, ChunkBlockEntity, ChunkData},
+        serialize::{Serialize, Serializer},
+    },
+    registries::{
+        self, Biomes, BlockEntityTypeNotFound, BlockState,
+        blocks::{
+            self,
+            properties::{Facing, Half},
+        },
+    },
 };
 
-#[derive(Debug)]
+/// The id of `minecraft:air`'s only state.
+pub const AIR: i32 = 0;
+
+/// A column of 16x16x16 sections, storing a block state id per block.
+#[derive(Clone, Debug)]
 pub struct Chunk {
+    /// The y of the bottom of the lowest section, a multiple of 16.
+    min_y: i32,
     subchunks: Vec<Subchunk>,
+    /// Keyed by `(y, z, x)` so they're sent in block order.
+    block_entities: BTreeMap<(i32, u8, u8), BlockEntity>,
+    /// Blocks set since the last [`Chunk::take_changes`], as `(y, z, x)`.
+    changes: BTreeSet<(i32, u8, u8)>,
 }
 
-#[derive(Debug)]
-struct Subchunk {
-    blocks: Vec<Block>,
+/// The extra data of a block like a sign or a chest.
+///
+/// https://minecraft.wiki/w/Block_entity
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockEntity {
+    /// The protocol id in the `minecraft:block_entity_type` registry.
+    pub type_id: i32,
+    /// The fields the client reads, without the id and position.
+    pub data: Tag,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Block {
-    Air,
-    GrayConcrete,
-    GrayStainedGlass,
-    StairsWestTop,
-    StairsWestBottom,
-    StairsEastTop,
-    StairsEastBottom,
+impl BlockEntity {
+    pub fn new(id: &str, data: Tag) -> Result<Self, BlockEntityTypeNotFound> {
+        Ok(Self {
+            type_id: registries::block_entity_type(id)?.protocol_id(),
+            data,
+        })
+    }
 }
 
+#[derive(Clone, Debug)]
+struct Subchunk {
+    blocks: PalettedContainer,
+    biomes: PalettedContainer,
+}
+
+/// Block positions within a chunk take x and z from 0 to 15 and the world y,
+/// which must be in [`Chunk::y_range`].
 impl Chunk {
-    pub fn empty(subchunk_count: u8) -> Self {
+    /// An empty chunk with its bottom at `min_y`, which must be a multiple of
+    /// 16.
+    pub fn empty(min_y: i32, subchunk_count: u8) -> Self {
+        debug_assert_eq!(min_y % 16, 0, "Chunks start at a section boundary");
         Self {
+            min_y,
             subchunks: (0..subchunk_count).map(|_| Subchunk::empty()).collect(),
+            block_entities: BTreeMap::new(),
+            changes: BTreeSet::new(),
         }
     }
 
-    pub fn from_fn(subchunk_count: u8, mut f: impl FnMut(u8, u16, u8) -> Block) -> Self {
+    pub fn from_fn(min_y: i32, subchunk_count: u8, mut f: impl FnMut(u8, i32, u8) -> i32) -> Self {
+        debug_assert_eq!(min_y % 16, 0, "Chunks start at a section boundary");
         Self {
+            min_y,
             subchunks: (0..subchunk_count)
                 .map(|chunk_y| {
-                    Subchunk::from_fn(|x, y, z| f(x, y as u16 + 16 * (chunk_y as u16), z))
+                    Subchunk::from_fn(|x, y, z| f(x, min_y + 16 * chunk_y as i32 + y as i32, z))
                 })
                 .collect(),
+            block_entities: BTreeMap::new(),
+            changes: BTreeSet::new(),
         }
     }
 
-    pub fn demo(subchunk_count: u8) -> Self {
-        let mut chunk = Self::empty(subchunk_count);
-        chunk.set_block(7, 7, 15, Block::StairsWestTop);
-        chunk.set_block(7, 8, 15, Block::StairsWestBottom);
-        chunk.set_block(8, 7, 15, Block::StairsEastTop);
-        chunk.set_block(8, 8, 15, Block::StairsEastBottom);
+    pub fn demo(min_y: i32, subchunk_count: u8) -> Self {
+        let stairs = |facing, half| {
+            blocks::DeepslateTileStairs {
+                facing,
+                half,
+                ..Default::default()
+            }
+            .state_id()
+        };
+
+        let mut chunk = Self::empty(min_y, subchunk_count);
+        chunk.set_block_id(7, 7, 15, stairs(Facing::West, Half::Top));
+        chunk.set_block_id(7, 8, 15, stairs(Facing::West, Half::Bottom));
+        chunk.set_block_id(8, 7, 15, stairs(Facing::East, Half::Top));
+        chunk.set_block_id(8, 8, 15, stairs(Facing::East, Half::Bottom));
         chunk
     }
 
+    pub fn subchunk_count(&self) -> u8 {
+        self.subchunks.len() as u8
+    }
+
+    /// The height of the chunk in blocks.
+    pub fn height(&self) -> u16 {
+        self.subchunks.len() as u16 * 16
+    }
+
+    /// The y of the bottom of the chunk.
+    pub fn min_y(&self) -> i32 {
+        self.min_y
+    }
+
+    /// The world y coordinates the chunk covers.
+    pub fn y_range(&self) -> Range<i32> {
+        self.min_y..self.min_y + self.height() as i32
+    }
+
+    /// The section containing a world y, and the y within it.
+    #[inline]
+    fn section(&self, y: i32) -> (usize, u8) {
+        let y = y - self.min_y;
+        ((y / 16) as usize, (y % 16) as u8)
+    }
+
     #[inline]
-    pub fn set_block(&mut self, x: u8, y: u16, z: u8, block: Block) {
-        let subchunk = &mut self.subchunks[(y / 16) as usize];
-        subchunk.set_block(x, (y % 16) as u8, z, block);
+    pub fn set_block(&mut self, x: u8, y: i32, z: u8, state: &BlockState) {
+        self.set_block_id(x, y, z, state.id);
     }
 
+    /// Sets a block, remembering it for [`Chunk::take_changes`] if the state
+    /// changed. A changed block loses its block entity.
     #[inline]
-    pub fn block(&self, x: u8, y: u16, z: u8) -> Block {
-        let subchunk = &self.subchunks[(y / 16) as usize];
-        subchunk.block(x, (y % 16) as u8, z)
+    pub fn set_block_id(&mut self, x: u8, y: i32, z: u8, state_id: i32) {
+        let (section, section_y) = self.section(y);
+        let subchunk = &mut self.subchunks[section];
+        if subchunk.block(x, section_y, z) != state_id {
+            subchunk.set_block(x, section_y, z, state_id);
+            self.block_entities.remove(&(y, z, x));
+            self.changes.insert((y, z, x));
+        }
+    }
+
+    /// The blocks changed since the last call, grouped by the section's y in
+    /// the world, for `BlockUpdate` and `SectionBlocksUpdate`.
+    pub fn take_changes(&mut self) -> BTreeMap<i32, Vec<BlockChange>> {
+        let mut sections = BTreeMap::<i32, Vec<BlockChange>>::new();
+        for (y, z, x) in std::mem::take(&mut self.changes) {
+            sections
+                .entry(y.div_euclid(16))
+                .or_default()
+                .push(BlockChange {
+                    x,
+                    y: y.rem_euclid(16) as u8,
+                    z,
+                    state_id: self.block(x, y, z),
+                });
+        }
+        sections
+    }
+
+    /// The block state id at the given position.
+    #[inline]
+    pub fn block(&self, x: u8, y: i32, z: u8) -> i32 {
+        let (section, section_y) = self.section(y);
+        self.subchunks[section].block(x, section_y, z)
+    }
+
+    /// Replaces the block entity at the given position. The block there
+    /// should be one that has this kind of block entity, or the client will
+    /// ignore it.
+    pub fn set_block_entity(&mut self, x: u8, y: i32, z: u8, block_entity: BlockEntity) {
+        self.block_entities.insert((y, z, x), block_entity);
+    }
+
+    pub fn remove_block_entity(&mut self, x: u8, y: i32, z: u8) -> Option<BlockEntity> {
+        self.block_entities.remove(&(y, z, x))
+    }
+
+    pub fn block_entity(&self, x: u8, y: i32, z: u8) -> Option<&BlockEntity> {
+        self.block_entities.get(&(y, z, x))
     }
 
-    pub fn chunk_data(&self) -> Vec<u8> {
+    /// Every block entity, with its position in the chunk.
+    pub fn block_entities(&self) -> impl Iterator<Item = ((u8, i32, u8), &BlockEntity)> {
+        self.block_entities
+            .iter()
+            .map(|(&(y, z, x), block_entity)| ((x, y, z), block_entity))
+    }
+
+    /// Sets the biome of the 4x4x4 cell containing the given block.
+    pub fn set_biome(&mut self, x: u8, y: i32, z: u8, biome_id: i32) {
+        let (section, section_y) = self.section(y);
+        self.subchunks[section]
+            .biomes
+            .set(biome_index(x, section_y, z), biome_id);
+    }
+
+    /// The biome id of the 4x4x4 cell containing the given block.
+    pub fn biome(&self, x: u8, y: i32, z: u8) -> i32 {
+        let (section, section_y) = self.section(y);
+        self.subchunks[section]
+            .biomes
+            .get(biome_index(x, section_y, z))
+    }
+
+    /// The height of each column above the bottom of the chunk, indexed by
+    /// `z * 16 + x`: one above the highest block the heightmap counts, or 0
+    /// if there is none.
+    pub fn heightmap(&self, heightmap: Heightmap) -> Vec<u16> {
+        let mut heights = Vec::with_capacity(16 * 16);
+        for z in 0..16 {
+            for x in 0..16 {
+                let height = self
+                    .y_range()
+                    .rev()
+                    .find(|&y| heightmap.is_opaque(self.block(x, y, z)))
+                    .map_or(0, |y| (y - self.min_y + 1) as u16);
+                heights.push(height);
+            }
+        }
+        heights
+    }
+
+    /// The heightmaps the client needs, packed for `ChunkData`.
+    pub fn heightmaps(&self) -> Tag {
+        let bits = paletted_container::bits_for(self.height() as usize + 1);
+        Tag::Compound(
+            Heightmap::CLIENT
+                .into_iter()
+                .map(|heightmap| {
+                    let heights = self.heightmap(heightmap);
+                    let longs = paletted_container::pack(bits, heights.iter().map(|&h| h as u64));
+                    (heightmap.name().to_owned(), Tag::LongArray(longs))
+                })
+                .collect(),
+        )
+    }
+
+    /// Everything `LevelChunkWithLight` needs except the light, with biome
+    /// ids from `biomes`.
+    pub fn to_chunk_data(&self, biomes: &Biomes) -> ChunkData {
+        ChunkData {
+            heightmaps: self.heightmaps(),
+            data: self.chunk_data(biomes),
+            block_entities: self
+                .block_entities()
+                .map(|((x, y, z), block_entity)| ChunkBlockEntity {
+                    x,
+                    y: y as i16,
+                    z,
+                    block_entity: block_entity.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn chunk_data(&self, biomes: &Biomes) -> Vec<u8> {
         let mut s = Serializer::new();
 
         for subchunk in &self.subchunks {
-            subchunk.chunk_data(&mut s);
+            subchunk.chunk_data(&mut s, biomes);
         }
 
         s.finish()
@@ -76,11 +284,12 @@ impl Chunk {
 impl Subchunk {
     fn empty() -> Self {
         Self {
-            blocks: vec![Block::Air; 16 * 16 * 16],
+            blocks: PalettedContainer::filled(Strategy::block_states(), AIR),
+            biomes: PalettedContainer::filled(Strategy::biomes(&Biomes::default()), 0),
         }
     }
 
-    fn from_fn(mut f: impl FnMut(u8, u8, u8) -> Block) -> Self {
+    fn from_fn(mut f: impl FnMut(u8, u8, u8) -> i32) -> Self {
         let mut blocks = Vec::with_capacity(16 * 16 * 16);
         for y in 0..16 {
             for z in 0..16 {
@@ -89,7 +298,10 @@ impl Subchunk {
                 }
             }
         }
-        Self { blocks }
+        Self {
+            blocks: PalettedContainer::from_values(Strategy::block_states(), blocks),
+            biomes: PalettedContainer::filled(Strategy::biomes(&Biomes::default()), 0),
+        }
     }
 
     #[inline]
@@ -98,85 +310,238 @@ impl Subchunk {
     }
 
     #[inline]
-    fn set_block(&mut self, x: u8, y: u8, z: u8, block: Block) {
+    fn set_block(&mut self, x: u8, y: u8, z: u8, state_id: i32) {
         let index = self.block_index(x, y, z);
-        self.blocks[index] = block;
+        self.blocks.set(index, state_id);
     }
 
     #[inline]
-    fn block(&self, x: u8, y: u8, z: u8) -> Block {
+    fn block(&self, x: u8, y: u8, z: u8) -> i32 {
         let index = self.block_index(x, y, z);
-        self.blocks[index]
+        self.blocks.get(index)
     }
 
-    fn chunk_data(&self, s: &mut Serializer) {
+    fn chunk_data(&self, s: &mut Serializer, biomes: &Biomes) {
         // Block count
-        let block_count = self.blocks.iter().filter(|&&b| b != Block::Air).count() as i16;
+        let block_count = self
+            .blocks
+            .values()
+            .iter()
+            .filter(|&&b| !registries::is_air(b))
+            .count() as i16;
         s.serialize_short(block_count);
 
-        // Block states
-        // https://minecraft.wiki/w/Minecraft_Wiki:Projects/wiki.vg_merge/Chunk_Format?oldid=2845070#Paletted_Container_structure
-        s.serialize_ubyte(4); // Bits per entry
-        s.serialize_prefixed_array(&[
-            registries::block_state("minecraft:air", &[]).unwrap(),
-            registries::block_state("minecraft:gray_concrete", &[]).unwrap(),
-            registries::block_state("minecraft:gray_stained_glass", &[]).unwrap(),
-            registries::block_state(
-                "minecraft:deepslate_tile_stairs",
-                &[
-                    ("facing", "west"),
-                    ("half", "top"),
-                    ("shape", "straight"),
-                    ("waterlogged", "false"),
-                ],
-            )
-            .unwrap(),
-            registries::block_state(
-                "minecraft:deepslate_tile_stairs",
-                &[
-                    ("facing", "west"),
-                    ("half", "bottom"),
-                    ("shape", "straight"),
-                    ("waterlogged", "false"),
-                ],
-            )
-            .unwrap(),
-            registries::block_state(
-                "minecraft:deepslate_tile_stairs",
-                &[
-                    ("facing", "east"),
-                    ("half", "top"),
-                    ("shape", "straight"),
-                    ("waterlogged", "false"),
-                ],
-            )
-            .unwrap(),
-            registries::block_state(
-                "minecraft:deepslate_tile_stairs",
-                &[
-                    ("facing", "east"),
-                    ("half", "bottom"),
-                    ("shape", "straight"),
-                    ("waterlogged", "false"),
-                ],
-            )
-            .unwrap(),
-        ]); // Pallete
-        s.serialize_varint(16 * 16 * 16 / 2 / 8); // Length in i64s
-        for chunk in self.blocks.chunks(2) {
-            let x = chunk[0] as u8 & 0x0F;
-            let y = chunk[1] as u8 & 0x0F;
-            let b = (x << 4) | y;
-            s.serialize_ubyte(b);
+        self.blocks.serialize(s);
+        // The server may have more biomes than the generated ones the section
+        // was created with, which can widen their global ids
+        match Strategy::biomes(biomes) {
+            strategy if strategy == self.biomes.strategy() => self.biomes.serialize(s),
+            strategy => {
+                PalettedContainer::from_values(strategy, self.biomes.values().to_vec()).serialize(s)
+            }
         }
-
-        // Biomes
-        s.serialize_byte_array(&[0x00, 0x00, 0x00]);
     }
 }
 
+#[inline]
+fn biome_index(x: u8, y: u8, z: u8) -> usize {
+    ((y as usize / 4) * 4 + z as usize / 4) * 4 + x as usize / 4
+}
+
 impl Serialize for &BlockState {
     fn serialize(&self, s: &mut Serializer) {
         s.serialize_varint(self.id);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chunk::{
+            AIR, BlockEntity, Chunk, Heightmap, PalettedContainer, Strategy,
+            paletted_container::Encoding,
+        },
+        nbt::Tag,
+        packets::{
+            deserialize::{Deserialize, Deserializer},
+            play::clientbound::BlockChange,
+            serialize::Serializer,
+        },
+        registries::{self, Biomes, blocks},
+    };
+
+    /// Reads back the block states and biomes of the first section of `data`.
+    fn decode_section(data: &[u8]) -> (PalettedContainer, PalettedContainer) {
+        let mut d = Deserializer::new(data);
+        d.deserialize_short().unwrap();
+        let blocks = PalettedContainer::deserialize(&mut d, Strategy::block_states()).unwrap();
+        let biomes =
+            PalettedContainer::deserialize(&mut d, Strategy::biomes(&Biomes::default())).unwrap();
+        (blocks, biomes)
+    }
+
+    #[test]
+    fn test_indirect_palette() {
+        let stone = blocks::Stone.state_id();
+        let mut chunk = Chunk::empty(0, 1);
+        chunk.set_block_id(1, 2, 3, stone);
+        chunk.set_biome(15, 15, 15, 1);
+
+        let (blocks, biomes) = decode_section(&chunk.chunk_data(&Biomes::default()));
+        assert!(matches!(
+            blocks.encoding(),
+            Encoding::Indirect { bits: 4, .. }
+        ));
+        assert_eq!(blocks.get((2 * 16 + 3) * 16 + 1), stone);
+        assert_eq!(blocks.values().iter().filter(|&&b| b != AIR).count(), 1);
+        assert_eq!(biomes.get(63), 1);
+        assert_eq!(biomes.values().iter().filter(|&&b| b != 0).count(), 1);
+    }
+
+    #[test]
+    fn test_heightmaps() {
+        let stone = blocks::Stone.state_id();
+        let leaves = blocks::OakLeaves::default().state_id();
+        let torch = blocks::Torch.state_id();
+        let grass = blocks::ShortGrass.state_id();
+        let water = blocks::Water::default().state_id();
+        let mut chunk = Chunk::from_fn(0, 2, |_, y, _| if y < 4 { stone } else { AIR });
+        chunk.set_block_id(1, 9, 0, leaves);
+        chunk.set_block_id(2, 30, 0, torch);
+        chunk.set_block_id(3, 20, 0, grass);
+        chunk.set_block_id(4, 12, 0, water);
+        chunk.set_block_id(15, 31, 15, stone);
+
+        let surface = chunk.heightmap(Heightmap::WorldSurface);
+        assert_eq!(&surface[..5], &[4, 10, 31, 21, 13]);
+        assert_eq!(surface[255], 32);
+        let motion = chunk.heightmap(Heightmap::MotionBlocking);
+        assert_eq!(&motion[..5], &[4, 10, 4, 4, 13]);
+        let no_leaves = chunk.heightmap(Heightmap::MotionBlockingNoLeaves);
+        assert_eq!(&no_leaves[..5], &[4, 4, 4, 4, 13]);
+
+        // Heights up to 32 need 6 bits, so 10 fit in each long
+        let Tag::Compound(heightmaps) = chunk.heightmaps() else {
+            panic!("Heightmaps should be a compound");
+        };
+        assert_eq!(heightmaps.len(), 3);
+        let (name, Tag::LongArray(longs)) = &heightmaps[0] else {
+            panic!("Heightmap should be a long array");
+        };
+        assert_eq!(name, "WORLD_SURFACE");
+        assert_eq!(longs.len(), 26);
+        assert_eq!(longs[0] & 0x3F, 4);
+        assert_eq!(longs[0] >> 6 & 0x3F, 10);
+        assert_eq!(longs[25] >> 30 & 0x3F, 32);
+    }
+
+    #[test]
+    fn test_direct_palette() {
+        let count = registries::block_state_count() as i32;
+        let chunk = Chunk::from_fn(0, 1, |x, y, z| (x as i32 + 16 * z as i32 + 256 * y) % count);
+
+        let (blocks, biomes) = decode_section(&chunk.chunk_data(&Biomes::default()));
+        assert!(matches!(blocks.encoding(), Encoding::Direct { .. }));
+        assert!(
+            blocks
+                .values()
+                .iter()
+                .enumerate()
+                .all(|(i, &b)| b == i as i32 % count)
+        );
+        assert_eq!(biomes.encoding(), Encoding::Single(0));
+    }
+
+    #[test]
+    fn test_block_entities() {
+        let chest = BlockEntity::new("minecraft:chest", Tag::Compound(vec![])).unwrap();
+        let sign = BlockEntity::new(
+            "minecraft:sign",
+            Tag::Compound(vec![("is_waxed".to_owned(), Tag::Byte(1))]),
+        )
+        .unwrap();
+        let mut chunk = Chunk::empty(0, 2);
+        chunk.set_block_entity(3, 20, 4, sign.clone());
+        chunk.set_block_entity(1, 2, 15, chest.clone());
+        chunk.set_block_entity(5, 6, 7, chest.clone());
+        assert_eq!(chunk.remove_block_entity(5, 6, 7), Some(chest.clone()));
+        assert_eq!(chunk.block_entity(3, 20, 4), Some(&sign));
+
+        // Replacing the block drops its block entity
+        let stone = blocks::Stone.state_id();
+        chunk.set_block_entity(8, 9, 10, chest.clone());
+        chunk.set_block_id(8, 9, 10, stone);
+        assert_eq!(chunk.block_entity(8, 9, 10), None);
+
+        let mut s = Serializer::new();
+        s.serialize_prefixed_array(&chunk.to_chunk_data(&Biomes::default()).block_entities);
+        let bytes = s.finish();
+        let mut d = Deserializer::new(&bytes);
+        assert_eq!(d.deserialize_varint().unwrap(), 2);
+        for (xz, y, block_entity) in [(0x1F, 2, &chest), (0x34, 20, &sign)] {
+            assert_eq!(d.deserialize_ubyte().unwrap(), xz);
+            assert_eq!(d.deserialize_short().unwrap(), y);
+            assert_eq!(d.deserialize_varint().unwrap(), block_entity.type_id);
+            assert_eq!(&Tag::deserialize(&mut d).unwrap(), &block_entity.data);
+        }
+    }
+
+    #[test]
+    fn test_take_changes() {
+        let stone = blocks::Stone.state_id();
+        let mut chunk = Chunk::empty(0, 2);
+        chunk.set_block_id(1, 2, 3, stone);
+        chunk.set_block_id(4, 5, 6, stone);
+        chunk.set_block_id(4, 5, 6, AIR);
+        chunk.set_block_id(7, 8, 9, AIR);
+        chunk.set_block_id(15, 31, 0, stone);
+
+        let changes = chunk.take_changes();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(
+            changes[&0],
+            [
+                BlockChange {
+                    x: 1,
+                    y: 2,
+                    z: 3,
+                    state_id: stone
+                },
+                BlockChange {
+                    x: 4,
+                    y: 5,
+                    z: 6,
+                    state_id: AIR
+                },
+            ]
+        );
+        assert_eq!(
+            changes[&1],
+            [BlockChange {
+                x: 15,
+                y: 15,
+                z: 0,
+                state_id: stone
+            }]
+        );
+        assert!(chunk.take_changes().is_empty());
+    }
+
+    #[test]
+    fn test_negative_min_y() {
+        let stone = blocks::Stone.state_id();
+        let mut chunk = Chunk::empty(-64, 24);
+        assert_eq!(chunk.y_range(), -64..320);
+        chunk.set_block_id(0, -64, 0, stone);
+        chunk.set_block_id(1, -1, 2, stone);
+        chunk.set_block_id(3, 319, 4, stone);
+        assert_eq!(chunk.block(0, -64, 0), stone);
+        assert_eq!(chunk.block(1, -1, 2), stone);
+        assert_eq!(chunk.block(1, 0, 2), AIR);
+        assert_eq!(chunk.block(3, 319, 4), stone);
+
+        let changes = chunk.take_changes();
+        assert_eq!(changes.keys().copied().collect::<Vec<_>>(), [-4, -1, 19]);
+        assert_eq!(changes[&-1][0].y, 15);
+    }
+}
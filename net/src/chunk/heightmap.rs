@@ -0,0 +1,42 @@
+//! https://minecraft.wiki/w/Heightmap
+
+use crate::registries;
+
+/// The heightmaps the client uses, sent with each chunk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Heightmap {
+    /// The highest non-air block.
+    WorldSurface,
+    /// The highest block that blocks motion or contains a fluid.
+    MotionBlocking,
+    /// Like `MotionBlocking`, but ignoring leaves.
+    MotionBlockingNoLeaves,
+}
+
+impl Heightmap {
+    pub const CLIENT: [Heightmap; 3] = [
+        Heightmap::WorldSurface,
+        Heightmap::MotionBlocking,
+        Heightmap::MotionBlockingNoLeaves,
+    ];
+
+    /// The key of the heightmap in chunk NBT.
+    pub fn name(self) -> &'static str {
+        match self {
+            Heightmap::WorldSurface => "WORLD_SURFACE",
+            Heightmap::MotionBlocking => "MOTION_BLOCKING",
+            Heightmap::MotionBlockingNoLeaves => "MOTION_BLOCKING_NO_LEAVES",
+        }
+    }
+
+    /// Whether a column's height is at least one above this block state.
+    pub fn is_opaque(self, state_id: i32) -> bool {
+        match self {
+            Heightmap::WorldSurface => !registries::is_air(state_id),
+            Heightmap::MotionBlocking => registries::is_motion_blocking(state_id),
+            Heightmap::MotionBlockingNoLeaves => {
+                registries::is_motion_blocking(state_id) && !registries::is_leaves(state_id)
+            }
+        }
+    }
+}
@@ -0,0 +1,319 @@
+//! https://minecraft.wiki/w/Minecraft_Wiki:Projects/wiki.vg_merge/Chunk_Format?oldid=2845070#Paletted_Container_structure
+
+use std::collections::HashMap;
+
+use crate::{
+    packets::{
+        deserialize::{self, Deserializer},
+        serialize::{Serialize, Serializer},
+    },
+    registries::{self, Biomes},
+};
+
+/// How a container of some kind of value is encoded, matching vanilla's
+/// `PalettedContainer.Strategy`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Strategy {
+    /// The number of values in the container.
+    pub len: usize,
+    /// Indirect palettes are widened to at least this many bits.
+    pub min_indirect_bits: u32,
+    /// Palettes needing more bits than this use global ids instead.
+    pub max_indirect_bits: u32,
+    /// The bits per entry of global ids.
+    pub direct_bits: u32,
+}
+
+/// The encoding chosen for a container's current values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Single(i32),
+    Indirect { bits: u32, palette: Vec<i32> },
+    Direct { bits: u32 },
+}
+
+/// A fixed number of global ids (block states or biomes), encoded with the
+/// smallest palette the client accepts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PalettedContainer {
+    strategy: Strategy,
+    values: Vec<i32>,
+}
+
+impl Strategy {
+    /// 16x16x16 block states per chunk section.
+    pub fn block_states() -> Self {
+        Self {
+            len: 16 * 16 * 16,
+            min_indirect_bits: 4,
+            max_indirect_bits: 8,
+            direct_bits: bits_for(registries::block_state_count()),
+        }
+    }
+
+    /// 4x4x4 biomes per chunk section, one per 4x4x4 block cell, with ids
+    /// from `biomes`.
+    pub fn biomes(biomes: &Biomes) -> Self {
+        Self {
+            len: 4 * 4 * 4,
+            min_indirect_bits: 1,
+            max_indirect_bits: 3,
+            direct_bits: bits_for(biomes.len()),
+        }
+    }
+}
+
+impl PalettedContainer {
+    pub fn filled(strategy: Strategy, value: i32) -> Self {
+        Self {
+            strategy,
+            values: vec![value; strategy.len],
+        }
+    }
+
+    /// Wraps `values`, which must have `strategy.len` entries.
+    pub fn from_values(strategy: Strategy, values: Vec<i32>) -> Self {
+        assert_eq!(values.len(), strategy.len, "Wrong number of values");
+        Self { strategy, values }
+    }
+
+    pub fn strategy(&self) -> Strategy {
+        self.strategy
+    }
+
+    pub fn values(&self) -> &[i32] {
+        &self.values
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> i32 {
+        self.values[index]
+    }
+
+    #[inline]
+    pub fn set(&mut self, index: usize, value: i32) {
+        self.values[index] = value;
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        let mut palette = Vec::new();
+        for &value in &self.values {
+            if !palette.contains(&value) {
+                palette.push(value);
+                if palette.len() > 1 << self.strategy.max_indirect_bits {
+                    break;
+                }
+            }
+        }
+
+        match bits_for(palette.len()) {
+            0 => Encoding::Single(palette.first().copied().unwrap_or(0)),
+            bits if bits <= self.strategy.max_indirect_bits => Encoding::Indirect {
+                bits: bits.max(self.strategy.min_indirect_bits),
+                palette,
+            },
+            _ => Encoding::Direct {
+                bits: self.strategy.direct_bits,
+            },
+        }
+    }
+
+    /// Reads a container written by [`PalettedContainer::serialize`].
+    pub fn deserialize(
+        d: &mut Deserializer<'_>,
+        strategy: Strategy,
+    ) -> Result<Self, deserialize::Error> {
+        let bits = d.deserialize_ubyte()? as u32;
+        let palette = if bits == 0 {
+            vec![d.deserialize_varint()?]
+        } else if bits <= strategy.max_indirect_bits {
+            let len = d.deserialize_varint()?;
+            (0..len)
+                .map(|_| d.deserialize_varint())
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            Vec::new()
+        };
+        let longs = (0..d.deserialize_varint()?)
+            .map(|_| d.deserialize_long())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let values = if bits == 0 {
+            vec![palette[0]; strategy.len]
+        } else {
+            unpack(bits, &longs, strategy.len)
+                .into_iter()
+                .map(|value| {
+                    if palette.is_empty() {
+                        value as i32
+                    } else {
+                        palette.get(value as usize).copied().unwrap_or(0)
+                    }
+                })
+                .collect()
+        };
+        Ok(Self { strategy, values })
+    }
+}
+
+impl Serialize for PalettedContainer {
+    fn serialize(&self, s: &mut Serializer) {
+        match self.encoding() {
+            Encoding::Single(value) => {
+                s.serialize_ubyte(0); // Bits per entry
+                s.serialize_varint(value);
+                s.serialize_varint(0); // Length in i64s
+            }
+            Encoding::Indirect { bits, palette } => {
+                let indices = palette
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &value)| (value, i as u64))
+                    .collect::<HashMap<_, _>>();
+                s.serialize_ubyte(bits as u8); // Bits per entry
+                s.serialize_prefixed_array_with(&palette, |s, &value| s.serialize_varint(value));
+                serialize_longs(s, &pack(bits, self.values.iter().map(|v| indices[v])));
+            }
+            Encoding::Direct { bits } => {
+                s.serialize_ubyte(bits as u8); // Bits per entry, no palette
+                serialize_longs(s, &pack(bits, self.values.iter().map(|&v| v as u64)));
+            }
+        }
+    }
+}
+
+fn serialize_longs(s: &mut Serializer, longs: &[i64]) {
+    s.serialize_varint(longs.len() as i32); // Length in i64s
+    for &long in longs {
+        s.serialize_long(long);
+    }
+}
+
+/// The bits needed to tell `count` values apart.
+pub fn bits_for(count: usize) -> u32 {
+    usize::BITS - count.saturating_sub(1).leading_zeros()
+}
+
+/// Packs `bits`-wide values into longs, starting from the least significant
+/// bits. Values never span two longs, so any leftover high bits are padding.
+pub fn pack(bits: u32, values: impl IntoIterator<Item = u64>) -> Vec<i64> {
+    let per_long = 64 / bits as usize;
+    let mut longs = Vec::new();
+    for (i, value) in values.into_iter().enumerate() {
+        if i % per_long == 0 {
+            longs.push(0);
+        }
+        let shift = (i % per_long) * bits as usize;
+        *longs.last_mut().unwrap() |= (value << shift) as i64;
+    }
+    longs
+}
+
+/// The inverse of [`pack`], reading `len` values. Missing longs read as 0.
+pub fn unpack(bits: u32, longs: &[i64], len: usize) -> Vec<u64> {
+    let per_long = 64 / bits as usize;
+    let mask = u64::MAX >> (64 - bits);
+    (0..len)
+        .map(|i| {
+            let long = longs.get(i / per_long).copied().unwrap_or(0) as u64;
+            (long >> ((i % per_long) * bits as usize)) & mask
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use crate::{
+        chunk::paletted_container::{
+            Encoding, PalettedContainer, Strategy, bits_for, pack, unpack,
+        },
+        packets::{
+            deserialize::Deserializer,
+            serialize::{Serialize, Serializer},
+        },
+    };
+
+    const STRATEGY: Strategy = Strategy {
+        len: 4096,
+        min_indirect_bits: 4,
+        max_indirect_bits: 8,
+        direct_bits: 15,
+    };
+
+    fn round_trip(container: &PalettedContainer) -> PalettedContainer {
+        let mut s = Serializer::new();
+        container.serialize(&mut s);
+        let bytes = s.finish();
+        let mut d = Deserializer::new(&bytes);
+        let decoded = PalettedContainer::deserialize(&mut d, container.strategy).unwrap();
+        assert_eq!(d.remaining(), 0);
+        decoded
+    }
+
+    #[test]
+    fn test_encodings() {
+        let mut container = PalettedContainer::filled(STRATEGY, 7);
+        assert_eq!(container.encoding(), Encoding::Single(7));
+
+        container.set(1, 9);
+        assert_eq!(
+            container.encoding(),
+            Encoding::Indirect {
+                bits: 4,
+                palette: vec![7, 9]
+            }
+        );
+
+        for i in 0..300 {
+            container.set(i, i as i32);
+        }
+        assert_eq!(container.encoding(), Encoding::Direct { bits: 15 });
+        assert_eq!(bits_for(1), 0);
+        assert_eq!(bits_for(2), 1);
+        assert_eq!(bits_for(257), 9);
+    }
+
+    #[test]
+    fn test_padding() {
+        // 5-bit entries fit 12 to a long with 4 bits of padding
+        let longs = pack(5, (0..13).map(|_| 0b11111));
+        assert_eq!(longs, vec![0x0FFF_FFFF_FFFF_FFFF, 0b11111]);
+    }
+
+    proptest! {
+        #[test]
+        fn test_pack_round_trip(
+            bits in 1u32..=32,
+            seed in proptest::collection::vec(any::<u64>(), 0..300),
+        ) {
+            let values = seed.iter().map(|v| v & (u64::MAX >> (64 - bits))).collect::<Vec<_>>();
+            let longs = pack(bits, values.iter().copied());
+            prop_assert_eq!(longs.len(), values.len().div_ceil(64 / bits as usize));
+            prop_assert_eq!(unpack(bits, &longs, values.len()), values);
+        }
+
+        #[test]
+        fn test_container_round_trip(
+            distinct in 1i32..2000,
+            seed in proptest::collection::vec(any::<i32>(), 4096),
+        ) {
+            let values = seed.iter().map(|v| v.rem_euclid(distinct)).collect();
+            let container = PalettedContainer::from_values(STRATEGY, values);
+            prop_assert_eq!(round_trip(&container), container);
+        }
+
+        #[test]
+        fn test_biome_round_trip(seed in proptest::collection::vec(0i32..20, 64)) {
+            let strategy = Strategy {
+                len: 64,
+                min_indirect_bits: 1,
+                max_indirect_bits: 3,
+                direct_bits: 5,
+            };
+            let container = PalettedContainer::from_values(strategy, seed);
+            prop_assert_eq!(round_trip(&container), container);
+        }
+    }
+}
@@ -1,9 +1,22 @@
-use std::{io, time::Duration};
+use std::{
+    io::{self, Read, Write},
+    sync::Arc,
+    time::Duration,
+};
 
+use aes::{
+    Aes128,
+    cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit, generic_array::GenericArray},
+};
+use flate2::{Compression, read::ZlibDecoder, write::ZlibEncoder};
 use snafu::prelude::*;
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
-    net::TcpStream,
+    net::{
+        TcpStream,
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+    },
+    sync::{Notify, mpsc},
 };
 
 use crate::packets::{
@@ -15,9 +28,27 @@ use crate::packets::{
 };
 
 pub struct Connection {
-    stream: TcpStream,
+    reader: OwnedReadHalf,
+    sender: Sender,
     recv_buf: Vec<u8>,
     state: State,
+    compression_threshold: Option<usize>,
+    decryptor: Option<cfb8::Decryptor<Aes128>>,
+}
+
+/// Handle to a connection's outbound queue. Packets are framed, compressed,
+/// encrypted and written by a dedicated task, so a slow socket only holds up
+/// its own queue.
+#[derive(Clone)]
+pub(crate) struct Sender {
+    queue: mpsc::Sender<Outgoing>,
+    abort: Arc<Notify>,
+}
+
+enum Outgoing {
+    Packet(Arc<[u8]>),
+    SetCompression(usize),
+    EnableEncryption(Box<cfb8::Encryptor<Aes128>>),
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -46,6 +77,28 @@ pub enum ServerboundPacket {
 pub enum Error {
     #[snafu(display("Client timed out"))]
     ClientTimedOut,
+    #[snafu(display("Connection closed"))]
+    ConnectionClosed,
+    #[snafu(display("Outbound queue is full"))]
+    QueueFull,
+    #[snafu(display(
+        "Badly compressed packet: size of {data_length} is below threshold of {threshold}"
+    ))]
+    BelowCompressionThreshold {
+        data_length: usize,
+        threshold: usize,
+    },
+    #[snafu(display("Badly compressed packet: size of {data_length} is above protocol maximum"))]
+    AboveProtocolMaximum { data_length: usize },
+    #[snafu(display(
+        "Badly compressed packet: expected {data_length} bytes, decompressed {actual_length}"
+    ))]
+    DataLengthMismatch {
+        data_length: usize,
+        actual_length: usize,
+    },
+    #[snafu(display("Invalid shared secret length: {length}"))]
+    InvalidSharedSecret { length: usize },
     #[snafu(transparent)]
     DeserializeError { source: deserialize::Error },
     #[snafu(transparent)]
@@ -53,44 +106,82 @@ pub enum Error {
 }
 
 const RECV_TIMEOUT: Duration = Duration::from_secs(10);
+/// Packets that may be waiting to be written before a connection is
+/// considered too slow to keep up.
+const QUEUE_CAPACITY: usize = 4096;
+/// Largest uncompressed packet the client will accept.
+const MAX_PACKET_SIZE: usize = 8_388_608;
 
 impl Connection {
     pub fn new(stream: TcpStream) -> Self {
+        let (reader, writer) = stream.into_split();
+        let (queue, outgoing) = mpsc::channel(QUEUE_CAPACITY);
+        let abort = Arc::new(Notify::new());
+        tokio::spawn(write_loop(writer, outgoing, abort.clone()));
+
         Connection {
-            stream,
+            reader,
+            sender: Sender { queue, abort },
             recv_buf: Vec::new(),
             state: State::Handshake,
+            compression_threshold: None,
+            decryptor: None,
         }
     }
 
-    async fn send_raw(&mut self, packet: &[u8]) -> Result<(), Error> {
-        let mut s = Serializer::new();
-        s.serialize_prefixed_byte_array(packet);
-        let write_buf = s.finish();
-
-        self.stream.write_all(&write_buf).await?;
+    /// Wraps the stream in AES-128-CFB8, using the shared secret as both key
+    /// and IV. Takes effect from the next byte sent or received.
+    pub async fn enable_encryption(&mut self, shared_secret: &[u8]) -> Result<(), Error> {
+        let length = shared_secret.len();
+        let new_err = |_| InvalidSharedSecretSnafu { length }.build();
+        let encryptor =
+            cfb8::Encryptor::new_from_slices(shared_secret, shared_secret).map_err(new_err)?;
+        self.decryptor =
+            Some(cfb8::Decryptor::new_from_slices(shared_secret, shared_secret).map_err(new_err)?);
+        self.sender
+            .push(Outgoing::EnableEncryption(Box::new(encryptor)))
+            .await
+    }
 
+    /// Sends `SetCompression` and switches both directions to the compressed
+    /// packet format. Packets of at least `threshold` bytes are zlib-compressed.
+    pub async fn set_compression(&mut self, threshold: usize) -> Result<(), Error> {
+        self.send(login::clientbound::Packet::LoginCompression {
+            threshold: threshold as i32,
+        })
+        .await?;
+        self.sender
+            .push(Outgoing::SetCompression(threshold))
+            .await?;
+        self.compression_threshold = Some(threshold);
         Ok(())
     }
 
+    pub(crate) fn sender(&self) -> Sender {
+        self.sender.clone()
+    }
+
     async fn recv_raw(&mut self) -> Result<Vec<u8>, Error> {
         loop {
             let mut d = Deserializer::new(&self.recv_buf);
-            if let Ok(packet) = d.deserialize_prefixed_byte_array() {
-                let packet = packet.to_owned();
-
+            if let Ok(frame) = d.deserialize_prefixed_byte_array() {
                 let consumed = self.recv_buf.len() - d.take_remaining().len();
                 self.recv_buf.drain(..consumed);
 
-                return Ok(packet);
+                return decode_frame(frame, self.compression_threshold);
             }
 
-            match tokio::time::timeout(RECV_TIMEOUT, self.stream.read_buf(&mut self.recv_buf)).await
+            let read_start = self.recv_buf.len();
+            match tokio::time::timeout(RECV_TIMEOUT, self.reader.read_buf(&mut self.recv_buf)).await
             {
                 Err(_) => ClientTimedOutSnafu.fail()?,
                 Ok(Err(e)) => return Err(e.into()),
+                Ok(Ok(0)) => ConnectionClosedSnafu.fail()?,
                 Ok(Ok(_)) => {}
             }
+            if let Some(decryptor) = &mut self.decryptor {
+                decrypt(decryptor, &mut self.recv_buf[read_start..]);
+            }
         }
     }
 
@@ -101,13 +192,7 @@ impl Connection {
             "Invalid state for packet",
         );
 
-        let mut s = Serializer::new();
-        packet.serialize(&mut s);
-        let raw = s.finish();
-
-        self.send_raw(&raw).await?;
-
-        Ok(())
+        self.sender.send(packet).await
     }
 
     pub async fn recv(&mut self) -> Result<ServerboundPacket, Error> {
@@ -137,11 +222,8 @@ impl Connection {
             State::Login => {
                 let packet = deserialize::<login::serverbound::Packet>(&raw)?;
 
-                match &packet {
-                    login::serverbound::Packet::LoginAcknowledged => {
-                        self.state = State::Configuration;
-                    }
-                    _ => {}
+                if let login::serverbound::Packet::LoginAcknowledged = &packet {
+                    self.state = State::Configuration;
                 }
 
                 Ok(ServerboundPacket::Login(packet))
@@ -149,11 +231,8 @@ impl Connection {
             State::Configuration => {
                 let packet = deserialize::<configuration::serverbound::Packet>(&raw)?;
 
-                match &packet {
-                    configuration::serverbound::Packet::FinishConfiguration => {
-                        self.state = State::Play;
-                    }
-                    _ => {}
+                if let configuration::serverbound::Packet::FinishConfiguration = &packet {
+                    self.state = State::Play;
                 }
 
                 Ok(ServerboundPacket::Configuration(packet))
@@ -166,7 +245,7 @@ impl Connection {
         }
     }
 
-    pub async fn disconnect(mut self, reason: &str) -> Result<(), Error> {
+    pub async fn disconnect(&mut self, reason: &str) -> Result<(), Error> {
         match self.state {
             State::Handshake | State::Status => {}
             State::Login => {
@@ -186,6 +265,175 @@ impl Connection {
     }
 }
 
+impl Sender {
+    /// Queues a packet, waiting for space if the queue is full.
+    pub(crate) async fn send(&self, packet: impl ClientboundPacket) -> Result<(), Error> {
+        self.send_raw(serialize(packet)).await
+    }
+
+    pub(crate) async fn send_raw(&self, packet: Arc<[u8]>) -> Result<(), Error> {
+        self.push(Outgoing::Packet(packet)).await
+    }
+
+    /// Queues an already serialized packet without waiting. A connection that
+    /// has fallen too far behind is aborted rather than holding up the caller.
+    pub(crate) fn try_send_raw(&self, packet: Arc<[u8]>) -> Result<(), Error> {
+        match self.queue.try_send(Outgoing::Packet(packet)) {
+            Ok(()) => Ok(()),
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                self.abort.notify_one();
+                QueueFullSnafu.fail()
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => ConnectionClosedSnafu.fail(),
+        }
+    }
+
+    /// How many more packets can be queued without waiting.
+    pub(crate) fn capacity(&self) -> usize {
+        self.queue.capacity()
+    }
+
+    async fn push(&self, outgoing: Outgoing) -> Result<(), Error> {
+        self.queue
+            .send(outgoing)
+            .await
+            .ok()
+            .context(ConnectionClosedSnafu)
+    }
+}
+
+pub(crate) fn serialize(packet: impl Serialize) -> Arc<[u8]> {
+    let mut s = Serializer::new();
+    packet.serialize(&mut s);
+    s.finish().into()
+}
+
+async fn write_loop(
+    mut writer: OwnedWriteHalf,
+    mut outgoing: mpsc::Receiver<Outgoing>,
+    abort: Arc<Notify>,
+) {
+    let mut compression_threshold = None;
+    let mut encryptor = None;
+    loop {
+        let result = tokio::select! {
+            _ = abort.notified() => return,
+            next = outgoing.recv() => match next {
+                Some(Outgoing::Packet(packet)) => {
+                    write_packet(&mut writer, &packet, compression_threshold, encryptor.as_deref_mut()).await
+                }
+                Some(Outgoing::SetCompression(threshold)) => {
+                    compression_threshold = Some(threshold);
+                    Ok(())
+                }
+                Some(Outgoing::EnableEncryption(new_encryptor)) => {
+                    encryptor = Some(new_encryptor);
+                    Ok(())
+                }
+                None => return,
+            },
+        };
+        if let Err(err) = result {
+            eprintln!("Error writing to connection: {err}");
+            return;
+        }
+    }
+}
+
+async fn write_packet(
+    writer: &mut OwnedWriteHalf,
+    packet: &[u8],
+    compression_threshold: Option<usize>,
+    encryptor: Option<&mut cfb8::Encryptor<Aes128>>,
+) -> Result<(), Error> {
+    let mut write_buf = encode_frame(packet, compression_threshold)?;
+    if let Some(encryptor) = encryptor {
+        encrypt(encryptor, &mut write_buf);
+    }
+
+    writer.write_all(&write_buf).await?;
+
+    Ok(())
+}
+
+fn encrypt(encryptor: &mut cfb8::Encryptor<Aes128>, buf: &mut [u8]) {
+    for byte in buf {
+        encryptor.encrypt_block_mut(GenericArray::from_mut_slice(std::slice::from_mut(byte)));
+    }
+}
+
+fn decrypt(decryptor: &mut cfb8::Decryptor<Aes128>, buf: &mut [u8]) {
+    for byte in buf {
+        decryptor.decrypt_block_mut(GenericArray::from_mut_slice(std::slice::from_mut(byte)));
+    }
+}
+
+/// Wraps a packet in its length prefix, using the compressed packet format once
+/// a compression threshold has been set.
+///
+/// https://minecraft.wiki/w/Java_Edition_protocol/Packets?oldid=2874788#Packet_format
+fn encode_frame(packet: &[u8], compression_threshold: Option<usize>) -> io::Result<Vec<u8>> {
+    let mut s = Serializer::new();
+    match compression_threshold {
+        None => s.serialize_prefixed_byte_array(packet),
+        Some(threshold) => {
+            let mut body = Serializer::new();
+            if packet.len() >= threshold {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(packet)?;
+                body.serialize_varint(packet.len() as i32);
+                body.serialize_byte_array(&encoder.finish()?);
+            } else {
+                body.serialize_varint(0);
+                body.serialize_byte_array(packet);
+            }
+            s.serialize_prefixed_byte_array(&body.finish());
+        }
+    }
+    Ok(s.finish())
+}
+
+/// Reverses [`encode_frame`] for a frame whose length prefix has already been
+/// stripped.
+fn decode_frame(frame: Vec<u8>, compression_threshold: Option<usize>) -> Result<Vec<u8>, Error> {
+    let Some(threshold) = compression_threshold else {
+        return Ok(frame);
+    };
+
+    let mut d = Deserializer::new(&frame);
+    let data_length = d.deserialize_varint()? as usize;
+    let data = d.take_remaining();
+    if data_length == 0 {
+        return Ok(data.to_owned());
+    }
+
+    ensure!(
+        data_length >= threshold,
+        BelowCompressionThresholdSnafu {
+            data_length,
+            threshold
+        }
+    );
+    ensure!(
+        data_length <= MAX_PACKET_SIZE,
+        AboveProtocolMaximumSnafu { data_length }
+    );
+
+    let mut packet = Vec::with_capacity(data_length);
+    ZlibDecoder::new(data)
+        .take(data_length as u64 + 1)
+        .read_to_end(&mut packet)?;
+    ensure!(
+        packet.len() == data_length,
+        DataLengthMismatchSnafu {
+            data_length,
+            actual_length: packet.len(),
+        }
+    );
+
+    Ok(packet)
+}
+
 fn deserialize<'de, T: Deserialize<'de>>(bytes: &'de [u8]) -> Result<T, deserialize::Error> {
     let mut d = Deserializer::new(bytes);
     let result = T::deserialize(&mut d)?;
@@ -220,3 +468,112 @@ impl ClientboundPacket for play::clientbound::Packet<'_> {
         State::Play
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use aes::cipher::KeyIvInit;
+
+    use crate::{
+        connection::{decode_frame, decrypt, encode_frame, encrypt},
+        packets::deserialize::Deserializer,
+    };
+
+    fn strip_length(frame: &[u8]) -> Vec<u8> {
+        let mut d = Deserializer::new(frame);
+        let packet = d.deserialize_prefixed_byte_array().unwrap();
+        d.finish().unwrap();
+        packet
+    }
+
+    fn chunk_packet() -> Vec<u8> {
+        let mut packet = vec![0x28];
+        packet.extend((0..299).map(|i| (i * 7 % 13) as u8));
+        packet
+    }
+
+    #[test]
+    fn test_uncompressed_frame() {
+        let keep_alive = [0x27, 0, 0, 0, 0, 0, 0, 0, 0];
+        let frame = [0x09, 0x27, 0, 0, 0, 0, 0, 0, 0, 0];
+
+        assert_eq!(encode_frame(&keep_alive, None).unwrap(), frame);
+        assert_eq!(
+            decode_frame(strip_length(&frame), None).unwrap(),
+            keep_alive
+        );
+    }
+
+    #[test]
+    fn test_compressed_frame_below_threshold() {
+        let keep_alive = [0x27, 0, 0, 0, 0, 0, 0, 0, 0];
+        let frame = [0x0A, 0x00, 0x27, 0, 0, 0, 0, 0, 0, 0, 0];
+
+        assert_eq!(encode_frame(&keep_alive, Some(256)).unwrap(), frame);
+        assert_eq!(
+            decode_frame(strip_length(&frame), Some(256)).unwrap(),
+            keep_alive
+        );
+    }
+
+    #[test]
+    fn test_compressed_frame_above_threshold() {
+        // Captured from a zlib stream at the default compression level
+        let frame = [
+            0x1c, 0xac, 0x02, 0x78, 0x9c, 0xd3, 0x60, 0x60, 0x67, 0xe4, 0x60, 0xe2, 0x64, 0xe6,
+            0x62, 0xe1, 0x66, 0xe5, 0x61, 0x1b, 0xe5, 0xe0, 0xe3, 0x00, 0x00, 0x43, 0x47, 0x07,
+            0x2b,
+        ];
+
+        assert_eq!(
+            decode_frame(strip_length(&frame), Some(256)).unwrap(),
+            chunk_packet()
+        );
+
+        let encoded = encode_frame(&chunk_packet(), Some(256)).unwrap();
+        assert_eq!(encoded[1..3], [0xac, 0x02]);
+        assert_eq!(
+            decode_frame(strip_length(&encoded), Some(256)).unwrap(),
+            chunk_packet()
+        );
+    }
+
+    #[test]
+    fn test_compressed_frame_errors() {
+        let frame = [
+            0x1c, 0xac, 0x02, 0x78, 0x9c, 0xd3, 0x60, 0x60, 0x67, 0xe4, 0x60, 0xe2, 0x64, 0xe6,
+            0x62, 0xe1, 0x66, 0xe5, 0x61, 0x1b, 0xe5, 0xe0, 0xe3, 0x00, 0x00, 0x43, 0x47, 0x07,
+            0x2b,
+        ];
+        assert!(decode_frame(strip_length(&frame), Some(512)).is_err());
+
+        let mut wrong_length = strip_length(&frame);
+        wrong_length[0] = 0xad;
+        assert!(decode_frame(wrong_length, Some(256)).is_err());
+    }
+
+    #[test]
+    fn test_cipher() {
+        // Reference vector from the cfb8 crate documentation
+        let key = [0x42; 16];
+        let iv = [0x24; 16];
+        let plaintext = *b"hello world! this is my plaintext.";
+        let ciphertext = [
+            0x33, 0xb3, 0x56, 0xce, 0x91, 0x84, 0x29, 0x0c, 0x4c, 0x8f, 0xac, 0xc1, 0xc0, 0xb1,
+            0xf9, 0x18, 0xd5, 0x47, 0x5a, 0xeb, 0x75, 0xb8, 0x8c, 0x16, 0x1c, 0xa6, 0x5b, 0xdf,
+            0x05, 0xc7, 0x13, 0x7f, 0xf4, 0xb0,
+        ];
+
+        let mut encryptor = cfb8::Encryptor::new(&key.into(), &iv.into());
+        let mut decryptor = cfb8::Decryptor::new(&key.into(), &iv.into());
+
+        // Split to check that state carries across calls
+        let mut buf = plaintext;
+        encrypt(&mut encryptor, &mut buf[..10]);
+        encrypt(&mut encryptor, &mut buf[10..]);
+        assert_eq!(buf, ciphertext);
+
+        decrypt(&mut decryptor, &mut buf[..20]);
+        decrypt(&mut decryptor, &mut buf[20..]);
+        assert_eq!(buf, plaintext);
+    }
+}
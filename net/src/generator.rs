@@ -0,0 +1,45 @@
+//! Terrain for chunks that haven't been loaded, generated as players need
+//! them.
+
+mod flat;
+mod noise;
+
+pub use flat::{PRESETS, Superflat};
+pub use noise::NoiseGenerator;
+
+use snafu::prelude::*;
+
+use crate::{
+    chunk::Chunk,
+    registries::{Biomes, BlockStateNotFound},
+};
+
+pub trait ChunkGenerator: Send + Sync {
+    /// Generates the chunk at the given chunk position, with
+    /// `subchunk_count` sections from `min_y` and biome ids from `biomes`.
+    /// Must give the same chunk every time it's called with the same
+    /// arguments.
+    fn generate(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        min_y: i32,
+        subchunk_count: u8,
+        biomes: &Biomes,
+    ) -> Chunk;
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(transparent)]
+    BlockStateNotFound { source: BlockStateNotFound },
+    #[snafu(display("Invalid superflat layer: {layer}"))]
+    InvalidLayer { layer: String },
+}
+
+/// Sets the biome of every 4x4x4 cell in a column of cells.
+fn set_column_biome(chunk: &mut Chunk, x: u8, z: u8, biome_id: i32) {
+    for y in chunk.y_range().step_by(4) {
+        chunk.set_biome(x, y, z, biome_id);
+    }
+}
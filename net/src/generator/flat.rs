@@ -0,0 +1,164 @@
+//! https://minecraft.wiki/w/Superflat
+
+use snafu::prelude::*;
+
+use crate::{
+    chunk::{AIR, Chunk},
+    generator::{ChunkGenerator, Error, InvalidLayerSnafu, set_column_biome},
+    registries::{self, Biomes},
+};
+
+/// The presets offered by the vanilla Superflat customization screen, as
+/// names and preset codes.
+pub const PRESETS: &[(&str, &str)] = &[
+    (
+        "Classic Flat",
+        "minecraft:bedrock,2*minecraft:dirt,minecraft:grass_block;minecraft:plains",
+    ),
+    (
+        "Tunnelers' Dream",
+        "minecraft:bedrock,230*minecraft:stone,5*minecraft:dirt,minecraft:grass_block;minecraft:windswept_hills",
+    ),
+    (
+        "Water World",
+        "minecraft:bedrock,5*minecraft:stone,5*minecraft:dirt,5*minecraft:sand,90*minecraft:water;minecraft:deep_ocean",
+    ),
+    (
+        "Overworld",
+        "minecraft:bedrock,59*minecraft:stone,3*minecraft:dirt,minecraft:grass_block;minecraft:plains",
+    ),
+    (
+        "Snowy Kingdom",
+        "minecraft:bedrock,59*minecraft:stone,3*minecraft:dirt,minecraft:grass_block,minecraft:snow;minecraft:snowy_plains",
+    ),
+    (
+        "Bottomless Pit",
+        "2*minecraft:cobblestone,3*minecraft:dirt,minecraft:grass_block;minecraft:plains",
+    ),
+    (
+        "Desert",
+        "minecraft:bedrock,3*minecraft:stone,52*minecraft:sandstone,8*minecraft:sand;minecraft:desert",
+    ),
+    (
+        "Redstone Ready",
+        "minecraft:bedrock,3*minecraft:stone,116*minecraft:sandstone;minecraft:desert",
+    ),
+    ("The Void", "minecraft:air;minecraft:the_void"),
+];
+
+/// Layers of blocks stacked from the bottom of the world, the same in every
+/// chunk.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Superflat {
+    /// The block state of each layer, from the bottom.
+    layers: Vec<i32>,
+    biome: String,
+}
+
+impl Superflat {
+    /// Takes layers as block state ids and heights, from the bottom, and the
+    /// id of the biome, which is plains if the server doesn't register it.
+    pub fn new(layers: &[(i32, u16)], biome: &str) -> Self {
+        Self {
+            layers: layers
+                .iter()
+                .flat_map(|&(state_id, height)| std::iter::repeat_n(state_id, height as usize))
+                .collect(),
+            biome: biome.to_owned(),
+        }
+    }
+
+    /// Parses a preset code such as
+    /// `minecraft:bedrock,2*minecraft:dirt,minecraft:grass_block;minecraft:plains`.
+    /// Layers use their block's default state.
+    pub fn from_preset(preset: &str) -> Result<Self, Error> {
+        let (layers, biome) = preset.split_once(';').unwrap_or((preset, ""));
+        let layers = layers
+            .split(',')
+            .map(|layer| {
+                let layer = layer.trim();
+                let (height, id) = match layer.split_once('*') {
+                    Some((height, id)) => {
+                        let height = height.parse().ok().context(InvalidLayerSnafu { layer })?;
+                        (height, id)
+                    }
+                    None => (1, layer),
+                };
+                ensure!(!id.is_empty(), InvalidLayerSnafu { layer });
+                Ok((registries::default_block_state(id)?.id, height))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Self::new(&layers, biome.trim()))
+    }
+
+    /// The y of the first air block above the layers, in a world starting
+    /// at `min_y`.
+    pub fn surface(&self, min_y: i32) -> i32 {
+        min_y + self.layers.len() as i32
+    }
+}
+
+impl ChunkGenerator for Superflat {
+    fn generate(
+        &self,
+        _chunk_x: i32,
+        _chunk_z: i32,
+        min_y: i32,
+        subchunk_count: u8,
+        biomes: &Biomes,
+    ) -> Chunk {
+        let mut chunk = Chunk::from_fn(min_y, subchunk_count, |_, y, _| {
+            self.layers
+                .get((y - min_y) as usize)
+                .copied()
+                .unwrap_or(AIR)
+        });
+        let biome_id = biomes
+            .protocol_id(&self.biome)
+            .unwrap_or_else(|_| biomes.plains());
+        for z in (0..16).step_by(4) {
+            for x in (0..16).step_by(4) {
+                set_column_biome(&mut chunk, x, z, biome_id);
+            }
+        }
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chunk::AIR,
+        generator::{ChunkGenerator, PRESETS, Superflat},
+        registries::{Biomes, blocks},
+    };
+
+    #[test]
+    fn test_classic_flat() {
+        let superflat = Superflat::from_preset(PRESETS[0].1).unwrap();
+        assert_eq!(superflat.surface(-64), -60);
+
+        let biomes = Biomes::default();
+        let chunk = superflat.generate(-3, 7, -64, 1, &biomes);
+        assert_eq!(chunk.block(0, -64, 0), blocks::Bedrock.state_id());
+        assert_eq!(chunk.block(5, -62, 9), blocks::Dirt.state_id());
+        assert_eq!(
+            chunk.block(15, -61, 15),
+            blocks::GrassBlock::default().state_id()
+        );
+        assert_eq!(chunk.block(15, -60, 15), AIR);
+        assert_eq!(
+            chunk.biome(8, -56, 8),
+            biomes.protocol_id("minecraft:plains").unwrap()
+        );
+
+        // Biomes are looked up when generating, with the server's ids
+        let desert = Superflat::from_preset("minecraft:stone;minecraft:desert").unwrap();
+        let biomes = Biomes::new(vec!["a:b".to_owned(), "minecraft:desert".to_owned()]);
+        let chunk = desert.generate(0, 0, -64, 1, &biomes);
+        assert_eq!(chunk.biome(0, -64, 0), 1);
+
+        assert!(Superflat::from_preset("x*minecraft:dirt").is_err());
+        assert!(Superflat::from_preset("minecraft:not_a_block").is_err());
+    }
+}
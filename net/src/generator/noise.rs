@@ -0,0 +1,245 @@
+//! Hills from fractal value noise, with a second noise choosing between a
+//! temperate and a snowy biome.
+
+use crate::{
+    chunk::{AIR, Chunk},
+    generator::{ChunkGenerator, set_column_biome},
+    registries::{Biomes, blocks},
+};
+
+/// Rolling terrain that is the same for the same seed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NoiseGenerator {
+    seed: u64,
+    sea_level: i32,
+    base_height: f64,
+    amplitude: f64,
+    blocks: Blocks,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Blocks {
+    bedrock: i32,
+    stone: i32,
+    dirt: i32,
+    grass_block: i32,
+    snow_block: i32,
+    sand: i32,
+    water: i32,
+}
+
+/// The horizontal distance between hills, roughly.
+const TERRAIN_SCALE: f64 = 96.0;
+const BIOME_SCALE: f64 = 384.0;
+/// Below this temperature noise, columns are snowy.
+const SNOW_TEMPERATURE: f64 = -0.25;
+
+impl NoiseGenerator {
+    /// Terrain around y 64 with the sea at y 62, like vanilla's.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            sea_level: 62,
+            base_height: 64.0,
+            amplitude: 24.0,
+            blocks: Blocks {
+                bedrock: blocks::Bedrock.state_id(),
+                stone: blocks::Stone.state_id(),
+                dirt: blocks::Dirt.state_id(),
+                grass_block: blocks::GrassBlock::default().state_id(),
+                snow_block: blocks::SnowBlock.state_id(),
+                sand: blocks::Sand.state_id(),
+                water: blocks::Water::default().state_id(),
+            },
+        }
+    }
+
+    /// Sets the highest y filled with water, where there's no terrain.
+    pub fn with_sea_level(mut self, sea_level: i32) -> Self {
+        self.sea_level = sea_level;
+        self
+    }
+
+    /// Sets the average surface height and how far hills rise above and
+    /// valleys sink below it.
+    pub fn with_terrain(mut self, base_height: f64, amplitude: f64) -> Self {
+        self.base_height = base_height;
+        self.amplitude = amplitude;
+        self
+    }
+
+    /// The y of the first block above the ground, before clamping to the
+    /// world's height.
+    pub fn surface(&self, x: i32, z: i32) -> i32 {
+        let noise = fractal_noise(
+            self.seed,
+            x as f64 / TERRAIN_SCALE,
+            z as f64 / TERRAIN_SCALE,
+            4,
+        );
+        (self.base_height + self.amplitude * noise).round() as i32
+    }
+
+    fn is_snowy(&self, x: i32, z: i32) -> bool {
+        let temperature = fractal_noise(
+            self.seed.wrapping_add(1),
+            x as f64 / BIOME_SCALE,
+            z as f64 / BIOME_SCALE,
+            2,
+        );
+        temperature < SNOW_TEMPERATURE
+    }
+}
+
+impl ChunkGenerator for NoiseGenerator {
+    fn generate(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+        min_y: i32,
+        subchunk_count: u8,
+        biomes: &Biomes,
+    ) -> Chunk {
+        let blocks = &self.blocks;
+        let max_y = min_y + subchunk_count as i32 * 16;
+        let sea_level = self.sea_level;
+
+        // The surface height, top block and filler below it of each column
+        let mut columns = Vec::with_capacity(16 * 16);
+        let mut snowy = Vec::with_capacity(16 * 16);
+        for z in 0..16 {
+            for x in 0..16 {
+                let world_x = chunk_x * 16 + x;
+                let world_z = chunk_z * 16 + z;
+                let surface = self.surface(world_x, world_z).clamp(min_y + 1, max_y);
+                let is_snowy = self.is_snowy(world_x, world_z);
+                let column = if surface <= sea_level + 1 {
+                    (surface, blocks.sand, blocks.sand)
+                } else if is_snowy {
+                    (surface, blocks.snow_block, blocks.dirt)
+                } else {
+                    (surface, blocks.grass_block, blocks.dirt)
+                };
+                columns.push(column);
+                snowy.push(is_snowy);
+            }
+        }
+
+        let mut chunk = Chunk::from_fn(min_y, subchunk_count, |x, y, z| {
+            let (surface, top, filler) = columns[z as usize * 16 + x as usize];
+            match y {
+                y if y == min_y => blocks.bedrock,
+                y if y == surface - 1 => top,
+                y if y >= surface - 4 && y < surface => filler,
+                y if y < surface => blocks.stone,
+                y if y <= sea_level => blocks.water,
+                _ => AIR,
+            }
+        });
+        let plains = biomes.plains();
+        let snowy_taiga = biomes
+            .protocol_id("minecraft:snowy_taiga")
+            .unwrap_or(plains);
+        for z in (0..16).step_by(4) {
+            for x in (0..16).step_by(4) {
+                let biome_id = if snowy[z as usize * 16 + x as usize] {
+                    snowy_taiga
+                } else {
+                    plains
+                };
+                set_column_biome(&mut chunk, x, z, biome_id);
+            }
+        }
+        chunk
+    }
+}
+
+/// Octaves of value noise, each twice the frequency and half the amplitude
+/// of the last, scaled to about -1..1.
+fn fractal_noise(seed: u64, x: f64, z: f64, octaves: u32) -> f64 {
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut max = 0.0;
+    for octave in 0..octaves {
+        let frequency = (1 << octave) as f64;
+        total += amplitude
+            * value_noise(
+                seed.wrapping_add(octave as u64),
+                x * frequency,
+                z * frequency,
+            );
+        max += amplitude;
+        amplitude /= 2.0;
+    }
+    total / max
+}
+
+/// Random values at integer coordinates, smoothly interpolated between.
+fn value_noise(seed: u64, x: f64, z: f64) -> f64 {
+    let (x0, z0) = (x.floor(), z.floor());
+    let corner = |dx: f64, dz: f64| lattice_value(seed, (x0 + dx) as i64, (z0 + dz) as i64);
+    let smooth = |t: f64| t * t * (3.0 - 2.0 * t);
+    let (tx, tz) = (smooth(x - x0), smooth(z - z0));
+    let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+    lerp(
+        lerp(corner(0.0, 0.0), corner(1.0, 0.0), tx),
+        lerp(corner(0.0, 1.0), corner(1.0, 1.0), tx),
+        tz,
+    )
+}
+
+/// A value in -1..1 from a hash of the seed and coordinates.
+fn lattice_value(seed: u64, x: i64, z: i64) -> f64 {
+    // SplitMix64's finalizer
+    let mut h = seed
+        ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (z as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+    h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    h ^= h >> 31;
+    (h >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chunk::AIR,
+        generator::{ChunkGenerator, NoiseGenerator},
+        registries::{Biomes, blocks},
+    };
+
+    #[test]
+    fn test_noise_generator() {
+        let biomes = Biomes::default();
+        let generator = NoiseGenerator::new(42);
+        let chunk = generator.generate(3, -5, -16, 9, &biomes);
+        let data = chunk.chunk_data(&biomes);
+        assert_eq!(
+            data,
+            generator
+                .generate(3, -5, -16, 9, &biomes)
+                .chunk_data(&biomes)
+        );
+        assert_ne!(
+            data,
+            NoiseGenerator::new(43)
+                .generate(3, -5, -16, 9, &biomes)
+                .chunk_data(&biomes)
+        );
+
+        let bedrock = blocks::Bedrock.state_id();
+        let water = blocks::Water::default().state_id();
+        for z in 0..16 {
+            for x in 0..16 {
+                assert_eq!(chunk.block(x, -16, z), bedrock);
+                let surface = generator.surface(3 * 16 + x as i32, -5 * 16 + z as i32);
+                let surface = surface.clamp(-15, 128);
+                assert_ne!(chunk.block(x, surface - 1, z), AIR);
+                assert_ne!(chunk.block(x, surface - 1, z), water);
+                if surface < 128 {
+                    assert_eq!(chunk.block(x, surface.max(63), z), AIR);
+                }
+            }
+        }
+    }
+}
@@ -1,8 +1,15 @@
 // https://minecraft.wiki/w/Java_Edition_protocol?oldid=2874788
 
+pub mod anvil;
+pub mod auth;
 pub mod chunk;
 pub mod connection;
+pub mod generator;
+pub mod light;
 pub mod nbt;
 pub mod packets;
 pub mod registries;
+pub mod schematic;
 pub mod server;
+pub mod volume;
+pub mod world;
@@ -0,0 +1,384 @@
+//! Sky and block light for chunks, sent alongside them in `LightData`.
+//!
+//! https://minecraft.wiki/w/Light
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt,
+};
+
+use crate::{
+    chunk::Chunk,
+    packets::play::clientbound::LightData,
+    registries::{light_dampening, light_emission},
+};
+
+const MAX_LIGHT: u8 = 15;
+
+const NEIGHBOURS: [(i32, i32, i32); 6] = [
+    (-1, 0, 0),
+    (1, 0, 0),
+    (0, -1, 0),
+    (0, 1, 0),
+    (0, 0, -1),
+    (0, 0, 1),
+];
+
+/// A light level per block of a 16x16x16 section, packed two to a byte with
+/// the even block in the low nibble.
+#[derive(Clone, PartialEq, Eq)]
+pub struct NibbleArray(Box<[u8; 2048]>);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Channel {
+    Sky,
+    Block,
+}
+
+/// Working light levels for one chunk, indexed like `Chunk` blocks.
+struct Column<'a> {
+    chunk: &'a Chunk,
+    sky: Vec<u8>,
+    block: Vec<u8>,
+}
+
+struct Engine<'a> {
+    columns: HashMap<(i32, i32), Column<'a>>,
+    has_skylight: bool,
+}
+
+impl NibbleArray {
+    pub fn filled(level: u8) -> Self {
+        Self(Box::new([level << 4 | level; 2048]))
+    }
+
+    #[inline]
+    pub fn get(&self, x: u8, y: u8, z: u8) -> u8 {
+        let index = nibble_index(x, y, z);
+        (self.0[index / 2] >> (index % 2 * 4)) & 0x0F
+    }
+
+    #[inline]
+    pub fn set(&mut self, x: u8, y: u8, z: u8, level: u8) {
+        let index = nibble_index(x, y, z);
+        let shift = index % 2 * 4;
+        let byte = &mut self.0[index / 2];
+        *byte = (*byte & !(0x0F << shift)) | (level & 0x0F) << shift;
+    }
+
+    /// Whether every level is 0, which the client is told with a mask bit
+    /// instead of an array.
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&b| b == 0)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 2048] {
+        &self.0
+    }
+}
+
+impl Default for NibbleArray {
+    fn default() -> Self {
+        Self::filled(0)
+    }
+}
+
+impl fmt::Debug for NibbleArray {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let max = self.0.iter().map(|b| (b & 0x0F).max(b >> 4)).max();
+        write!(f, "NibbleArray {{ max: {} }}", max.unwrap_or(0))
+    }
+}
+
+#[inline]
+fn nibble_index(x: u8, y: u8, z: u8) -> usize {
+    (y as usize * 16 + z as usize) * 16 + x as usize
+}
+
+impl LightData {
+    /// Computes the light of a chunk on its own, as if surrounded by
+    /// unloaded chunks.
+    pub fn for_chunk(chunk: &Chunk, has_skylight: bool) -> Self {
+        compute([((0, 0), chunk)], has_skylight)
+            .remove(&(0, 0))
+            .unwrap()
+    }
+}
+
+/// Computes light for a group of chunks keyed by chunk position. Light
+/// spreads between any chunks in the group, but not into missing ones. Sky
+/// light is left out in dimensions without it, like the Nether.
+pub fn compute<'a>(
+    chunks: impl IntoIterator<Item = ((i32, i32), &'a Chunk)>,
+    has_skylight: bool,
+) -> BTreeMap<(i32, i32), LightData> {
+    let mut engine = Engine {
+        columns: chunks
+            .into_iter()
+            .map(|(pos, chunk)| {
+                let len = 16 * 16 * chunk.height() as usize;
+                let column = Column {
+                    chunk,
+                    sky: vec![0; len],
+                    block: vec![0; len],
+                };
+                (pos, column)
+            })
+            .collect(),
+        has_skylight,
+    };
+
+    let sky_sources = engine.seed_sky();
+    engine.propagate(Channel::Sky, sky_sources);
+    let block_sources = engine.seed_block();
+    engine.propagate(Channel::Block, block_sources);
+
+    engine
+        .columns
+        .into_iter()
+        .map(|(pos, column)| (pos, column.light_data(has_skylight)))
+        .collect()
+}
+
+impl Column<'_> {
+    /// The index of a block, with y in world coordinates.
+    #[inline]
+    fn index(&self, x: u8, y: i32, z: u8) -> usize {
+        ((y - self.chunk.min_y()) as usize * 16 + z as usize) * 16 + x as usize
+    }
+
+    fn levels(&mut self, channel: Channel) -> &mut [u8] {
+        match channel {
+            Channel::Sky => &mut self.sky,
+            Channel::Block => &mut self.block,
+        }
+    }
+
+    fn light_data(&self, has_skylight: bool) -> LightData {
+        let section = |levels: &[u8], chunk_y: usize| {
+            let mut array = NibbleArray::default();
+            let levels = &levels[chunk_y * 4096..(chunk_y + 1) * 4096];
+            for (i, &level) in levels.iter().enumerate() {
+                array.set((i % 16) as u8, (i / 256) as u8, (i / 16 % 16) as u8, level);
+            }
+            array
+        };
+
+        let count = self.chunk.subchunk_count() as usize;
+        let sky_light = if has_skylight {
+            std::iter::once(NibbleArray::default())
+                .chain((0..count).map(|chunk_y| section(&self.sky, chunk_y)))
+                .chain(std::iter::once(NibbleArray::filled(MAX_LIGHT)))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let block_light = std::iter::once(NibbleArray::default())
+            .chain((0..count).map(|chunk_y| section(&self.block, chunk_y)))
+            .chain(std::iter::once(NibbleArray::default()))
+            .collect();
+        LightData {
+            sky_light,
+            block_light,
+        }
+    }
+}
+
+impl Engine<'_> {
+    /// Splits a block position into its column and the index within it.
+    fn locate(&self, x: i32, y: i32, z: i32) -> Option<((i32, i32), usize)> {
+        let pos = (x.div_euclid(16), z.div_euclid(16));
+        let column = self.columns.get(&pos)?;
+        if !column.chunk.y_range().contains(&y) {
+            return None;
+        }
+        let index = column.index(x.rem_euclid(16) as u8, y, z.rem_euclid(16) as u8);
+        Some((pos, index))
+    }
+
+    fn state(&self, pos: (i32, i32), index: usize) -> i32 {
+        let chunk = self.columns[&pos].chunk;
+        chunk.block(
+            (index % 16) as u8,
+            chunk.min_y() + (index / 256) as i32,
+            (index / 16 % 16) as u8,
+        )
+    }
+
+    /// Lights every column from the top down until the light is absorbed,
+    /// and returns the positions that light can spread sideways from.
+    fn seed_sky(&mut self) -> VecDeque<(i32, i32, i32)> {
+        if !self.has_skylight {
+            return VecDeque::new();
+        }
+        for column in self.columns.values_mut() {
+            for z in 0..16 {
+                for x in 0..16 {
+                    let mut level = MAX_LIGHT;
+                    for y in column.chunk.y_range().rev() {
+                        let state = column.chunk.block(x, y, z);
+                        // Sky light travels straight down through blocks
+                        // that don't dampen it
+                        level = level.saturating_sub(light_dampening(state));
+                        if level == 0 {
+                            break;
+                        }
+                        let index = column.index(x, y, z);
+                        column.sky[index] = level;
+                    }
+                }
+            }
+        }
+
+        // Only the edges of lit areas need to spread, since light never
+        // moves sideways within a fully lit region
+        let mut sources = VecDeque::new();
+        for (&(chunk_x, chunk_z), column) in &self.columns {
+            for (index, &level) in column.sky.iter().enumerate() {
+                if level <= 1 {
+                    continue;
+                }
+                let x = chunk_x * 16 + (index % 16) as i32;
+                let y = column.chunk.min_y() + (index / 256) as i32;
+                let z = chunk_z * 16 + (index / 16 % 16) as i32;
+                let is_edge = NEIGHBOURS.iter().any(|&(dx, dy, dz)| {
+                    self.locate(x + dx, y + dy, z + dz)
+                        .is_some_and(|(pos, i)| self.columns[&pos].sky[i] < level - 1)
+                });
+                if is_edge {
+                    sources.push_back((x, y, z));
+                }
+            }
+        }
+        sources
+    }
+
+    fn seed_block(&mut self) -> VecDeque<(i32, i32, i32)> {
+        let mut sources = VecDeque::new();
+        for (&(chunk_x, chunk_z), column) in &mut self.columns {
+            for y in column.chunk.y_range() {
+                for z in 0..16 {
+                    for x in 0..16 {
+                        let state = column.chunk.block(x, y, z);
+                        let emission = light_emission(state);
+                        if emission > 0 {
+                            let index = column.index(x, y, z);
+                            column.block[index] = emission;
+                            sources.push_back((
+                                chunk_x * 16 + x as i32,
+                                y,
+                                chunk_z * 16 + z as i32,
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        sources
+    }
+
+    /// Spreads light outwards from `queue` with a breadth-first flood fill,
+    /// losing at least 1 per block.
+    fn propagate(&mut self, channel: Channel, mut queue: VecDeque<(i32, i32, i32)>) {
+        while let Some((x, y, z)) = queue.pop_front() {
+            let Some((pos, index)) = self.locate(x, y, z) else {
+                continue;
+            };
+            let level = self.columns.get_mut(&pos).unwrap().levels(channel)[index];
+
+            for (dx, dy, dz) in NEIGHBOURS {
+                let (nx, ny, nz) = (x + dx, y + dy, z + dz);
+                let Some((pos, index)) = self.locate(nx, ny, nz) else {
+                    continue;
+                };
+                let dampening = light_dampening(self.state(pos, index));
+                let spread = level.saturating_sub(dampening.max(1));
+                let levels = self.columns.get_mut(&pos).unwrap().levels(channel);
+                if spread > levels[index] {
+                    levels[index] = spread;
+                    queue.push_back((nx, ny, nz));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chunk::Chunk,
+        light::{self, NibbleArray},
+        packets::play::clientbound::LightData,
+        registries::blocks,
+    };
+
+    #[test]
+    fn test_nibble_array() {
+        let mut array = NibbleArray::default();
+        assert!(array.is_empty());
+        array.set(1, 0, 0, 15);
+        array.set(0, 0, 0, 3);
+        assert_eq!(array.as_bytes()[0], 0xF3);
+        assert_eq!(array.get(1, 0, 0), 15);
+        assert!(!array.is_empty());
+    }
+
+    #[test]
+    fn test_sky_light() {
+        // A stone roof at y = 4 over a 3x3 hole at x, z = 7..10, in a chunk
+        // from y = -16 to 16
+        let stone = blocks::Stone.state_id();
+        let chunk = Chunk::from_fn(-16, 2, |x, y, z| {
+            let hole = (7..10).contains(&x) && (7..10).contains(&z);
+            if y == 4 && !hole { stone } else { 0 }
+        });
+
+        let light = LightData::for_chunk(&chunk, true);
+        assert_eq!(light.sky_light.len(), 4);
+        assert!(light.sky_light[0].is_empty());
+        assert_eq!(light.sky_light[3], NibbleArray::filled(15));
+
+        let sky = |x, y: i32, z| {
+            let section = (y + 16).div_euclid(16) as usize + 1;
+            light.sky_light[section].get(x, y.rem_euclid(16) as u8, z)
+        };
+        assert_eq!(sky(0, 9, 0), 15);
+        assert_eq!(sky(8, -11, 8), 15);
+        assert_eq!(sky(6, 3, 8), 14);
+        assert_eq!(sky(2, 3, 8), 10);
+        assert_eq!(sky(0, 4, 0), 0);
+        assert!(light.block_light.iter().all(NibbleArray::is_empty));
+    }
+
+    #[test]
+    fn test_no_skylight() {
+        let glowstone = blocks::Glowstone.state_id();
+        let chunk = Chunk::from_fn(
+            0,
+            1,
+            |x, y, z| if (x, y, z) == (0, 0, 0) { glowstone } else { 0 },
+        );
+
+        let light = LightData::for_chunk(&chunk, false);
+        assert!(light.sky_light.is_empty());
+        assert_eq!(light.block_light[1].get(0, 0, 0), 15);
+        assert_eq!(light.block_light[1].get(0, 5, 0), 10);
+    }
+
+    #[test]
+    fn test_block_light_across_chunks() {
+        let stone = blocks::Stone.state_id();
+        let glowstone = blocks::Glowstone.state_id();
+        let mut lit = Chunk::from_fn(0, 1, |_, y, _| if y == 15 { stone } else { 0 });
+        lit.set_block_id(15, 4, 0, glowstone);
+        let dark = Chunk::from_fn(0, 1, |_, y, _| if y == 15 { stone } else { 0 });
+
+        let light = light::compute([((0, 0), &lit), ((1, 0), &dark)], true);
+        let block = |pos, x, y| light[&pos].block_light[1].get(x, y, 0);
+        assert_eq!(block((0, 0), 15, 4), 15);
+        assert_eq!(block((0, 0), 14, 4), 14);
+        assert_eq!(block((1, 0), 0, 4), 14);
+        assert_eq!(block((1, 0), 5, 4), 9);
+        assert_eq!(block((1, 0), 0, 15), 0);
+    }
+}
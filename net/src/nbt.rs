@@ -1,9 +1,23 @@
-use crate::packets::serialize::{Serialize, Serializer};
+use std::borrow::Cow;
+
+use snafu::prelude::*;
+
+use crate::packets::{
+    deserialize::{self, Deserialize, Deserializer},
+    serialize::{Serialize, Serializer},
+};
+
+pub mod de;
+mod json;
+pub mod ser;
+pub mod snbt;
+
+pub use de::from_tag;
+pub use ser::{ByteArray, IntArray, LongArray, to_tag};
 
 /// https://minecraft.wiki/w/NBT_format#Binary_format
 #[derive(Clone, Copy, Debug, PartialEq)]
 #[repr(u8)]
-#[allow(dead_code)]
 pub enum Kind {
     End = 0,
     Byte = 1,
@@ -21,7 +35,6 @@ pub enum Kind {
 }
 
 #[derive(Clone, Debug, PartialEq)]
-#[allow(dead_code)]
 pub enum Tag {
     End,
     Byte(i8),
@@ -38,6 +51,72 @@ pub enum Tag {
     LongArray(Vec<i64>),
 }
 
+/// Bounds applied while reading NBT, so that malformed or hostile input cannot
+/// exhaust the stack or memory.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// Maximum nesting of lists and compounds.
+    pub max_depth: usize,
+    /// Maximum number of input bytes a single tag may span.
+    pub max_size: usize,
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Invalid NBT tag type: {kind}"))]
+    InvalidKind { kind: u8 },
+    #[snafu(display("Expected a named root tag"))]
+    UnnamedRoot,
+    #[snafu(display("Negative NBT length: {length}"))]
+    NegativeLength { length: i32 },
+    #[snafu(display("NBT list of type End must be empty, got {length} elements"))]
+    NonEmptyEndList { length: i32 },
+    #[snafu(display("NBT is nested deeper than {max_depth}"))]
+    TooDeep { max_depth: usize },
+    #[snafu(display("NBT is larger than {max_size} bytes"))]
+    TooLarge { max_size: usize },
+    #[snafu(display("Invalid modified UTF-8 in NBT string"))]
+    InvalidString,
+    #[snafu(transparent)]
+    DeserializeError { source: deserialize::Error },
+}
+
+impl Limits {
+    /// Vanilla's limits for NBT received in packets.
+    pub const NETWORK: Limits = Limits {
+        max_depth: 512,
+        max_size: 2 * 1024 * 1024,
+    };
+    /// Limits for trusted files such as region and structure files.
+    pub const FILE: Limits = Limits {
+        max_depth: 512,
+        max_size: usize::MAX,
+    };
+}
+
+impl TryFrom<u8> for Kind {
+    type Error = Error;
+
+    fn try_from(kind: u8) -> Result<Self, Error> {
+        Ok(match kind {
+            0 => Kind::End,
+            1 => Kind::Byte,
+            2 => Kind::Short,
+            3 => Kind::Int,
+            4 => Kind::Long,
+            5 => Kind::Float,
+            6 => Kind::Double,
+            7 => Kind::ByteArray,
+            8 => Kind::String,
+            9 => Kind::List,
+            10 => Kind::Compound,
+            11 => Kind::IntArray,
+            12 => Kind::LongArray,
+            _ => return InvalidKindSnafu { kind }.fail(),
+        })
+    }
+}
+
 impl Tag {
     pub fn kind(&self) -> Kind {
         match self {
@@ -56,6 +135,25 @@ impl Tag {
             Tag::LongArray(_) => Kind::LongArray,
         }
     }
+
+    /// The value of `key`, if this is a compound containing it.
+    pub fn get(&self, key: &str) -> Option<&Tag> {
+        match self {
+            Tag::Compound(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Removes `key` from a compound, returning its value.
+    pub fn remove(&mut self, key: &str) -> Option<Tag> {
+        match self {
+            Tag::Compound(entries) => {
+                let index = entries.iter().position(|(k, _)| k == key)?;
+                Some(entries.remove(index).1)
+            }
+            _ => None,
+        }
+    }
 }
 
 impl From<bool> for Tag {
@@ -106,6 +204,12 @@ impl From<&str> for Tag {
     }
 }
 
+impl From<String> for Tag {
+    fn from(value: String) -> Self {
+        Tag::String(value)
+    }
+}
+
 #[macro_export]
 macro_rules! nbt {
     ( $( () )? ) => {
@@ -131,7 +235,62 @@ impl Serialize for Tag {
     }
 }
 
+impl<'de> Deserialize<'de> for Tag {
+    fn deserialize(d: &mut Deserializer<'de>) -> Result<Self, deserialize::Error> {
+        Tag::deserialize_network(d, Limits::NETWORK).map_err(|err| match err {
+            Error::DeserializeError { source } => source,
+            err => deserialize::Error::InvalidNbt {
+                message: err.to_string(),
+            },
+        })
+    }
+}
+
 impl Tag {
+    /// Encodes the tag with a nameless root, as used in packets since 1.20.2.
+    pub fn to_network_bytes(&self) -> Vec<u8> {
+        let mut s = Serializer::new();
+        self.serialize_unnamed(&mut s);
+        s.finish()
+    }
+
+    /// Encodes the tag with a named root, as used in files.
+    pub fn to_file_bytes(&self, name: &str) -> Vec<u8> {
+        let mut s = Serializer::new();
+        self.serialize_named(&mut s, name);
+        s.finish()
+    }
+
+    pub fn from_network_bytes(bytes: &[u8]) -> Result<Tag, Error> {
+        let mut d = Deserializer::new(bytes);
+        let tag = Tag::deserialize_network(&mut d, Limits::NETWORK)?;
+        d.finish()?;
+        Ok(tag)
+    }
+
+    /// Decodes an uncompressed NBT file, returning the root name and tag.
+    pub fn from_file_bytes(bytes: &[u8]) -> Result<(String, Tag), Error> {
+        let mut d = Deserializer::new(bytes);
+        let root = Tag::deserialize_file(&mut d, Limits::FILE)?;
+        d.finish()?;
+        Ok(root)
+    }
+
+    pub fn deserialize_network(d: &mut Deserializer, limits: Limits) -> Result<Tag, Error> {
+        let mut reader = Reader::new(d, limits);
+        let kind = reader.kind()?;
+        reader.body(kind)
+    }
+
+    pub fn deserialize_file(d: &mut Deserializer, limits: Limits) -> Result<(String, Tag), Error> {
+        let mut reader = Reader::new(d, limits);
+        let kind = reader.kind()?;
+        ensure!(kind != Kind::End, UnnamedRootSnafu);
+        let name = reader.string()?;
+        let tag = reader.body(kind)?;
+        Ok((name, tag))
+    }
+
     fn serialize_unnamed(&self, s: &mut Serializer) {
         s.serialize_ubyte(self.kind() as u8);
         self.serialize_body(s);
@@ -139,8 +298,7 @@ impl Tag {
 
     fn serialize_named(&self, s: &mut Serializer, name: &str) {
         s.serialize_ubyte(self.kind() as u8);
-        s.serialize_ushort(name.len() as u16);
-        s.serialize_byte_array(name.as_bytes());
+        serialize_string(s, name);
         self.serialize_body(s);
     }
 
@@ -153,17 +311,29 @@ impl Tag {
             Tag::Long(value) => s.serialize_long(*value),
             Tag::Float(value) => s.serialize_float(*value),
             Tag::Double(value) => s.serialize_double(*value),
-            Tag::ByteArray(_) => todo!(),
-            Tag::String(value) => {
-                s.serialize_ushort(value.len() as u16);
-                s.serialize_byte_array(value.as_bytes());
+            Tag::ByteArray(value) => {
+                s.serialize_int(value.len() as i32);
+                s.serialize_byte_array(value);
+            }
+            Tag::String(value) => serialize_string(s, value),
+            Tag::List(value) => {
+                let kind = value.first().map_or(Kind::End, Tag::kind);
+                debug_assert!(
+                    value.iter().all(|item| item.kind() == kind),
+                    "NBT list elements must all have the same type",
+                );
+                s.serialize_ubyte(kind as u8);
+                s.serialize_int(value.len() as i32);
+                s.serialize_array_with(value, |s, item| item.serialize_body(s));
             }
-            Tag::List(_) => todo!(),
             Tag::Compound(value) => {
                 s.serialize_array_with(value, |s, (name, item)| item.serialize_named(s, name));
                 Tag::End.serialize_unnamed(s);
             }
-            Tag::IntArray(_) => todo!(),
+            Tag::IntArray(value) => {
+                s.serialize_int(value.len() as i32);
+                s.serialize_array_with(value, |s, item| s.serialize_int(*item));
+            }
             Tag::LongArray(value) => {
                 s.serialize_int(value.len() as i32);
                 s.serialize_array_with(value, |s, item| s.serialize_long(*item));
@@ -171,3 +341,382 @@ impl Tag {
         }
     }
 }
+
+/// Reads tag bodies while enforcing [`Limits`].
+struct Reader<'a, 'de> {
+    d: &'a mut Deserializer<'de>,
+    limits: Limits,
+    end: usize,
+}
+
+impl<'a, 'de> Reader<'a, 'de> {
+    fn new(d: &'a mut Deserializer<'de>, limits: Limits) -> Self {
+        let end = d.remaining().saturating_sub(limits.max_size);
+        Self { d, limits, end }
+    }
+
+    /// Fails once more than `max_size` bytes have been consumed, or when a
+    /// length prefix claims more bytes than remain.
+    fn reserve(&mut self, bytes: usize) -> Result<(), Error> {
+        let remaining = self.d.remaining();
+        ensure!(
+            remaining.saturating_sub(bytes) >= self.end,
+            TooLargeSnafu {
+                max_size: self.limits.max_size
+            }
+        );
+        ensure!(remaining >= bytes, deserialize::EndOfPacketSnafu);
+        Ok(())
+    }
+
+    fn kind(&mut self) -> Result<Kind, Error> {
+        Kind::try_from(self.d.deserialize_ubyte()?)
+    }
+
+    fn length(&mut self, element_size: usize) -> Result<usize, Error> {
+        let length = self.d.deserialize_int()?;
+        ensure!(length >= 0, NegativeLengthSnafu { length });
+        self.reserve(length as usize * element_size)?;
+        Ok(length as usize)
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let length = self.d.deserialize_ushort()? as usize;
+        self.reserve(length)?;
+        decode_mutf8(self.d.deserialize_byte_array(length)?)
+    }
+
+    /// Reads a tag body. Lists and compounds are tracked on an explicit stack
+    /// rather than by recursion, so deeply nested input cannot overflow the
+    /// thread's stack.
+    fn body(&mut self, kind: Kind) -> Result<Tag, Error> {
+        let mut stack = Vec::new();
+        let mut next = kind;
+        loop {
+            self.reserve(0)?;
+            let mut value = match next {
+                Kind::List | Kind::Compound => {
+                    ensure!(
+                        stack.len() < self.limits.max_depth,
+                        TooDeepSnafu {
+                            max_depth: self.limits.max_depth
+                        }
+                    );
+                    stack.push(self.open(next)?);
+                    None
+                }
+                _ => Some(self.scalar(next)?),
+            };
+
+            loop {
+                let Some(frame) = stack.last_mut() else {
+                    return Ok(value.expect("Scalar read with an empty stack"));
+                };
+                match frame {
+                    Frame::List {
+                        kind,
+                        remaining,
+                        items,
+                    } => {
+                        items.extend(value.take());
+                        if *remaining > 0 {
+                            *remaining -= 1;
+                            next = *kind;
+                            break;
+                        }
+                        value = Some(Tag::List(std::mem::take(items)));
+                    }
+                    Frame::Compound { entries, name } => {
+                        if let Some(value) = value.take() {
+                            entries.push((std::mem::take(name), value));
+                        }
+                        let kind = self.kind()?;
+                        if kind != Kind::End {
+                            *name = self.string()?;
+                            next = kind;
+                            break;
+                        }
+                        value = Some(Tag::Compound(std::mem::take(entries)));
+                    }
+                }
+                stack.pop();
+            }
+        }
+    }
+
+    fn open(&mut self, kind: Kind) -> Result<Frame, Error> {
+        if kind == Kind::Compound {
+            return Ok(Frame::Compound {
+                entries: Vec::new(),
+                name: String::new(),
+            });
+        }
+
+        let kind = self.kind()?;
+        let length = self.length(0)?;
+        if kind == Kind::End {
+            ensure!(
+                length == 0,
+                NonEmptyEndListSnafu {
+                    length: length as i32
+                }
+            );
+        }
+        // Every non-End element occupies at least one byte
+        self.reserve(length)?;
+        Ok(Frame::List {
+            kind,
+            remaining: length,
+            items: Vec::with_capacity(length),
+        })
+    }
+
+    fn scalar(&mut self, kind: Kind) -> Result<Tag, Error> {
+        Ok(match kind {
+            Kind::End => Tag::End,
+            Kind::Byte => Tag::Byte(self.d.deserialize_byte()?),
+            Kind::Short => Tag::Short(self.d.deserialize_short()?),
+            Kind::Int => Tag::Int(self.d.deserialize_int()?),
+            Kind::Long => Tag::Long(self.d.deserialize_long()?),
+            Kind::Float => Tag::Float(self.d.deserialize_float()?),
+            Kind::Double => Tag::Double(self.d.deserialize_double()?),
+            Kind::ByteArray => {
+                let length = self.length(1)?;
+                Tag::ByteArray(self.d.deserialize_byte_array(length)?.to_owned())
+            }
+            Kind::String => Tag::String(self.string()?),
+            Kind::IntArray => {
+                let length = self.length(4)?;
+                let mut items = Vec::with_capacity(length);
+                for _ in 0..length {
+                    items.push(self.d.deserialize_int()?);
+                }
+                Tag::IntArray(items)
+            }
+            Kind::LongArray => {
+                let length = self.length(8)?;
+                let mut items = Vec::with_capacity(length);
+                for _ in 0..length {
+                    items.push(self.d.deserialize_long()?);
+                }
+                Tag::LongArray(items)
+            }
+            Kind::List | Kind::Compound => unreachable!("Nested tags are read by Reader::body"),
+        })
+    }
+}
+
+/// A list or compound that is still being read.
+enum Frame {
+    List {
+        kind: Kind,
+        remaining: usize,
+        items: Vec<Tag>,
+    },
+    Compound {
+        entries: Vec<(String, Tag)>,
+        name: String,
+    },
+}
+
+fn serialize_string(s: &mut Serializer, value: &str) {
+    let bytes = encode_mutf8(value);
+    assert!(bytes.len() <= u16::MAX as usize, "NBT string is too long");
+    s.serialize_ushort(bytes.len() as u16);
+    s.serialize_byte_array(&bytes);
+}
+
+/// Java's modified UTF-8: UTF-16 code units encoded individually, with NUL
+/// written as two bytes.
+fn encode_mutf8(value: &str) -> Cow<'_, [u8]> {
+    if value.bytes().all(|b| b != 0 && b < 0x80) {
+        return Cow::Borrowed(value.as_bytes());
+    }
+
+    let mut bytes = Vec::with_capacity(value.len());
+    for unit in value.encode_utf16() {
+        match unit {
+            0x01..=0x7F => bytes.push(unit as u8),
+            0x00 | 0x80..=0x7FF => {
+                bytes.push(0xC0 | (unit >> 6) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+            _ => {
+                bytes.push(0xE0 | (unit >> 12) as u8);
+                bytes.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+    Cow::Owned(bytes)
+}
+
+fn decode_mutf8(bytes: &[u8]) -> Result<String, Error> {
+    if bytes.iter().all(|&b| b != 0 && b < 0x80) {
+        return Ok(str::from_utf8(bytes).unwrap().to_owned());
+    }
+
+    let continuation = |b: Option<&u8>| match b {
+        Some(&b) if b & 0xC0 == 0x80 => Ok((b & 0x3F) as u16),
+        _ => InvalidStringSnafu.fail(),
+    };
+
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut iter = bytes.iter();
+    while let Some(&b) = iter.next() {
+        let unit = match b {
+            0x01..=0x7F => b as u16,
+            0xC0..=0xDF => ((b & 0x1F) as u16) << 6 | continuation(iter.next())?,
+            0xE0..=0xEF => {
+                ((b & 0x0F) as u16) << 12
+                    | continuation(iter.next())? << 6
+                    | continuation(iter.next())?
+            }
+            _ => return InvalidStringSnafu.fail(),
+        };
+        units.push(unit);
+    }
+    String::from_utf16(&units).ok().context(InvalidStringSnafu)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        nbt::{Error, Limits, Tag},
+        packets::deserialize::Deserializer,
+    };
+
+    // https://raw.github.com/Dinnerbone/mcassetsanalyzer/master/src/test/resources/hello_world.nbt
+    const HELLO_WORLD: &[u8] = &[
+        0x0a, 0x00, 0x0b, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x20, 0x77, 0x6f, 0x72, 0x6c, 0x64, 0x08,
+        0x00, 0x04, 0x6e, 0x61, 0x6d, 0x65, 0x00, 0x09, 0x42, 0x61, 0x6e, 0x61, 0x6e, 0x72, 0x61,
+        0x6d, 0x61, 0x00,
+    ];
+
+    fn every_kind() -> Tag {
+        Tag::Compound(vec![
+            ("byte".to_owned(), Tag::Byte(-1)),
+            ("short".to_owned(), Tag::Short(-300)),
+            ("int".to_owned(), Tag::Int(70_000)),
+            ("long".to_owned(), Tag::Long(-5_000_000_000)),
+            ("float".to_owned(), Tag::Float(0.5)),
+            ("double".to_owned(), Tag::Double(-0.25)),
+            ("byte_array".to_owned(), Tag::ByteArray(vec![0, 1, 255])),
+            ("string".to_owned(), Tag::from("nul  é 😀")),
+            (
+                "list".to_owned(),
+                Tag::List(vec![
+                    Tag::List(vec![Tag::Short(1), Tag::Short(2)]),
+                    Tag::List(vec![]),
+                ]),
+            ),
+            (
+                "compounds".to_owned(),
+                Tag::List(vec![
+                    Tag::Compound(vec![("a".to_owned(), Tag::Int(1))]),
+                    Tag::Compound(vec![]),
+                ]),
+            ),
+            (
+                "int_array".to_owned(),
+                Tag::IntArray(vec![i32::MIN, 0, i32::MAX]),
+            ),
+            (
+                "long_array".to_owned(),
+                Tag::LongArray(vec![i64::MIN, i64::MAX]),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_hello_world() {
+        let tag = Tag::Compound(vec![("name".to_owned(), Tag::from("Bananrama"))]);
+
+        assert_eq!(tag.to_file_bytes("hello world"), HELLO_WORLD);
+        assert_eq!(
+            Tag::from_file_bytes(HELLO_WORLD).unwrap(),
+            ("hello world".to_owned(), tag)
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let tag = every_kind();
+
+        assert_eq!(
+            Tag::from_network_bytes(&tag.to_network_bytes()).unwrap(),
+            tag
+        );
+        assert_eq!(
+            Tag::from_file_bytes(&tag.to_file_bytes("root")).unwrap(),
+            ("root".to_owned(), tag)
+        );
+        assert_eq!(
+            Tag::from_network_bytes(&Tag::End.to_network_bytes()).unwrap(),
+            Tag::End
+        );
+    }
+
+    #[test]
+    fn test_modified_utf8() {
+        let bytes = Tag::from(" 😀").to_network_bytes();
+        assert_eq!(
+            bytes,
+            [
+                0x08, 0x00, 0x08, 0xC0, 0x80, 0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert!(matches!(
+            Tag::from_network_bytes(&[0x0D]),
+            Err(Error::InvalidKind { kind: 0x0D })
+        ));
+        assert!(matches!(
+            Tag::from_file_bytes(&[0x00]),
+            Err(Error::UnnamedRoot)
+        ));
+        assert!(matches!(
+            Tag::from_network_bytes(&[0x07, 0xFF, 0xFF, 0xFF, 0xFF]),
+            Err(Error::NegativeLength { length: -1 })
+        ));
+        assert!(matches!(
+            Tag::from_network_bytes(&[0x09, 0x00, 0x7F, 0xFF, 0xFF, 0xFF]),
+            Err(Error::NonEmptyEndList { .. })
+        ));
+        assert!(matches!(
+            Tag::from_network_bytes(&[0x0B, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01]),
+            Err(Error::DeserializeError { .. })
+        ));
+        assert!(matches!(
+            Tag::from_network_bytes(&[0x08, 0x00, 0x01, 0xFF]),
+            Err(Error::InvalidString)
+        ));
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut deep = Tag::End;
+        for _ in 0..600 {
+            deep = Tag::List(vec![deep]);
+        }
+        let bytes = deep.to_network_bytes();
+        assert!(matches!(
+            Tag::from_network_bytes(&bytes),
+            Err(Error::TooDeep { max_depth: 512 })
+        ));
+
+        let large = Tag::ByteArray(vec![0; 100]).to_network_bytes();
+        let limits = Limits {
+            max_depth: 512,
+            max_size: 64,
+        };
+        assert!(matches!(
+            Tag::deserialize_network(&mut Deserializer::new(&large), limits),
+            Err(Error::TooLarge { max_size: 64 })
+        ));
+        assert!(Tag::deserialize_network(&mut Deserializer::new(&large), Limits::NETWORK).is_ok());
+    }
+}
@@ -0,0 +1,226 @@
+//! Converts NBT tags into `serde::Deserialize` types.
+
+use serde::{
+    de::{
+        self, DeserializeOwned, IntoDeserializer, Visitor,
+        value::{MapAccessDeserializer, MapDeserializer, SeqDeserializer},
+    },
+    forward_to_deserialize_any,
+};
+use snafu::prelude::*;
+
+use crate::nbt::Tag;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{message}"))]
+    Custom { message: String },
+}
+
+impl de::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Error::Custom {
+            message: msg.to_string(),
+        }
+    }
+}
+
+/// Converts `tag` into `T`. Numeric tags widen into larger integer types and
+/// byte tags are accepted for `bool`. Arrays deserialize like lists.
+pub fn from_tag<T: DeserializeOwned>(tag: Tag) -> Result<T, Error> {
+    T::deserialize(TagDeserializer(tag))
+}
+
+pub struct TagDeserializer(Tag);
+
+impl IntoDeserializer<'_, Error> for Tag {
+    type Deserializer = TagDeserializer;
+
+    fn into_deserializer(self) -> TagDeserializer {
+        TagDeserializer(self)
+    }
+}
+
+fn visit_seq<'de, V: Visitor<'de>>(
+    items: impl Iterator<Item = Tag>,
+    visitor: V,
+) -> Result<V::Value, Error> {
+    let mut seq = SeqDeserializer::new(items);
+    let value = visitor.visit_seq(&mut seq)?;
+    seq.end()?;
+    Ok(value)
+}
+
+impl<'de> de::Deserializer<'de> for TagDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Tag::End => visitor.visit_unit(),
+            Tag::Byte(v) => visitor.visit_i8(v),
+            Tag::Short(v) => visitor.visit_i16(v),
+            Tag::Int(v) => visitor.visit_i32(v),
+            Tag::Long(v) => visitor.visit_i64(v),
+            Tag::Float(v) => visitor.visit_f32(v),
+            Tag::Double(v) => visitor.visit_f64(v),
+            Tag::ByteArray(v) => visit_seq(v.into_iter().map(|b| Tag::Byte(b as i8)), visitor),
+            Tag::String(v) => visitor.visit_string(v),
+            Tag::List(v) => visit_seq(v.into_iter(), visitor),
+            Tag::Compound(v) => {
+                let mut map = MapDeserializer::new(v.into_iter());
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            Tag::IntArray(v) => visit_seq(v.into_iter().map(Tag::Int), visitor),
+            Tag::LongArray(v) => visit_seq(v.into_iter().map(Tag::Long), visitor),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Tag::Byte(v) => visitor.visit_bool(v != 0),
+            Tag::Short(v) => visitor.visit_bool(v != 0),
+            Tag::Int(v) => visitor.visit_bool(v != 0),
+            Tag::Long(v) => visitor.visit_bool(v != 0),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Tag::ByteArray(v) => visitor.visit_byte_buf(v),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.0 {
+            Tag::End => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.0 {
+            Tag::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Tag::Compound(entries) if entries.len() == 1 => visitor.visit_enum(
+                MapAccessDeserializer::new(MapDeserializer::new(entries.into_iter())),
+            ),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::nbt::{self, LongArray, Tag};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct DimensionType {
+        has_skylight: bool,
+        height: i32,
+        coordinate_scale: f64,
+        fixed_time: Option<i64>,
+        monster_spawn_light_level: Distribution,
+        heightmap: LongArray,
+        tags: Vec<String>,
+        mode: Mode,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Distribution {
+        r#type: String,
+        min_inclusive: i8,
+        max_inclusive: i8,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    enum Mode {
+        Survival,
+        Spectator { flying: bool },
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let value = DimensionType {
+            has_skylight: true,
+            height: 384,
+            coordinate_scale: 1.0,
+            fixed_time: None,
+            monster_spawn_light_level: Distribution {
+                r#type: "minecraft:uniform".to_owned(),
+                min_inclusive: 0,
+                max_inclusive: 7,
+            },
+            heightmap: LongArray(vec![1, 2]),
+            tags: vec!["a".to_owned()],
+            mode: Mode::Spectator { flying: false },
+        };
+
+        let tag = nbt::to_tag(&value).unwrap();
+        assert_eq!(
+            tag.to_string(),
+            "{has_skylight:1b,height:384,coordinate_scale:1.0d,\
+             monster_spawn_light_level:{type:\"minecraft:uniform\",min_inclusive:0b,max_inclusive:7b},\
+             heightmap:[L;1L,2L],tags:[\"a\"],mode:{spectator:{flying:0b}}}"
+        );
+        assert_eq!(nbt::from_tag::<DimensionType>(tag).unwrap(), value);
+
+        assert_eq!(nbt::to_tag(&Mode::Survival).unwrap(), Tag::from("survival"));
+    }
+
+    #[test]
+    fn test_from_tag() {
+        // Vanilla data often stores booleans as ints and widens integers
+        let tag = Tag::from_snbt("{b: 1, a: 2b}").unwrap();
+        let map = nbt::from_tag::<BTreeMap<String, i64>>(tag.clone()).unwrap();
+        assert_eq!(
+            map,
+            BTreeMap::from([("a".to_owned(), 2), ("b".to_owned(), 1)])
+        );
+
+        #[derive(Debug, Deserialize)]
+        struct Flags {
+            b: bool,
+        }
+        assert!(nbt::from_tag::<Flags>(tag).unwrap().b);
+
+        let tag = Tag::from_snbt("{a: 300}").unwrap();
+        assert!(nbt::from_tag::<BTreeMap<String, i8>>(tag).is_err());
+    }
+
+    #[test]
+    fn test_to_tag_errors() {
+        assert!(nbt::to_tag(&200u8).is_err());
+        assert!(nbt::to_tag(&BTreeMap::from([(1, 2)])).is_err());
+        assert!(nbt::to_tag(&vec![Some(1), None]).is_err());
+    }
+}
@@ -0,0 +1,176 @@
+//! JSON as found in data packs, converted the way vanilla's `JsonOps` to
+//! `NbtOps` conversion does.
+
+use serde_json::{Number, Value};
+
+use crate::nbt::{Kind, Tag};
+
+impl Tag {
+    /// Converts JSON to NBT. Booleans become bytes and whole numbers the
+    /// smallest integer type that holds them. Other numbers become floats if
+    /// that's exact, or doubles otherwise. `null` becomes `End`, which leaves
+    /// the field out of a compound.
+    ///
+    /// Lists of bytes, ints or longs become arrays. Lists whose items don't
+    /// all have the same kind have each item that isn't already a compound
+    /// wrapped in one, under an empty key.
+    pub fn from_json(value: &Value) -> Self {
+        match value {
+            Value::Null => Tag::End,
+            Value::Bool(b) => Tag::from(*b),
+            Value::Number(n) => number(n),
+            Value::String(s) => Tag::String(s.clone()),
+            Value::Array(items) => list(
+                items
+                    .iter()
+                    .map(Tag::from_json)
+                    .filter(|item| item.kind() != Kind::End)
+                    .collect(),
+            ),
+            Value::Object(entries) => Tag::Compound(
+                entries
+                    .iter()
+                    .map(|(key, value)| (key.clone(), Tag::from_json(value)))
+                    .filter(|(_, value)| value.kind() != Kind::End)
+                    .collect(),
+            ),
+        }
+    }
+}
+
+fn number(n: &Number) -> Tag {
+    let whole = n.as_i64().or_else(|| {
+        // Like `BigDecimal::longValueExact`, which accepts `1.0`
+        n.as_f64()
+            .filter(|f| f.fract() == 0.0 && (i64::MIN as f64..i64::MAX as f64).contains(f))
+            .map(|f| f as i64)
+    });
+    if let Some(n) = whole {
+        return if let Ok(n) = i8::try_from(n) {
+            Tag::Byte(n)
+        } else if let Ok(n) = i16::try_from(n) {
+            Tag::Short(n)
+        } else if let Ok(n) = i32::try_from(n) {
+            Tag::Int(n)
+        } else {
+            Tag::Long(n)
+        };
+    }
+
+    let n = n.as_f64().unwrap_or(f64::NAN);
+    if n as f32 as f64 == n {
+        Tag::Float(n as f32)
+    } else {
+        Tag::Double(n)
+    }
+}
+
+fn list(items: Vec<Tag>) -> Tag {
+    let Some(first) = items.first().map(Tag::kind) else {
+        return Tag::List(items);
+    };
+    if items.iter().all(|item| item.kind() == first) {
+        return match first {
+            Kind::Byte => Tag::ByteArray(
+                items
+                    .into_iter()
+                    .map(|item| match item {
+                        Tag::Byte(n) => n as u8,
+                        _ => unreachable!(),
+                    })
+                    .collect(),
+            ),
+            Kind::Int => Tag::IntArray(
+                items
+                    .into_iter()
+                    .map(|item| match item {
+                        Tag::Int(n) => n,
+                        _ => unreachable!(),
+                    })
+                    .collect(),
+            ),
+            Kind::Long => Tag::LongArray(
+                items
+                    .into_iter()
+                    .map(|item| match item {
+                        Tag::Long(n) => n,
+                        _ => unreachable!(),
+                    })
+                    .collect(),
+            ),
+            _ => Tag::List(items),
+        };
+    }
+
+    Tag::List(
+        items
+            .into_iter()
+            .map(|item| match item {
+                Tag::Compound(entries) if !is_wrapper(&entries) => Tag::Compound(entries),
+                item => Tag::Compound(vec![(String::new(), item)]),
+            })
+            .collect(),
+    )
+}
+
+/// Whether a compound would be mistaken for a wrapped list item.
+fn is_wrapper(entries: &[(String, Tag)]) -> bool {
+    matches!(entries, [(key, _)] if key.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::nbt::Tag;
+
+    #[test]
+    fn test_from_json() {
+        let tag = Tag::from_json(&json!({
+            "exhaustion": 0.1,
+            "scale": 0.5,
+            "max_level": 5,
+            "cost": 1000,
+            "weight": 1.0,
+            "seed": 1i64 << 40,
+            "decal": false,
+            "missing": null,
+            "slots": ["mainhand", "offhand"],
+            "levels": [1, 2],
+            "mixed": [1, 1000, "two", {"three": 3}, {"": 4}],
+        }));
+        assert_eq!(
+            tag,
+            Tag::Compound(vec![
+                ("cost".to_owned(), Tag::Short(1000)),
+                ("decal".to_owned(), Tag::Byte(0)),
+                ("exhaustion".to_owned(), Tag::Double(0.1)),
+                ("levels".to_owned(), Tag::ByteArray(vec![1, 2])),
+                ("max_level".to_owned(), Tag::Byte(5)),
+                (
+                    "mixed".to_owned(),
+                    Tag::List(vec![
+                        Tag::Compound(vec![(String::new(), Tag::Byte(1))]),
+                        Tag::Compound(vec![(String::new(), Tag::Short(1000))]),
+                        Tag::Compound(vec![(String::new(), Tag::String("two".to_owned()))]),
+                        Tag::Compound(vec![("three".to_owned(), Tag::Byte(3))]),
+                        Tag::Compound(vec![(
+                            String::new(),
+                            Tag::Compound(vec![(String::new(), Tag::Byte(4))])
+                        )]),
+                    ])
+                ),
+                ("scale".to_owned(), Tag::Float(0.5)),
+                ("seed".to_owned(), Tag::Long(1 << 40)),
+                (
+                    "slots".to_owned(),
+                    Tag::List(vec![
+                        Tag::String("mainhand".to_owned()),
+                        Tag::String("offhand".to_owned())
+                    ])
+                ),
+                ("weight".to_owned(), Tag::Byte(1)),
+            ])
+        );
+    }
+}
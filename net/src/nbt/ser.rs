@@ -0,0 +1,634 @@
+//! Converts `serde::Serialize` types into NBT tags.
+
+use serde::{
+    Deserialize, Deserializer, Serialize,
+    ser::{self, Impossible},
+};
+use snafu::prelude::*;
+
+use crate::nbt::{Kind, Tag};
+
+const BYTE_ARRAY: &str = "__nbt_byte_array";
+const INT_ARRAY: &str = "__nbt_int_array";
+const LONG_ARRAY: &str = "__nbt_long_array";
+
+/// Serializes as a `ByteArray` tag instead of a list of bytes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ByteArray(pub Vec<i8>);
+
+/// Serializes as an `IntArray` tag instead of a list of ints.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IntArray(pub Vec<i32>);
+
+/// Serializes as a `LongArray` tag instead of a list of longs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LongArray(pub Vec<i64>);
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{message}"))]
+    Custom { message: String },
+    #[snafu(display("{value} does not fit in a signed NBT integer"))]
+    IntegerOutOfRange { value: u64 },
+    #[snafu(display("Compound keys must be strings"))]
+    KeyMustBeString,
+    #[snafu(display("Can't insert {found:?} into list of {expected:?}"))]
+    MixedList { expected: Kind, found: Kind },
+    #[snafu(display("Lists can't contain None or unit values"))]
+    EndInList,
+}
+
+impl ser::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Error::Custom {
+            message: msg.to_string(),
+        }
+    }
+}
+
+/// Converts `value` into a tag. Integers keep their width (`i8` becomes a
+/// `Byte`, `i64` a `Long`, and so on), and struct fields and map entries keep
+/// their order. `None` fields are left out of the compound.
+///
+/// Use [`ByteArray`], [`IntArray`] and [`LongArray`] for the array tags.
+pub fn to_tag<T: Serialize + ?Sized>(value: &T) -> Result<Tag, Error> {
+    value.serialize(TagSerializer)
+}
+
+macro_rules! array_serde {
+    ($ty:ident, $name:ident, $item:ty) => {
+        impl Serialize for $ty {
+            fn serialize<S: ser::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                s.serialize_newtype_struct($name, &self.0)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+                Vec::<$item>::deserialize(d).map($ty)
+            }
+        }
+    };
+}
+
+array_serde!(ByteArray, BYTE_ARRAY, i8);
+array_serde!(IntArray, INT_ARRAY, i32);
+array_serde!(LongArray, LONG_ARRAY, i64);
+
+fn unsigned(value: u64) -> Result<i64, Error> {
+    i64::try_from(value)
+        .ok()
+        .context(IntegerOutOfRangeSnafu { value })
+}
+
+/// Collects every item of `items` that matches `f`, or fails on the first
+/// item that doesn't.
+fn array_items<T>(
+    items: Vec<Tag>,
+    kind: Kind,
+    f: impl Fn(Tag) -> Option<T>,
+) -> Result<Vec<T>, Error> {
+    items
+        .into_iter()
+        .map(|item| {
+            let found = item.kind();
+            f(item).context(MixedListSnafu {
+                expected: kind,
+                found,
+            })
+        })
+        .collect()
+}
+
+fn wrap_variant(variant: &'static str, tag: Tag) -> Tag {
+    Tag::Compound(vec![(variant.to_owned(), tag)])
+}
+
+struct TagSerializer;
+
+impl ser::Serializer for TagSerializer {
+    type Ok = Tag;
+    type Error = Error;
+
+    type SerializeSeq = ListSerializer;
+    type SerializeTuple = ListSerializer;
+    type SerializeTupleStruct = ListSerializer;
+    type SerializeTupleVariant = ListSerializer;
+    type SerializeMap = CompoundSerializer;
+    type SerializeStruct = CompoundSerializer;
+    type SerializeStructVariant = CompoundSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Tag, Error> {
+        Ok(Tag::from(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Tag, Error> {
+        Ok(Tag::Byte(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Tag, Error> {
+        Ok(Tag::Short(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Tag, Error> {
+        Ok(Tag::Int(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Tag, Error> {
+        Ok(Tag::Long(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Tag, Error> {
+        let value = i8::try_from(v)
+            .ok()
+            .context(IntegerOutOfRangeSnafu { value: v })?;
+        Ok(Tag::Byte(value))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Tag, Error> {
+        let value = i16::try_from(v)
+            .ok()
+            .context(IntegerOutOfRangeSnafu { value: v })?;
+        Ok(Tag::Short(value))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Tag, Error> {
+        let value = i32::try_from(v)
+            .ok()
+            .context(IntegerOutOfRangeSnafu { value: v })?;
+        Ok(Tag::Int(value))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Tag, Error> {
+        Ok(Tag::Long(unsigned(v)?))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Tag, Error> {
+        Ok(Tag::Float(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Tag, Error> {
+        Ok(Tag::Double(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Tag, Error> {
+        Ok(Tag::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Tag, Error> {
+        Ok(Tag::from(v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Tag, Error> {
+        Ok(Tag::ByteArray(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<Tag, Error> {
+        Ok(Tag::End)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Tag, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Tag, Error> {
+        Ok(Tag::End)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Tag, Error> {
+        Ok(Tag::End)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Tag, Error> {
+        Ok(Tag::from(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Tag, Error> {
+        let tag = value.serialize(self)?;
+        let Tag::List(items) = tag else {
+            return Ok(tag);
+        };
+        Ok(match name {
+            BYTE_ARRAY => Tag::ByteArray(array_items(items, Kind::Byte, |item| match item {
+                Tag::Byte(v) => Some(v as u8),
+                _ => None,
+            })?),
+            INT_ARRAY => Tag::IntArray(array_items(items, Kind::Int, |item| match item {
+                Tag::Int(v) => Some(v),
+                _ => None,
+            })?),
+            LONG_ARRAY => Tag::LongArray(array_items(items, Kind::Long, |item| match item {
+                Tag::Long(v) => Some(v),
+                _ => None,
+            })?),
+            _ => Tag::List(items),
+        })
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Tag, Error> {
+        Ok(wrap_variant(variant, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<ListSerializer, Error> {
+        Ok(ListSerializer {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<ListSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<ListSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<ListSerializer, Error> {
+        Ok(ListSerializer {
+            items: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<CompoundSerializer, Error> {
+        Ok(CompoundSerializer {
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            next_key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<CompoundSerializer, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<CompoundSerializer, Error> {
+        Ok(CompoundSerializer {
+            entries: Vec::with_capacity(len),
+            next_key: None,
+            variant: Some(variant),
+        })
+    }
+}
+
+struct ListSerializer {
+    items: Vec<Tag>,
+    variant: Option<&'static str>,
+}
+
+impl ListSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let item = to_tag(value)?;
+        ensure!(item.kind() != Kind::End, EndInListSnafu);
+        if let Some(first) = self.items.first().map(Tag::kind) {
+            ensure!(
+                item.kind() == first,
+                MixedListSnafu {
+                    expected: first,
+                    found: item.kind(),
+                }
+            );
+        }
+        self.items.push(item);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Tag, Error> {
+        let list = Tag::List(self.items);
+        Ok(match self.variant {
+            Some(variant) => wrap_variant(variant, list),
+            None => list,
+        })
+    }
+}
+
+impl ser::SerializeSeq for ListSerializer {
+    type Ok = Tag;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Tag, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for ListSerializer {
+    type Ok = Tag;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Tag, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for ListSerializer {
+    type Ok = Tag;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Tag, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for ListSerializer {
+    type Ok = Tag;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Tag, Error> {
+        self.finish()
+    }
+}
+
+struct CompoundSerializer {
+    entries: Vec<(String, Tag)>,
+    next_key: Option<String>,
+    variant: Option<&'static str>,
+}
+
+impl CompoundSerializer {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<(), Error> {
+        let value = to_tag(value)?;
+        if value.kind() != Kind::End {
+            self.entries.push((key, value));
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Tag, Error> {
+        let compound = Tag::Compound(self.entries);
+        Ok(match self.variant {
+            Some(variant) => wrap_variant(variant, compound),
+            None => compound,
+        })
+    }
+}
+
+impl ser::SerializeMap for CompoundSerializer {
+    type Ok = Tag;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.next_key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .next_key
+            .take()
+            .expect("serialize_value called before serialize_key");
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Tag, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for CompoundSerializer {
+    type Ok = Tag;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.insert(key.to_owned(), value)
+    }
+
+    fn end(self) -> Result<Tag, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for CompoundSerializer {
+    type Ok = Tag;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.insert(key.to_owned(), value)
+    }
+
+    fn end(self) -> Result<Tag, Error> {
+        self.finish()
+    }
+}
+
+/// Accepts only string-like map keys.
+struct KeySerializer;
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = Error;
+
+    type SerializeSeq = Impossible<String, Error>;
+    type SerializeTuple = Impossible<String, Error>;
+    type SerializeTupleStruct = Impossible<String, Error>;
+    type SerializeTupleVariant = Impossible<String, Error>;
+    type SerializeMap = Impossible<String, Error>;
+    type SerializeStruct = Impossible<String, Error>;
+    type SerializeStructVariant = Impossible<String, Error>;
+
+    fn serialize_str(self, v: &str) -> Result<String, Error> {
+        Ok(v.to_owned())
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, Error> {
+        Ok(variant.to_owned())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<String, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<String, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<String, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<String, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<String, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<String, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<String, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<String, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<String, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<String, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<String, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_none(self) -> Result<String, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<String, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_unit(self) -> Result<String, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        KeyMustBeStringSnafu.fail()
+    }
+}
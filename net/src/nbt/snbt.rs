@@ -0,0 +1,632 @@
+//! Stringified NBT, as used by commands and `/data get`.
+//!
+//! https://minecraft.wiki/w/NBT_format#SNBT_format
+
+use std::{fmt, str::FromStr};
+
+use snafu::prelude::*;
+
+use crate::nbt::{Kind, Tag};
+
+/// Matches vanilla's nesting limit for parsed SNBT.
+const MAX_DEPTH: usize = 512;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Expected {expected} at position {position}"))]
+    Expected {
+        expected: &'static str,
+        position: usize,
+    },
+    #[snafu(display("Can't insert {found:?} into list of {expected:?} at position {position}"))]
+    MixedList {
+        expected: Kind,
+        found: Kind,
+        position: usize,
+    },
+    #[snafu(display("Can't insert {found:?} into {array:?} at position {position}"))]
+    MixedArray {
+        array: Kind,
+        found: Kind,
+        position: usize,
+    },
+    #[snafu(display("Invalid escape sequence at position {position}"))]
+    InvalidEscape { position: usize },
+    #[snafu(display("SNBT is nested deeper than {MAX_DEPTH} at position {position}"))]
+    TooDeep { position: usize },
+    #[snafu(display("Unexpected trailing data at position {position}"))]
+    TrailingData { position: usize },
+}
+
+impl Tag {
+    /// Parses a single SNBT value, such as `{id: "minecraft:stone", count: 1b}`.
+    pub fn from_snbt(input: &str) -> Result<Tag, Error> {
+        let mut parser = Parser {
+            input,
+            position: 0,
+            depth: 0,
+        };
+        let tag = parser.value()?;
+        parser.skip_whitespace();
+        ensure!(
+            parser.position == input.len(),
+            TrailingDataSnafu {
+                position: parser.position
+            }
+        );
+        Ok(tag)
+    }
+}
+
+impl FromStr for Tag {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Tag::from_snbt(s)
+    }
+}
+
+/// Prints compact SNBT, or indented SNBT with the alternate flag (`{:#}`).
+/// Either form parses back with [`Tag::from_snbt`].
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let indent = f.alternate().then_some(0);
+        write_tag(f, self, indent)
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek().filter(|c| c.is_whitespace()) {
+            self.position += c.len_utf8();
+        }
+    }
+
+    fn expect(&mut self, c: char, expected: &'static str) -> Result<(), Error> {
+        self.skip_whitespace();
+        ensure!(
+            self.peek() == Some(c),
+            ExpectedSnafu {
+                expected,
+                position: self.position
+            }
+        );
+        self.position += 1;
+        Ok(())
+    }
+
+    /// Consumes `c` if it is the next non-whitespace character.
+    fn accept(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn value(&mut self) -> Result<Tag, Error> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.nested(Self::compound),
+            Some('[') => self.nested(Self::list),
+            Some('"' | '\'') => Ok(Tag::String(self.quoted_string()?)),
+            _ => {
+                let position = self.position;
+                let token = self.unquoted_string();
+                ensure!(
+                    !token.is_empty(),
+                    ExpectedSnafu {
+                        expected: "value",
+                        position
+                    }
+                );
+                Ok(typed_token(token))
+            }
+        }
+    }
+
+    fn nested(&mut self, f: impl FnOnce(&mut Self) -> Result<Tag, Error>) -> Result<Tag, Error> {
+        ensure!(
+            self.depth < MAX_DEPTH,
+            TooDeepSnafu {
+                position: self.position
+            }
+        );
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn compound(&mut self) -> Result<Tag, Error> {
+        self.expect('{', "'{'")?;
+        let mut entries = Vec::new();
+        while !self.accept('}') {
+            self.skip_whitespace();
+            let position = self.position;
+            let key = match self.peek() {
+                Some('"' | '\'') => self.quoted_string()?,
+                _ => self.unquoted_string().to_owned(),
+            };
+            ensure!(
+                !key.is_empty(),
+                ExpectedSnafu {
+                    expected: "key",
+                    position
+                }
+            );
+            self.expect(':', "':'")?;
+            entries.push((key, self.value()?));
+
+            if !self.accept(',') {
+                self.expect('}', "'}'")?;
+                break;
+            }
+        }
+        Ok(Tag::Compound(entries))
+    }
+
+    fn list(&mut self) -> Result<Tag, Error> {
+        self.expect('[', "'['")?;
+
+        let rest = &self.input[self.position..];
+        let array = match rest.as_bytes() {
+            [b'B', b';', ..] => Some(Kind::ByteArray),
+            [b'I', b';', ..] => Some(Kind::IntArray),
+            [b'L', b';', ..] => Some(Kind::LongArray),
+            _ => None,
+        };
+        if let Some(array) = array {
+            self.position += 2;
+            return self.array(array);
+        }
+
+        let mut items = Vec::new();
+        while !self.accept(']') {
+            self.skip_whitespace();
+            let position = self.position;
+            let item = self.value()?;
+            if let Some(first) = items.first().map(Tag::kind) {
+                ensure!(
+                    item.kind() == first,
+                    MixedListSnafu {
+                        expected: first,
+                        found: item.kind(),
+                        position
+                    }
+                );
+            }
+            items.push(item);
+
+            if !self.accept(',') {
+                self.expect(']', "']'")?;
+                break;
+            }
+        }
+        Ok(Tag::List(items))
+    }
+
+    fn array(&mut self, array: Kind) -> Result<Tag, Error> {
+        let mut tag = match array {
+            Kind::ByteArray => Tag::ByteArray(Vec::new()),
+            Kind::IntArray => Tag::IntArray(Vec::new()),
+            _ => Tag::LongArray(Vec::new()),
+        };
+        while !self.accept(']') {
+            self.skip_whitespace();
+            let position = self.position;
+            match (&mut tag, self.value()?) {
+                (Tag::ByteArray(items), Tag::Byte(item)) => items.push(item as u8),
+                (Tag::IntArray(items), Tag::Int(item)) => items.push(item),
+                (Tag::LongArray(items), Tag::Long(item)) => items.push(item),
+                (_, item) => {
+                    return MixedArraySnafu {
+                        array,
+                        found: item.kind(),
+                        position,
+                    }
+                    .fail();
+                }
+            }
+
+            if !self.accept(',') {
+                self.expect(']', "']'")?;
+                break;
+            }
+        }
+        Ok(tag)
+    }
+
+    fn unquoted_string(&mut self) -> &str {
+        let start = self.position;
+        while let Some(c) = self.peek().filter(|&c| is_unquoted_char(c)) {
+            self.position += c.len_utf8();
+        }
+        &self.input[start..self.position]
+    }
+
+    fn quoted_string(&mut self) -> Result<String, Error> {
+        let quote = self.peek().expect("Caller checked for a quote");
+        self.position += 1;
+
+        let mut value = String::new();
+        loop {
+            let position = self.position;
+            let Some(c) = self.peek() else {
+                return ExpectedSnafu {
+                    expected: "closing quote",
+                    position,
+                }
+                .fail();
+            };
+            self.position += c.len_utf8();
+            match c {
+                '\\' => {
+                    let escaped = self.peek().filter(|&c| c == '\\' || c == quote);
+                    let escaped = escaped.context(InvalidEscapeSnafu { position })?;
+                    self.position += 1;
+                    value.push(escaped);
+                }
+                c if c == quote => return Ok(value),
+                c => value.push(c),
+            }
+        }
+    }
+}
+
+fn is_unquoted_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+')
+}
+
+/// Interprets an unquoted token the way vanilla's `TagParser.type` does:
+/// numbers by suffix, `true`/`false` as bytes, and anything else (including
+/// out-of-range numbers) as a string.
+fn typed_token(token: &str) -> Tag {
+    let (body, suffix) = match token.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&token[..i], Some(c.to_ascii_lowercase())),
+        _ => (token, None),
+    };
+
+    let tag = match suffix {
+        Some('b') if is_integer(body) => body.parse().ok().map(Tag::Byte),
+        Some('s') if is_integer(body) => body.parse().ok().map(Tag::Short),
+        Some('l') if is_integer(body) => body.parse().ok().map(Tag::Long),
+        Some('f') if is_decimal(body, false) => body.parse().ok().map(Tag::Float),
+        Some('d') if is_decimal(body, false) => body.parse().ok().map(Tag::Double),
+        None if is_integer(body) => body.parse().ok().map(Tag::Int),
+        None if is_decimal(body, true) => body.parse().ok().map(Tag::Double),
+        _ => None,
+    };
+
+    tag.unwrap_or_else(|| match token {
+        "true" => Tag::Byte(1),
+        "false" => Tag::Byte(0),
+        _ => Tag::String(token.to_owned()),
+    })
+}
+
+fn strip_sign(s: &str) -> &str {
+    s.strip_prefix(['+', '-']).unwrap_or(s)
+}
+
+/// `[-+]?(?:0|[1-9][0-9]*)`
+fn is_integer(s: &str) -> bool {
+    let digits = strip_sign(s);
+    digits == "0"
+        || digits.starts_with(|c: char| ('1'..='9').contains(&c))
+            && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// `[-+]?(?:[0-9]+[.]?|[0-9]*[.][0-9]+)(?:e[-+]?[0-9]+)?`, where the `.` is
+/// mandatory if `require_dot` is set.
+fn is_decimal(s: &str, require_dot: bool) -> bool {
+    let s = strip_sign(s);
+    let (mantissa, exponent) = match s.find(['e', 'E']) {
+        Some(i) => (&s[..i], Some(strip_sign(&s[i + 1..]))),
+        None => (s, None),
+    };
+    if exponent.is_some_and(|e| e.is_empty() || !e.bytes().all(|b| b.is_ascii_digit())) {
+        return false;
+    }
+
+    let (whole, fraction) = match mantissa.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (mantissa, None),
+    };
+    let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    match fraction {
+        None => !require_dot && !whole.is_empty() && digits(whole),
+        Some(fraction) => {
+            digits(whole) && digits(fraction) && !(whole.is_empty() && fraction.is_empty())
+        }
+    }
+}
+
+fn write_tag(f: &mut fmt::Formatter<'_>, tag: &Tag, indent: Option<usize>) -> fmt::Result {
+    match tag {
+        Tag::End => write!(f, "END"),
+        Tag::Byte(value) => write!(f, "{value}b"),
+        Tag::Short(value) => write!(f, "{value}s"),
+        Tag::Int(value) => write!(f, "{value}"),
+        Tag::Long(value) => write!(f, "{value}L"),
+        Tag::Float(value) => write!(f, "{:?}f", finite(*value as f64, f32::MAX as f64) as f32),
+        Tag::Double(value) => write!(f, "{:?}d", finite(*value, f64::MAX)),
+        Tag::ByteArray(value) => write_array(f, "B", value, |v| format!("{}B", *v as i8), indent),
+        Tag::String(value) => write_string(f, value),
+        Tag::List(value) => {
+            let nested = value
+                .iter()
+                .any(|item| matches!(item, Tag::List(_) | Tag::Compound(_)));
+            let indent = indent.filter(|_| nested);
+            write_entries(f, ('[', ']'), value, indent, |f, item, indent| {
+                write_tag(f, item, indent)
+            })
+        }
+        Tag::Compound(value) => {
+            write_entries(f, ('{', '}'), value, indent, |f, (key, item), indent| {
+                if key.chars().all(is_unquoted_char) && !key.is_empty() {
+                    write!(f, "{key}")?;
+                } else {
+                    write_string(f, key)?;
+                }
+                write!(f, "{}", if indent.is_some() { ": " } else { ":" })?;
+                write_tag(f, item, indent)
+            })
+        }
+        Tag::IntArray(value) => write_array(f, "I", value, |v| v.to_string(), indent),
+        Tag::LongArray(value) => write_array(f, "L", value, |v| format!("{v}L"), indent),
+    }
+}
+
+/// SNBT has no NaN or infinity, so they're written as 0 and the largest
+/// finite value of the type, keeping the output parseable.
+fn finite(value: f64, max: f64) -> f64 {
+    if value.is_nan() {
+        0.0
+    } else {
+        value.clamp(-max, max)
+    }
+}
+
+fn write_array<T>(
+    f: &mut fmt::Formatter<'_>,
+    prefix: &str,
+    items: &[T],
+    format: impl Fn(&T) -> String,
+    indent: Option<usize>,
+) -> fmt::Result {
+    let separator = if indent.is_some() { ", " } else { "," };
+    let items = items.iter().map(format).collect::<Vec<_>>();
+    if indent.is_some() && !items.is_empty() {
+        write!(f, "[{prefix}; {}]", items.join(separator))
+    } else {
+        write!(f, "[{prefix};{}]", items.join(separator))
+    }
+}
+
+/// Writes a list or compound, one entry per line when `indent` is set.
+fn write_entries<T>(
+    f: &mut fmt::Formatter<'_>,
+    (open, close): (char, char),
+    entries: &[T],
+    indent: Option<usize>,
+    mut write_entry: impl FnMut(&mut fmt::Formatter<'_>, &T, Option<usize>) -> fmt::Result,
+) -> fmt::Result {
+    write!(f, "{open}")?;
+    match indent {
+        Some(indent) if !entries.is_empty() => {
+            for (i, entry) in entries.iter().enumerate() {
+                let separator = if i + 1 < entries.len() { "," } else { "" };
+                write!(f, "\n{:width$}", "", width = (indent + 1) * 4)?;
+                write_entry(f, entry, Some(indent + 1))?;
+                write!(f, "{separator}")?;
+            }
+            write!(f, "\n{:width$}", "", width = indent * 4)?;
+        }
+        _ => {
+            for (i, entry) in entries.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ",")?;
+                }
+                write_entry(f, entry, None)?;
+            }
+        }
+    }
+    write!(f, "{close}")
+}
+
+/// Quotes with `"` unless the string contains one and no `'`, like vanilla's
+/// `StringTag.quoteAndEscape`.
+fn write_string(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    let quote = if value.contains('"') && !value.contains('\'') {
+        '\''
+    } else {
+        '"'
+    };
+    write!(f, "{quote}")?;
+    for c in value.chars() {
+        if c == '\\' || c == quote {
+            write!(f, "\\")?;
+        }
+        write!(f, "{c}")?;
+    }
+    write!(f, "{quote}")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nbt::{Kind, Tag, snbt::Error};
+
+    fn compound(entries: &[(&str, Tag)]) -> Tag {
+        Tag::Compound(
+            entries
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_parse_numbers() {
+        fn test(snbt: &str, expected: Tag) {
+            assert_eq!(Tag::from_snbt(snbt).unwrap(), expected, "{snbt}");
+        }
+
+        test("1b", Tag::Byte(1));
+        test("-2B", Tag::Byte(-2));
+        test("3s", Tag::Short(3));
+        test("4", Tag::Int(4));
+        test("+5", Tag::Int(5));
+        test("6L", Tag::Long(6));
+        test("7.5f", Tag::Float(7.5));
+        test("8d", Tag::Double(8.0));
+        test("9.", Tag::Double(9.0));
+        test(".5", Tag::Double(0.5));
+        test("1.5e3", Tag::Double(1500.0));
+        test("true", Tag::Byte(1));
+        test("false", Tag::Byte(0));
+        // Not numbers, so read as strings like vanilla does
+        test("128b", Tag::from("128b"));
+        test("01", Tag::from("01"));
+        test("1e5", Tag::from("1e5"));
+        test("minecraft.stone", Tag::from("minecraft.stone"));
+    }
+
+    #[test]
+    fn test_parse_nested() {
+        let tag = Tag::from_snbt(
+            r#"{
+                "minecraft:foo": 'it\'s',
+                name: "say \"hi\" \\ there",
+                list: [{a: 1}, {}],
+                empty: [],
+                bytes: [B; 1b, -1b],
+                ints: [I; 1, 2,],
+                longs: [L;],
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            tag,
+            compound(&[
+                ("minecraft:foo", Tag::from("it's")),
+                ("name", Tag::from(r#"say "hi" \ there"#)),
+                (
+                    "list",
+                    Tag::List(vec![compound(&[("a", Tag::Int(1))]), compound(&[])]),
+                ),
+                ("empty", Tag::List(vec![])),
+                ("bytes", Tag::ByteArray(vec![1, 255])),
+                ("ints", Tag::IntArray(vec![1, 2])),
+                ("longs", Tag::LongArray(vec![])),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(
+            Tag::from_snbt("[1, 2b]"),
+            Err(Error::MixedList {
+                expected: Kind::Int,
+                found: Kind::Byte,
+                ..
+            })
+        ));
+        assert!(matches!(
+            Tag::from_snbt("[I; 1, 2L]"),
+            Err(Error::MixedArray { .. })
+        ));
+        assert!(matches!(
+            Tag::from_snbt("{a 1}"),
+            Err(Error::Expected { position: 3, .. })
+        ));
+        assert!(matches!(
+            Tag::from_snbt("{a: 1} 2"),
+            Err(Error::TrailingData { position: 7 })
+        ));
+        assert!(matches!(
+            Tag::from_snbt(r#""\n""#),
+            Err(Error::InvalidEscape { position: 1 })
+        ));
+        assert!(matches!(
+            Tag::from_snbt(&"[".repeat(600)),
+            Err(Error::TooDeep { .. })
+        ));
+    }
+
+    #[test]
+    fn test_display() {
+        let tag = compound(&[
+            ("byte", Tag::Byte(1)),
+            ("minecraft:key", Tag::Float(0.5)),
+            ("quote", Tag::from("a\"b")),
+            ("list", Tag::List(vec![Tag::Long(1), Tag::Long(2)])),
+            ("bytes", Tag::ByteArray(vec![1, 255])),
+            (
+                "nested",
+                Tag::List(vec![compound(&[("double", Tag::Double(2.0))])]),
+            ),
+        ]);
+
+        let compact = tag.to_string();
+        assert_eq!(
+            compact,
+            r#"{byte:1b,"minecraft:key":0.5f,quote:'a"b',list:[1L,2L],bytes:[B;1B,-1B],nested:[{double:2.0d}]}"#
+        );
+        assert_eq!(Tag::from_snbt(&compact).unwrap(), tag);
+
+        let pretty = format!("{tag:#}");
+        assert_eq!(
+            pretty,
+            r#"{
+    byte: 1b,
+    "minecraft:key": 0.5f,
+    quote: 'a"b',
+    list: [1L,2L],
+    bytes: [B; 1B, -1B],
+    nested: [
+        {
+            double: 2.0d
+        }
+    ]
+}"#
+        );
+        assert_eq!(Tag::from_snbt(&pretty).unwrap(), tag);
+    }
+
+    #[test]
+    fn test_display_non_finite() {
+        let tag = Tag::List(vec![
+            Tag::Float(f32::NAN),
+            Tag::Float(f32::INFINITY),
+            Tag::Float(f32::NEG_INFINITY),
+        ]);
+        assert_eq!(
+            Tag::from_snbt(&tag.to_string()).unwrap(),
+            Tag::List(vec![
+                Tag::Float(0.0),
+                Tag::Float(f32::MAX),
+                Tag::Float(f32::MIN)
+            ])
+        );
+
+        let tag = Tag::List(vec![Tag::Double(f64::NAN), Tag::Double(f64::INFINITY)]);
+        assert_eq!(
+            Tag::from_snbt(&tag.to_string()).unwrap(),
+            Tag::List(vec![Tag::Double(0.0), Tag::Double(f64::MAX)])
+        );
+    }
+}
@@ -25,6 +25,7 @@ pub mod types {
 
     pub type string = String;
     pub type uuid = ::uuid::Uuid;
+    pub type position = crate::packets::Position;
     pub type varint = i32;
     pub type varlong = i64;
     pub type prefixed_array<T> = Vec<T>;
@@ -41,7 +42,7 @@ pub struct Deserializer<'de> {
 
 #[derive(Debug, Snafu)]
 pub enum Error {
-    #[snafu(display("Unexpected end of packet"))]
+    #[snafu(visibility(pub), display("Unexpected end of packet"))]
     EndOfPacket,
     #[snafu(display("Unread bytes remaining"))]
     BytesRemaining,
@@ -59,6 +60,8 @@ pub enum Error {
         display("Invalid enum variant ({enum_name:?}): {value}")
     )]
     InvalidEnumVariant { enum_name: &'static str, value: i32 },
+    #[snafu(display("Invalid NBT: {message}"))]
+    InvalidNbt { message: String },
     #[snafu(transparent)]
     Utf8Error { source: std::string::FromUtf8Error },
 }
@@ -93,6 +96,10 @@ impl<'de> Deserializer<'de> {
         Ok(())
     }
 
+    pub fn remaining(&self) -> usize {
+        self.bytes.len()
+    }
+
     #[must_use]
     pub fn take_remaining(&mut self) -> &'de [u8] {
         let bytes = self.bytes;
@@ -136,6 +143,10 @@ impl<'de> Deserializer<'de> {
         Ok(value)
     }
 
+    pub fn deserialize_position(&mut self) -> Result<types::position, Error> {
+        self.deserialize_long().map(types::position::unpack)
+    }
+
     pub fn deserialize_varint(&mut self) -> Result<types::varint, Error> {
         let mut result = 0;
         let mut shift = 0;
@@ -178,12 +189,17 @@ impl<'de> Deserializer<'de> {
         Ok(result)
     }
 
+    pub fn deserialize_byte_array(&mut self, length: usize) -> Result<&'de [u8], Error> {
+        ensure!(self.bytes.len() >= length, EndOfPacketSnafu);
+        let bytes = &self.bytes[..length];
+        self.bytes = &self.bytes[length..];
+        Ok(bytes)
+    }
+
     pub fn deserialize_prefixed_byte_array(&mut self) -> Result<types::prefixed_byte_array, Error> {
         let length = self.deserialize_varint()?;
-        ensure!(self.bytes.len() >= length as usize, EndOfPacketSnafu);
-        let bytes = &self.bytes[..length as usize];
-        self.bytes = &self.bytes[length as usize..];
-        Ok(bytes.to_owned())
+        self.deserialize_byte_array(length as usize)
+            .map(|bytes| bytes.to_owned())
     }
 }
 
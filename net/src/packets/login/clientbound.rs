@@ -4,10 +4,43 @@ use crate::packets::serialize::{Serialize, types};
 pub enum Packet<'a> {
     #[packet(id = 0x00)]
     LoginDisconnect { reason: types::string<'a> },
+    #[packet(id = 0x01)]
+    Hello {
+        server_id: types::string<'a>,
+        public_key: types::prefixed_byte_array<'a>,
+        verify_token: types::prefixed_byte_array<'a>,
+        should_authenticate: types::boolean,
+    },
     #[packet(id = 0x02)]
     LoginFinished {
-        uuid: types::uuid,
-        username: types::string<'a>,
-        properties: types::prefixed_byte_array<'a>,
+        #[packet(serialize_with = profile.serialize(s))]
+        profile: &'a GameProfile,
     },
+    #[packet(id = 0x03)]
+    LoginCompression { threshold: types::varint },
+}
+
+/// A player's identity. Deserializes from the session server's JSON format.
+#[derive(Clone, Debug, Serialize, serde::Deserialize)]
+pub struct GameProfile {
+    #[serde(rename = "id")]
+    pub uuid: types::uuid,
+    #[serde(rename = "name")]
+    #[packet(serialize_with = s.serialize_string(username))]
+    pub username: String,
+    #[serde(default)]
+    #[packet(serialize_with = s.serialize_prefixed_array(properties))]
+    pub properties: Vec<Property>,
+}
+
+/// A signed profile property, such as the `textures` property carrying a
+/// player's skin and cape.
+#[derive(Clone, Debug, Serialize, serde::Deserialize)]
+pub struct Property {
+    #[packet(serialize_with = s.serialize_string(name))]
+    pub name: String,
+    #[packet(serialize_with = s.serialize_string(value))]
+    pub value: String,
+    #[packet(serialize_with = s.serialize_prefixed_optional(&signature.as_deref()))]
+    pub signature: Option<String>,
 }
@@ -8,6 +8,11 @@ pub enum Packet {
         name: types::string,
         player_uuid: types::uuid,
     },
+    #[packet(id = 0x01)]
+    Key {
+        shared_secret: types::prefixed_byte_array,
+        verify_token: types::prefixed_byte_array,
+    },
     #[packet(id = 0x03)]
     LoginAcknowledged,
 }
@@ -6,3 +6,52 @@ pub mod status;
 
 pub mod deserialize;
 pub mod serialize;
+
+/// A block position, packed into a long on the wire.
+///
+/// https://minecraft.wiki/w/Java_Edition_protocol/Data_types#Position
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl Position {
+    pub fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// 26 bits of x, then 26 bits of z, then 12 bits of y.
+    pub fn pack(self) -> i64 {
+        ((self.x as i64 & 0x3FF_FFFF) << 38)
+            | ((self.z as i64 & 0x3FF_FFFF) << 12)
+            | (self.y as i64 & 0xFFF)
+    }
+
+    pub fn unpack(packed: i64) -> Self {
+        Self {
+            x: (packed >> 38) as i32,
+            y: (packed << 52 >> 52) as i32,
+            z: (packed << 26 >> 38) as i32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::packets::Position;
+
+    #[test]
+    fn test_position() {
+        let position = Position::new(18357644, 831, -20882616);
+        assert_eq!(position.pack(), 0x4607632c15b4833f);
+        for position in [
+            position,
+            Position::new(-1, -64, -1),
+            Position::new(-33554432, -2048, 33554431),
+        ] {
+            assert_eq!(Position::unpack(position.pack()), position);
+        }
+    }
+}
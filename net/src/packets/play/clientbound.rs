@@ -1,4 +1,6 @@
 use crate::{
+    chunk::BlockEntity,
+    light::NibbleArray,
     nbt,
     packets::serialize::{Serialize, Serializer, types},
 };
@@ -21,6 +23,17 @@ pub enum Packet<'a> {
         velocity_y: types::short,
         velocity_z: types::short,
     },
+    #[packet(id = 0x07)]
+    BlockEntityData {
+        location: types::position,
+        type_id: types::varint,
+        data: nbt::Tag,
+    },
+    #[packet(id = 0x09)]
+    BlockUpdate {
+        location: types::position,
+        block_id: types::varint,
+    },
     #[packet(id = 0x0C)]
     ChunkBatchFinished { batch_size: types::varint },
     #[packet(id = 0x0D)]
@@ -43,6 +56,11 @@ pub enum Packet<'a> {
         pitch: types::float,
         on_ground: types::boolean,
     },
+    #[packet(id = 0x22)]
+    ForgetLevelChunk {
+        chunk_z: types::int,
+        chunk_x: types::int,
+    },
     #[packet(id = 0x23)]
     GameEvent {
         #[packet(serialize_with = s.serialize_ubyte(*event as u8))]
@@ -58,6 +76,12 @@ pub enum Packet<'a> {
         data: ChunkData,
         light: LightData,
     },
+    #[packet(id = 0x2B)]
+    LightUpdate {
+        chunk_x: types::varint,
+        chunk_z: types::varint,
+        light: LightData,
+    },
     #[packet(id = 0x2C)]
     Login {
         entity_id: types::int,
@@ -82,6 +106,17 @@ pub enum Packet<'a> {
         pitch: types::float,
         flags: types::int,
     },
+    #[packet(id = 0x4C)]
+    Respawn {
+        spawn: SpawnInfo,
+        /// Bit 1 keeps attributes and bit 2 keeps entity metadata.
+        data_kept: types::byte,
+    },
+    #[packet(id = 0x4E)]
+    SectionBlocksUpdate {
+        section: SectionPosition,
+        blocks: types::prefixed_array<'a, BlockChange>,
+    },
     #[packet(id = 0x58)]
     SetChunkCacheCenter {
         chunk_x: types::varint,
@@ -93,11 +128,42 @@ pub enum Packet<'a> {
 pub struct ChunkData {
     pub heightmaps: nbt::Tag,
     pub data: Vec<u8>,
+    pub block_entities: Vec<ChunkBlockEntity>,
 }
 
-#[derive(Debug)]
+/// A block entity, positioned relative to its chunk.
+#[derive(Clone, Debug)]
+pub struct ChunkBlockEntity {
+    pub x: u8,
+    pub y: i16,
+    pub z: u8,
+    pub block_entity: BlockEntity,
+}
+
+/// The position of a 16x16x16 chunk section, in sections.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SectionPosition {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+/// A block in a `SectionBlocksUpdate`, positioned relative to its section.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockChange {
+    pub x: u8,
+    pub y: u8,
+    pub z: u8,
+    pub state_id: i32,
+}
+
+#[derive(Clone, Debug)]
 pub struct LightData {
-    pub subchunk_count: u8,
+    /// Sky light for each section, plus the sections just below and above
+    /// the chunk. Empty in dimensions without sky light.
+    pub sky_light: Vec<NibbleArray>,
+    /// Block light, with the same sections as `sky_light`.
+    pub block_light: Vec<NibbleArray>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -108,8 +174,19 @@ pub enum GameEvent {
 
 #[derive(Debug)]
 pub struct LoginData {
-    pub game_mode: u8,
+    /// Every dimension the player could be sent to.
+    pub dimension_names: Vec<String>,
+    pub spawn: SpawnInfo,
     pub enforces_secure_chat: bool,
+}
+
+/// The dimension a player is in, shared by `Login` and `Respawn`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpawnInfo {
+    /// The id of the dimension's type in the `dimension_type` registry.
+    pub dimension_type: i32,
+    pub dimension_name: String,
+    pub game_mode: u8,
     pub is_flat: bool,
 }
 
@@ -117,43 +194,89 @@ impl Serialize for ChunkData {
     fn serialize(&self, s: &mut Serializer) {
         self.heightmaps.serialize(s);
         s.serialize_prefixed_byte_array(&self.data);
-        s.serialize_varint(0);
+        s.serialize_prefixed_array(&self.block_entities);
+    }
+}
+
+impl Serialize for ChunkBlockEntity {
+    fn serialize(&self, s: &mut Serializer) {
+        s.serialize_ubyte((self.x & 0xF) << 4 | (self.z & 0xF));
+        s.serialize_short(self.y);
+        s.serialize_varint(self.block_entity.type_id);
+        self.block_entity.data.serialize(s);
+    }
+}
+
+impl Serialize for SectionPosition {
+    fn serialize(&self, s: &mut Serializer) {
+        s.serialize_long(
+            (self.x as i64 & 0x3F_FFFF) << 42
+                | (self.z as i64 & 0x3F_FFFF) << 20
+                | (self.y as i64 & 0xF_FFFF),
+        );
+    }
+}
+
+impl Serialize for BlockChange {
+    fn serialize(&self, s: &mut Serializer) {
+        let position = (self.x as i64) << 8 | (self.z as i64) << 4 | self.y as i64;
+        s.serialize_varlong((self.state_id as i64) << 12 | position);
     }
 }
 
 impl Serialize for LightData {
     fn serialize(&self, s: &mut Serializer) {
-        let count = self.subchunk_count as usize + 2;
-        s.serialize_prefixed_bitset(&vec![true; count]);
-        s.serialize_prefixed_bitset(&vec![true; count]);
-        s.serialize_prefixed_bitset(&vec![false; count]);
-        s.serialize_prefixed_bitset(&vec![false; count]);
-        s.serialize_prefixed_array_with(&vec![(); count], |s, ()| {
-            s.serialize_prefixed_byte_array(&[0xFF; 2048]);
-        });
-        s.serialize_prefixed_array_with(&vec![(); count], |s, ()| {
-            s.serialize_prefixed_byte_array(&[0xFF; 2048]);
-        });
+        // Empty sections are sent as a bit in the empty mask instead of an
+        // array of zeros
+        let mask = |light: &[NibbleArray], empty| {
+            light
+                .iter()
+                .map(|l| l.is_empty() == empty)
+                .collect::<Vec<_>>()
+        };
+        s.serialize_prefixed_bitset(&mask(&self.sky_light, false));
+        s.serialize_prefixed_bitset(&mask(&self.block_light, false));
+        s.serialize_prefixed_bitset(&mask(&self.sky_light, true));
+        s.serialize_prefixed_bitset(&mask(&self.block_light, true));
+        for light in [&self.sky_light, &self.block_light] {
+            let non_empty = light.iter().filter(|l| !l.is_empty()).collect::<Vec<_>>();
+            s.serialize_prefixed_array_with(&non_empty, |s, light| {
+                s.serialize_prefixed_byte_array(light.as_bytes());
+            });
+        }
     }
 }
 
 impl Serialize for LoginData {
     fn serialize(&self, s: &mut Serializer) {
         let LoginData {
-            game_mode,
+            dimension_names,
+            spawn,
             enforces_secure_chat,
-            is_flat,
         } = self;
         s.serialize_boolean(false);
-        s.serialize_prefixed_array_with(&["overworld"], |s, item| s.serialize_string(item));
+        s.serialize_prefixed_array_with(dimension_names, |s, item| s.serialize_string(item));
         s.serialize_varint(1);
         s.serialize_varint(8);
         s.serialize_varint(8);
         s.serialize_boolean(false);
         s.serialize_boolean(false);
         s.serialize_boolean(false);
-        s.serialize_varint(0);
-        s.serialize_string("overworld");
+        spawn.serialize(s);
+        s.serialize_boolean(*enforces_secure_chat);
+    }
+}
+
+impl Serialize for SpawnInfo {
+    fn serialize(&self, s: &mut Serializer) {
+        let SpawnInfo {
+            dimension_type,
+            dimension_name,
+            game_mode,
+            is_flat,
+        } = self;
+        s.serialize_varint(*dimension_type);
+        s.serialize_string(dimension_name);
         s.serialize_long(0);
         s.serialize_ubyte(*game_mode);
         s.serialize_byte(-1);
@@ -162,6 +285,5 @@ impl Serialize for LoginData {
         s.serialize_boolean(false);
         s.serialize_varint(0);
         s.serialize_varint(0);
-        s.serialize_boolean(*enforces_secure_chat);
     }
 }
@@ -9,6 +9,18 @@ pub enum Packet {
     ChunkBatchReceived { chunks_per_tick: types::float },
     #[packet(id = 0x0B)]
     ClientTickEnd,
+    #[packet(id = 0x0C)]
+    ClientInformation {
+        locale: types::string,
+        view_distance: types::byte,
+        chat_mode: types::varint,
+        chat_colors: types::boolean,
+        displayed_skin_parts: types::ubyte,
+        main_hand: types::varint,
+        enable_text_filtering: types::boolean,
+        allow_server_listings: types::boolean,
+        particle_status: types::varint,
+    },
     #[packet(id = 0x14)]
     CustomPayload {
         channel: types::string,
@@ -21,6 +21,7 @@ pub mod types {
 
     pub type string<'a> = &'a str;
     pub type uuid = ::uuid::Uuid;
+    pub type position = crate::packets::Position;
     pub type varint = i32;
     pub type varlong = i64;
     pub type prefixed_optional<T> = Option<T>;
@@ -34,6 +35,7 @@ pub trait Serialize {
     fn serialize(&self, s: &mut Serializer);
 }
 
+#[derive(Default)]
 pub struct Serializer {
     buf: Vec<u8>,
 }
@@ -116,6 +118,10 @@ impl Serializer {
         self.buf.extend_from_slice(value.as_bytes());
     }
 
+    pub fn serialize_position(&mut self, value: types::position) {
+        self.serialize_long(value.pack());
+    }
+
     pub fn serialize_prefixed_bitset(&mut self, value: &[bool]) {
         let len = value.len().div_ceil(i64::BITS as usize);
         self.serialize_varint(len as i32);
@@ -189,6 +195,12 @@ impl Serializer {
     }
 }
 
+impl Serialize for &str {
+    fn serialize(&self, s: &mut Serializer) {
+        s.serialize_string(self);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::packets::serialize::Serializer;
@@ -1,39 +1,55 @@
-use std::{collections::BTreeMap, sync::OnceLock};
+pub mod block_entity_types;
+pub mod blocks;
+pub mod entity_types;
+pub mod items;
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, OnceLock},
+};
 
-use serde::Deserialize;
 use snafu::prelude::*;
 
-#[derive(Debug, Deserialize)]
-struct Registries {
-    #[serde(rename = "minecraft:entity_type")]
-    pub entity_types: Registry<EntityType>,
-}
+use crate::{nbt::Tag, registries::block_entity_types::BlockEntityType};
 
-#[derive(Debug, Deserialize)]
-struct Registry<T> {
-    pub entries: BTreeMap<String, T>,
-}
+include!(concat!(env!("OUT_DIR"), "/registry_data.rs"));
+include!(concat!(env!("OUT_DIR"), "/block_states.rs"));
 
-#[derive(Debug, Deserialize)]
-pub struct EntityType {
-    pub protocol_id: i32,
+/// A block from the report. Its states have consecutive ids, one for each
+/// combination of property values.
+#[derive(Debug)]
+struct Block {
+    id: &'static str,
+    first_state_id: i32,
+    default_state_id: i32,
+    properties: &'static [Property],
 }
 
-#[derive(Debug, Deserialize)]
-struct Block {
-    pub states: Vec<BlockState>,
+#[derive(Debug)]
+struct Property {
+    name: &'static str,
+    values: &'static [&'static str],
+    /// How far apart the ids of states differing only in this property's
+    /// value are.
+    stride: i32,
 }
 
-#[derive(Debug, Deserialize)]
+/// A block state, looked up by id or by block and properties.
+#[derive(Clone, Copy, Debug)]
 pub struct BlockState {
     pub id: i32,
-    #[serde(default)]
-    pub properties: BTreeMap<String, String>,
+    block: &'static Block,
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(display("Block entity type not found: {id}"))]
+pub struct BlockEntityTypeNotFound {
+    id: String,
 }
 
 #[derive(Debug, Snafu)]
-#[snafu(display("Entity type not found: {id}"))]
-pub struct EntityTypeNotFound {
+#[snafu(display("Biome not found: {id}"))]
+pub struct BiomeNotFound {
     id: String,
 }
 
@@ -41,57 +57,336 @@ pub struct EntityTypeNotFound {
 pub enum BlockStateNotFound {
     #[snafu(display("Block not found: {id}"))]
     BlockNotFound { id: String },
-    #[snafu(display("Block state not found: {id} {properties:?}"))]
-    BlockStateNotFound {
+    #[snafu(display("Invalid properties for {id}: {properties:?}"))]
+    InvalidProperties {
         id: String,
-        properties: Vec<(&'static str, &'static str)>,
+        properties: BTreeMap<String, String>,
     },
 }
 
-fn registries() -> &'static Registries {
-    static CACHE: OnceLock<Registries> = OnceLock::new();
-    CACHE.get_or_init(|| {
-        serde_json::from_str(include_str!(
-            "../../target/registries/generated/reports/registries.json"
-        ))
-        .unwrap()
-    })
+/// A registry the client gets from the server during configuration, rather
+/// than having built in.
+#[derive(Clone, Debug)]
+pub struct SynchronizedRegistry {
+    /// Such as `worldgen/biome`.
+    pub id: &'static str,
+    /// Entry ids and their data, in protocol id order.
+    pub entries: Vec<(String, Tag)>,
 }
 
-fn blocks() -> &'static BTreeMap<String, Block> {
-    static CACHE: OnceLock<BTreeMap<String, Block>> = OnceLock::new();
+/// Every synchronized registry except `dimension_type`, as written by the
+/// data generator.
+pub fn synchronized_registries() -> &'static [SynchronizedRegistry] {
+    static CACHE: OnceLock<Vec<SynchronizedRegistry>> = OnceLock::new();
     CACHE.get_or_init(|| {
-        serde_json::from_str(include_str!(
-            "../../target/registries/generated/reports/blocks.json"
-        ))
-        .unwrap()
+        REGISTRY_DATA
+            .iter()
+            .map(|&(id, entries)| SynchronizedRegistry {
+                id,
+                entries: entries
+                    .iter()
+                    .map(|&(entry_id, json)| {
+                        let value = serde_json::from_str(json).unwrap();
+                        (entry_id.to_owned(), Tag::from_json(&value))
+                    })
+                    .collect(),
+            })
+            .collect()
     })
 }
 
-pub fn entity_type(id: &str) -> Result<&'static EntityType, EntityTypeNotFound> {
-    let registries = registries();
-    let entity_type = registries
-        .entity_types
-        .entries
-        .get(id)
-        .context(EntityTypeNotFoundSnafu { id })?;
-    Ok(entity_type)
+/// The biomes a server sends in its `worldgen/biome` registry, so a biome's
+/// protocol id is its index here. Chunks store protocol ids, so they're
+/// generated, loaded and sent with the server's
+/// [`World::biomes`](crate::world::World::biomes).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Biomes {
+    ids: Arc<[String]>,
+}
+
+impl Biomes {
+    pub fn new(ids: Vec<String>) -> Self {
+        Self { ids: ids.into() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// The protocol id of a biome.
+    pub fn protocol_id(&self, id: &str) -> Result<i32, BiomeNotFound> {
+        let index = self
+            .ids
+            .iter()
+            .position(|biome| biome == id)
+            .context(BiomeNotFoundSnafu { id })?;
+        Ok(index as i32)
+    }
+
+    /// The id of the biome with the given protocol id.
+    pub fn id(&self, protocol_id: i32) -> Option<&str> {
+        let index = usize::try_from(protocol_id).ok()?;
+        self.ids.get(index).map(String::as_str)
+    }
+
+    /// The protocol id of plains, used in place of biomes that aren't
+    /// registered.
+    pub fn plains(&self) -> i32 {
+        self.protocol_id("minecraft:plains").unwrap_or(0)
+    }
+}
+
+/// The generated biomes, which a server sends unless
+/// [`Callbacks::registry_entries`](crate::server::Callbacks::registry_entries)
+/// adds more.
+impl Default for Biomes {
+    fn default() -> Self {
+        static CACHE: OnceLock<Biomes> = OnceLock::new();
+        CACHE
+            .get_or_init(|| {
+                let ids = REGISTRY_DATA
+                    .iter()
+                    .find(|&&(id, _)| id == "worldgen/biome")
+                    .map(|(_, entries)| entries.iter().map(|&(id, _)| id.to_owned()).collect())
+                    .unwrap_or_default();
+                Biomes::new(ids)
+            })
+            .clone()
+    }
+}
+
+pub fn block_entity_type(id: &str) -> Result<BlockEntityType, BlockEntityTypeNotFound> {
+    BlockEntityType::from_id(id).context(BlockEntityTypeNotFoundSnafu { id })
+}
+
+impl BlockState {
+    /// The id of the block, like `minecraft:stone`.
+    pub fn block_id(&self) -> &'static str {
+        self.block.id
+    }
+
+    /// The value of a property, if the block has it.
+    pub fn property(&self, name: &str) -> Option<&'static str> {
+        self.properties()
+            .find(|&(property, _)| property == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Every property and its value, sorted by property name.
+    pub fn properties(&self) -> impl Iterator<Item = (&'static str, &'static str)> + use<> {
+        let state = *self;
+        self.block.properties.iter().map(move |property| {
+            (
+                property.name,
+                property.values[state.index(property) as usize],
+            )
+        })
+    }
+
+    /// The index of this state's value in the property's values.
+    fn index(&self, property: &Property) -> i32 {
+        (self.id - self.block.first_state_id) / property.stride % property.values.len() as i32
+    }
 }
 
-pub fn block_state(
+fn find_block(id: &str) -> Result<&'static Block, BlockStateNotFound> {
+    let index = BLOCKS
+        .binary_search_by(|block| block.id.cmp(id))
+        .ok()
+        .context(BlockNotFoundSnafu { id })?;
+    Ok(&BLOCKS[index])
+}
+
+/// The state a block has when placed without any properties given.
+pub fn default_block_state(id: &str) -> Result<BlockState, BlockStateNotFound> {
+    let block = find_block(id)?;
+    Ok(BlockState {
+        id: block.default_state_id,
+        block,
+    })
+}
+
+/// The block state with the given properties, as stored in saves. Properties
+/// that aren't given take their value from the block's default state.
+pub fn block_state_with_properties(
     id: &str,
-    properties: &[(&'static str, &'static str)],
-) -> Result<&'static BlockState, BlockStateNotFound> {
-    let blocks = blocks();
-    let block = blocks.get(id).context(BlockNotFoundSnafu { id })?;
-    let state = block
-        .states
-        .iter()
-        .find(|s| {
-            properties
+    properties: &BTreeMap<String, String>,
+) -> Result<BlockState, BlockStateNotFound> {
+    let default = default_block_state(id)?;
+    let mut state_id = default.id;
+    for property in default.block.properties {
+        let Some(value) = properties.get(property.name) else {
+            continue;
+        };
+        let index =
+            property
+                .values
                 .iter()
-                .all(|&(k, v)| s.properties.get(k).is_some_and(|x| x == v))
-        })
-        .context(BlockStateNotFoundSnafu { id, properties })?;
-    Ok(state)
+                .position(|v| v == value)
+                .context(InvalidPropertiesSnafu {
+                    id,
+                    properties: properties.clone(),
+                })?;
+        state_id += (index as i32 - default.index(property)) * property.stride;
+    }
+    Ok(BlockState {
+        id: state_id,
+        block: default.block,
+    })
+}
+
+/// The state with the given id.
+pub fn block_state_by_id(state_id: i32) -> Option<BlockState> {
+    let index = *STATE_BLOCKS.get(usize::try_from(state_id).ok()?)?;
+    Some(BlockState {
+        id: state_id,
+        block: &BLOCKS[index as usize],
+    })
+}
+
+/// Every block state, in id order.
+pub fn block_states() -> impl Iterator<Item = BlockState> {
+    (0..STATE_BLOCKS.len() as i32).filter_map(block_state_by_id)
+}
+
+/// The number of block states, which sets the bits per entry of the global
+/// palette.
+pub fn block_state_count() -> usize {
+    STATE_BLOCKS.len()
+}
+
+/// The light level a block state gives off, from 0 to 15.
+pub fn light_emission(state_id: i32) -> u8 {
+    usize::try_from(state_id)
+        .ok()
+        .and_then(|index| LIGHT_EMISSION.get(index))
+        .copied()
+        .unwrap_or(0)
+}
+
+/// How much light is lost entering a block state: 0 for blocks that light
+/// passes through, 1 for leaves, fluids and the like and 15 for opaque
+/// blocks. Unknown states are opaque.
+pub fn light_dampening(state_id: i32) -> u8 {
+    usize::try_from(state_id)
+        .ok()
+        .and_then(|index| LIGHT_DAMPENING.get(index))
+        .copied()
+        .unwrap_or(15)
+}
+
+/// Whether a block state blocks motion or holds a fluid, which puts it in
+/// the `MOTION_BLOCKING` heightmaps.
+pub fn is_motion_blocking(state_id: i32) -> bool {
+    usize::try_from(state_id)
+        .ok()
+        .and_then(|index| MOTION_BLOCKING.get(index))
+        .copied()
+        .unwrap_or(false)
+}
+
+/// Whether a block state is leaves, which `MOTION_BLOCKING_NO_LEAVES`
+/// ignores.
+pub fn is_leaves(state_id: i32) -> bool {
+    usize::try_from(state_id)
+        .ok()
+        .and_then(|index| LEAVES.get(index))
+        .copied()
+        .unwrap_or(false)
+}
+
+/// Whether `state_id` is one of the air blocks, which don't count towards a
+/// chunk section's block count.
+pub fn is_air(state_id: i32) -> bool {
+    [
+        blocks::Air.state_id(),
+        blocks::CaveAir.state_id(),
+        blocks::VoidAir.state_id(),
+    ]
+    .contains(&state_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::registries::{
+        self, Biomes, block_state_by_id, block_state_with_properties, block_states,
+        synchronized_registries,
+    };
+
+    #[test]
+    fn test_block_states() {
+        for state in block_states() {
+            let properties: BTreeMap<_, _> = state
+                .properties()
+                .map(|(name, value)| (name.to_owned(), value.to_owned()))
+                .collect();
+            let found = block_state_with_properties(state.block_id(), &properties).unwrap();
+            assert_eq!(found.id, state.id);
+        }
+        assert_eq!(block_states().count(), registries::block_state_count());
+        assert!(block_state_by_id(-1).is_none());
+        assert!(block_state_by_id(registries::block_state_count() as i32).is_none());
+
+        let invalid = BTreeMap::from([("facing".to_owned(), "up".to_owned())]);
+        assert!(block_state_with_properties("minecraft:deepslate_tile_stairs", &invalid).is_err());
+        assert!(block_state_with_properties("minecraft:not_a_block", &BTreeMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_light_tables() {
+        use registries::{blocks, light_dampening, light_emission};
+
+        assert_eq!(light_emission(blocks::Glowstone.state_id()), 15);
+        assert_eq!(light_emission(blocks::Torch.state_id()), 14);
+        assert_eq!(light_emission(blocks::Stone.state_id()), 0);
+        let light = blocks::Light {
+            level: 6,
+            waterlogged: false,
+        };
+        assert_eq!(light_emission(light.state_id()), 6);
+
+        assert_eq!(light_dampening(blocks::Stone.state_id()), 15);
+        assert_eq!(light_dampening(blocks::Air.state_id()), 0);
+        assert_eq!(light_dampening(blocks::Glass.state_id()), 0);
+        assert_eq!(light_dampening(blocks::Torch.state_id()), 0);
+        assert_eq!(light_dampening(blocks::OakLeaves::default().state_id()), 1);
+        assert_eq!(light_dampening(blocks::Water::default().state_id()), 1);
+        let waterlogged = BTreeMap::from([("waterlogged".to_owned(), "true".to_owned())]);
+        let chest = block_state_with_properties("minecraft:chest", &waterlogged).unwrap();
+        assert_eq!(light_dampening(chest.id), 1);
+        assert_eq!(light_dampening(-1), 15);
+    }
+
+    #[test]
+    fn test_synchronized_registries() {
+        let registries = synchronized_registries();
+        assert!(!registries.is_empty());
+        for registry in registries {
+            assert!(!registry.entries.is_empty(), "{} is empty", registry.id);
+        }
+        let biome_registry = registries
+            .iter()
+            .find(|registry| registry.id == "worldgen/biome")
+            .unwrap();
+        assert!(
+            biome_registry
+                .entries
+                .iter()
+                .any(|(id, _)| id == "minecraft:plains")
+        );
+
+        let biomes = Biomes::default();
+        assert_eq!(biomes.len(), biome_registry.entries.len());
+        let plains = biomes.protocol_id("minecraft:plains").unwrap();
+        assert_eq!(plains, biomes.plains());
+        assert_eq!(biomes.id(plains), Some("minecraft:plains"));
+        assert_eq!(biomes.id(biomes.len() as i32), None);
+        assert!(biomes.protocol_id("minecraft:not_a_biome").is_err());
+    }
 }
@@ -0,0 +1,3 @@
+//! Block entity types by protocol id, generated from `registries.json`.
+
+include!(concat!(env!("OUT_DIR"), "/block_entity_types.rs"));
@@ -0,0 +1,53 @@
+//! A struct for every block, generated from `blocks.json`, with a field per
+//! property. States are built like
+//! `DeepslateTileStairs { facing: Facing::West, ..Default::default() }`, so a
+//! misspelt block, property or value doesn't compile, and
+//! [`DeepslateTileStairs::state_id`] works out the id without a lookup.
+
+// Defaults are written out even where they match the derived ones
+#![allow(clippy::derivable_impls)]
+
+include!(concat!(env!("OUT_DIR"), "/blocks.rs"));
+
+#[cfg(test)]
+mod tests {
+    use crate::registries::{
+        self,
+        blocks::{
+            DeepslateTileStairs, Glowstone,
+            properties::{Facing, Half, Shape},
+        },
+    };
+
+    #[test]
+    fn test_state_ids() {
+        let stairs = DeepslateTileStairs {
+            facing: Facing::West,
+            half: Half::Top,
+            shape: Shape::OuterLeft,
+            waterlogged: true,
+        };
+        let properties = [
+            ("facing", "west"),
+            ("half", "top"),
+            ("shape", "outer_left"),
+            ("waterlogged", "true"),
+        ]
+        .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        .into();
+        let expected =
+            registries::block_state_with_properties(DeepslateTileStairs::ID, &properties).unwrap();
+        assert_eq!(stairs.state_id(), expected.id);
+
+        assert_eq!(
+            DeepslateTileStairs::default().state_id(),
+            registries::default_block_state(DeepslateTileStairs::ID)
+                .unwrap()
+                .id
+        );
+        assert_eq!(
+            Glowstone.state_id(),
+            registries::default_block_state(Glowstone::ID).unwrap().id
+        );
+    }
+}
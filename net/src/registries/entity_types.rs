@@ -0,0 +1,22 @@
+//! Entity types by protocol id, generated from `registries.json`.
+
+include!(concat!(env!("OUT_DIR"), "/entity_types.rs"));
+
+#[cfg(test)]
+mod tests {
+    use crate::registries::entity_types::EntityType;
+
+    #[test]
+    fn test_protocol_ids() {
+        for (protocol_id, &entity_type) in EntityType::ALL.iter().enumerate() {
+            assert_eq!(entity_type.protocol_id(), protocol_id as i32);
+            assert_eq!(
+                EntityType::from_protocol_id(protocol_id as i32),
+                Some(entity_type)
+            );
+            assert_eq!(EntityType::from_id(entity_type.id()), Some(entity_type));
+        }
+        assert_eq!(EntityType::from_protocol_id(-1), None);
+        assert_eq!(EntityType::from_id("minecraft:not_an_entity"), None);
+    }
+}
@@ -0,0 +1,3 @@
+//! Items by protocol id, generated from `registries.json`.
+
+include!(concat!(env!("OUT_DIR"), "/items.rs"));
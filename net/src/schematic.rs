@@ -0,0 +1,459 @@
+//! Builds saved by other tools, read into a box of block states that can be
+//! placed into chunks.
+
+mod litematic;
+mod sponge;
+mod structure;
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+use flate2::read::GzDecoder;
+use snafu::prelude::*;
+
+use crate::{
+    chunk::{BlockEntity, Chunk},
+    nbt::{self, Tag},
+    packets::{Position, deserialize},
+    registries::{self, BlockEntityTypeNotFound, BlockStateNotFound},
+};
+
+/// A box of blocks, indexed from its minimum corner. Blocks that are `None`
+/// leave the world unchanged when placed, like structure voids.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schematic {
+    width: u32,
+    height: u32,
+    length: u32,
+    /// Indexed by `(y * length + z) * width + x`.
+    blocks: Vec<Option<i32>>,
+    block_entities: BTreeMap<Position, BlockEntity>,
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(transparent)]
+    IOError { source: io::Error },
+    #[snafu(transparent)]
+    NbtError { source: nbt::Error },
+    #[snafu(transparent)]
+    NbtDeError { source: nbt::de::Error },
+    #[snafu(transparent)]
+    NbtSerError { source: nbt::ser::Error },
+    #[snafu(transparent)]
+    DeserializeError { source: deserialize::Error },
+    #[snafu(transparent)]
+    BlockStateNotFound { source: BlockStateNotFound },
+    #[snafu(transparent)]
+    BlockEntityTypeNotFound { source: BlockEntityTypeNotFound },
+    #[snafu(display("Unrecognized schematic format"))]
+    UnknownFormat,
+    #[snafu(display("Unsupported {format} version: {version}"))]
+    UnsupportedVersion { format: &'static str, version: i32 },
+    #[snafu(display("Invalid block state: {state}"))]
+    InvalidBlockState { state: String },
+    #[snafu(display("Palette index {index} is out of range"))]
+    InvalidPaletteIndex { index: i32 },
+    #[snafu(display("Schematic has {found} blocks, expected {expected}"))]
+    WrongBlockCount { expected: usize, found: usize },
+    #[snafu(display("Schematic size should be three non-negative dimensions"))]
+    InvalidSize,
+    #[snafu(display("Schematic is too large: {width}x{height}x{length}"))]
+    TooLarge {
+        width: i64,
+        height: i64,
+        length: i64,
+    },
+    #[snafu(display("Block is missing its state or position"))]
+    InvalidBlock,
+    #[snafu(display("Block entity is missing its id or position"))]
+    InvalidBlockEntity,
+    #[snafu(display("Invalid block state id: {state_id}"))]
+    InvalidBlockStateId { state_id: i32 },
+    #[snafu(display("Invalid block entity type: {type_id}"))]
+    InvalidBlockEntityType { type_id: i32 },
+}
+
+/// The most blocks a schematic read from a file can have, so a corrupt size
+/// is an error rather than an allocation of many gigabytes.
+const MAX_BLOCKS: u64 = 1 << 27;
+
+/// `width * height * length` for sizes read from a file, or `None` if it
+/// overflows.
+fn block_count(width: i64, height: i64, length: i64) -> Option<u64> {
+    let [width, height, length] = [width, height, length].map(|size| u32::try_from(size).ok());
+    (width? as u64)
+        .checked_mul(height? as u64)?
+        .checked_mul(length? as u64)
+}
+
+impl Schematic {
+    /// An empty schematic, which changes nothing when placed.
+    pub fn new(width: u32, height: u32, length: u32) -> Self {
+        Self {
+            width,
+            height,
+            length,
+            blocks: vec![None; width as usize * height as usize * length as usize],
+            block_entities: BTreeMap::new(),
+        }
+    }
+
+    /// Like [`Schematic::new`], for sizes read from a file, which are checked
+    /// against [`MAX_BLOCKS`] before anything is allocated.
+    fn with_size(width: i64, height: i64, length: i64) -> Result<Self, Error> {
+        ensure!(width >= 0 && height >= 0 && length >= 0, InvalidSizeSnafu);
+        let volume = block_count(width, height, length);
+        ensure!(
+            volume.is_some_and(|volume| volume <= MAX_BLOCKS),
+            TooLargeSnafu {
+                width,
+                height,
+                length,
+            }
+        );
+        Ok(Self::new(width as u32, height as u32, length as u32))
+    }
+
+    /// Reads a Sponge `.schem`, structure block `.nbt` or `.litematic` file,
+    /// telling them apart by their contents.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::read(&fs::read(path)?)
+    }
+
+    /// Like [`Schematic::open`], for bytes that may be gzipped.
+    pub fn read(bytes: &[u8]) -> Result<Self, Error> {
+        let mut decompressed = Vec::new();
+        let bytes = if bytes.starts_with(&[0x1F, 0x8B]) {
+            GzDecoder::new(bytes).read_to_end(&mut decompressed)?;
+            &decompressed
+        } else {
+            bytes
+        };
+        let (_, tag) = Tag::from_file_bytes(bytes)?;
+        Self::from_nbt(tag)
+    }
+
+    pub fn from_nbt(mut tag: Tag) -> Result<Self, Error> {
+        if let Some(schematic) = tag.remove("Schematic") {
+            sponge::read(schematic)
+        } else if tag.get("BlockData").is_some() || tag.get("Blocks").is_some() {
+            sponge::read(tag)
+        } else if tag.get("Regions").is_some() {
+            litematic::read(tag)
+        } else if tag.get("blocks").is_some() {
+            structure::read(tag)
+        } else {
+            UnknownFormatSnafu.fail()
+        }
+    }
+
+    /// Writes a gzipped Sponge schematic, version 3.
+    pub fn save_sponge(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        fs::write(path, self.to_sponge_bytes()?)?;
+        Ok(())
+    }
+
+    pub fn to_sponge_bytes(&self) -> Result<Vec<u8>, Error> {
+        let bytes = self.to_sponge()?.to_file_bytes("");
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        io::Write::write_all(&mut encoder, &bytes)?;
+        Ok(encoder.finish()?)
+    }
+
+    /// The root compound of a Sponge schematic, version 3.
+    pub fn to_sponge(&self) -> Result<Tag, Error> {
+        sponge::write(self)
+    }
+
+    /// Copies the blocks from `min` to `min + size` out of loaded chunks.
+    /// Blocks in chunks that aren't loaded are left as `None`.
+    pub fn from_chunks(
+        chunks: &BTreeMap<(i32, i32), Chunk>,
+        min: Position,
+        (width, height, length): (u32, u32, u32),
+    ) -> Self {
+        let mut schematic = Self::new(width, height, length);
+        for (x, y, z) in schematic.positions() {
+            let (world_x, world_y, world_z) =
+                (min.x + x as i32, min.y + y as i32, min.z + z as i32);
+            let Some(chunk) = chunks.get(&(world_x.div_euclid(16), world_z.div_euclid(16))) else {
+                continue;
+            };
+            if !chunk.y_range().contains(&world_y) {
+                continue;
+            }
+            let (cx, cy, cz) = (
+                world_x.rem_euclid(16) as u8,
+                world_y,
+                world_z.rem_euclid(16) as u8,
+            );
+            schematic.set_block(x, y, z, Some(chunk.block(cx, cy, cz)));
+            if let Some(block_entity) = chunk.block_entity(cx, cy, cz) {
+                schematic.set_block_entity(x, y, z, block_entity.clone());
+            }
+        }
+        schematic
+    }
+
+    /// Places the schematic with its minimum corner at `origin`, creating
+    /// empty chunks of `subchunk_count` sections from `min_y` where none are
+    /// loaded. Blocks outside the world's height are skipped.
+    pub fn place(
+        &self,
+        chunks: &mut BTreeMap<(i32, i32), Chunk>,
+        origin: Position,
+        min_y: i32,
+        subchunk_count: u8,
+    ) {
+        for (x, y, z) in self.positions() {
+            let Some(state_id) = self.block(x, y, z) else {
+                continue;
+            };
+            let (world_x, world_y, world_z) = (
+                origin.x + x as i32,
+                origin.y + y as i32,
+                origin.z + z as i32,
+            );
+            let chunk = chunks
+                .entry((world_x.div_euclid(16), world_z.div_euclid(16)))
+                .or_insert_with(|| Chunk::empty(min_y, subchunk_count));
+            if !chunk.y_range().contains(&world_y) {
+                continue;
+            }
+            let (cx, cy, cz) = (
+                world_x.rem_euclid(16) as u8,
+                world_y,
+                world_z.rem_euclid(16) as u8,
+            );
+            chunk.set_block_id(cx, cy, cz, state_id);
+            match self.block_entity(x, y, z) {
+                Some(block_entity) => chunk.set_block_entity(cx, cy, cz, block_entity.clone()),
+                None => {
+                    chunk.remove_block_entity(cx, cy, cz);
+                }
+            }
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    /// The block state id at the given position, or `None` if placing the
+    /// schematic leaves it unchanged.
+    pub fn block(&self, x: u32, y: u32, z: u32) -> Option<i32> {
+        self.blocks[self.index(x, y, z)]
+    }
+
+    pub fn set_block(&mut self, x: u32, y: u32, z: u32, state_id: Option<i32>) {
+        let index = self.index(x, y, z);
+        self.blocks[index] = state_id;
+    }
+
+    pub fn block_entity(&self, x: u32, y: u32, z: u32) -> Option<&BlockEntity> {
+        self.block_entities
+            .get(&Position::new(x as i32, y as i32, z as i32))
+    }
+
+    pub fn set_block_entity(&mut self, x: u32, y: u32, z: u32, block_entity: BlockEntity) {
+        self.block_entities
+            .insert(Position::new(x as i32, y as i32, z as i32), block_entity);
+    }
+
+    /// Every block entity, relative to the minimum corner.
+    pub fn block_entities(&self) -> impl Iterator<Item = (Position, &BlockEntity)> {
+        self.block_entities
+            .iter()
+            .map(|(&position, block_entity)| (position, block_entity))
+    }
+
+    #[inline]
+    fn index(&self, x: u32, y: u32, z: u32) -> usize {
+        assert!(x < self.width && y < self.height && z < self.length);
+        (y as usize * self.length as usize + z as usize) * self.width as usize + x as usize
+    }
+
+    /// Every position in the schematic, in index order.
+    fn positions(&self) -> impl Iterator<Item = (u32, u32, u32)> + use<> {
+        let (width, height, length) = (self.width, self.height, self.length);
+        (0..height)
+            .flat_map(move |y| (0..length).flat_map(move |z| (0..width).map(move |x| (x, y, z))))
+    }
+
+    /// Adds a block entity from a file. Ones whose position is outside the
+    /// schematic are dropped.
+    fn insert_block_entity(
+        &mut self,
+        position: Position,
+        id: &str,
+        data: Tag,
+    ) -> Result<(), Error> {
+        let in_bounds = (0..self.width as i32).contains(&position.x)
+            && (0..self.height as i32).contains(&position.y)
+            && (0..self.length as i32).contains(&position.z);
+        if in_bounds {
+            self.block_entities
+                .insert(position, BlockEntity::new(id, data)?);
+        }
+        Ok(())
+    }
+}
+
+/// Parses a block state like `minecraft:oak_stairs[facing=east,half=top]`.
+fn parse_block_state(state: &str) -> Result<i32, Error> {
+    let (name, properties) = match state.split_once('[') {
+        Some((name, properties)) => {
+            let properties = properties
+                .strip_suffix(']')
+                .context(InvalidBlockStateSnafu { state })?;
+            (name, properties)
+        }
+        None => (state, ""),
+    };
+    let properties = properties
+        .split(',')
+        .filter(|property| !property.is_empty())
+        .map(|property| {
+            let (k, v) = property
+                .split_once('=')
+                .context(InvalidBlockStateSnafu { state })?;
+            Ok((k.to_owned(), v.to_owned()))
+        })
+        .collect::<Result<BTreeMap<_, _>, Error>>()?;
+    let name = if name.contains(':') {
+        name.to_owned()
+    } else {
+        format!("minecraft:{name}")
+    };
+    Ok(registries::block_state_with_properties(&name, &properties)?.id)
+}
+
+/// The inverse of [`parse_block_state`].
+fn format_block_state(state_id: i32) -> Result<String, Error> {
+    let state =
+        registries::block_state_by_id(state_id).context(InvalidBlockStateIdSnafu { state_id })?;
+    let name = state.block_id();
+    let properties = state
+        .properties()
+        .map(|(k, v)| format!("{k}={v}"))
+        .collect::<Vec<_>>();
+    if properties.is_empty() {
+        return Ok(name.to_owned());
+    }
+    Ok(format!("{name}[{}]", properties.join(",")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::{
+        chunk::{AIR, BlockEntity, Chunk},
+        nbt::Tag,
+        packets::Position,
+        registries::blocks::{
+            self, DeepslateTileStairs,
+            properties::{Facing, Half},
+        },
+        schematic::{Error, Schematic, format_block_state, parse_block_state},
+    };
+
+    fn stairs() -> i32 {
+        DeepslateTileStairs {
+            facing: Facing::West,
+            half: Half::Top,
+            ..Default::default()
+        }
+        .state_id()
+    }
+
+    fn sign() -> BlockEntity {
+        BlockEntity::new(
+            "minecraft:sign",
+            Tag::Compound(vec![("is_waxed".to_owned(), Tag::Byte(1))]),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_block_state_strings() {
+        let stairs = stairs();
+        let string = format_block_state(stairs).unwrap();
+        assert!(string.starts_with("minecraft:deepslate_tile_stairs[facing=west,half=top"));
+        assert_eq!(parse_block_state(&string).unwrap(), stairs);
+        assert_eq!(parse_block_state("air").unwrap(), AIR);
+        assert!(parse_block_state("minecraft:stone[").is_err());
+        assert!(parse_block_state("minecraft:not_a_block").is_err());
+    }
+
+    #[test]
+    fn test_sponge_round_trip() {
+        let stone = blocks::Stone.state_id();
+        let mut schematic = Schematic::new(3, 2, 5);
+        schematic.set_block(0, 0, 0, Some(stone));
+        schematic.set_block(2, 1, 4, Some(stairs()));
+        schematic.set_block(1, 1, 1, Some(AIR));
+        schematic.set_block_entity(2, 1, 4, sign());
+
+        let read = Schematic::read(&schematic.to_sponge_bytes().unwrap()).unwrap();
+        // Sponge has no way to leave blocks unchanged, so they become air
+        assert_eq!(read.block(0, 0, 0), Some(stone));
+        assert_eq!(read.block(2, 1, 4), Some(stairs()));
+        assert_eq!(read.block(1, 0, 3), Some(AIR));
+        assert_eq!(read.block_entity(2, 1, 4), Some(&sign()));
+        assert_eq!(read.block_entities().count(), 1);
+    }
+
+    #[test]
+    fn test_sponge_size_mismatch() {
+        // 65535 as a short, for a schematic far bigger than its data
+        let tag: Tag = "{Schematic: {Version: 3, DataVersion: 4189, Width: -1s, Height: -1s, \
+            Length: 1s, Blocks: {Palette: {\"minecraft:air\": 0}, Data: [B; 0B, 0B]}}}"
+            .parse()
+            .unwrap();
+        assert!(matches!(
+            Schematic::from_nbt(tag),
+            Err(Error::WrongBlockCount { found: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_place_and_copy() {
+        let stone = blocks::Stone.state_id();
+        let mut schematic = Schematic::new(2, 2, 2);
+        schematic.set_block(0, 0, 0, Some(stone));
+        schematic.set_block(1, 1, 1, Some(stairs()));
+        schematic.set_block_entity(1, 1, 1, sign());
+
+        let mut chunks = BTreeMap::new();
+        chunks.insert((0, 0), Chunk::from_fn(0, 1, |_, _, _| stone));
+        schematic.place(&mut chunks, Position::new(15, 3, -1), 0, 1);
+
+        assert_eq!(chunks.len(), 3);
+        // Unset blocks leave the world alone
+        assert_eq!(chunks[&(0, 0)].block(15, 4, 0), stone);
+        assert_eq!(chunks[&(0, -1)].block(15, 3, 15), stone);
+        assert_eq!(chunks[&(1, 0)].block(0, 4, 0), stairs());
+        assert_eq!(chunks[&(1, 0)].block_entity(0, 4, 0), Some(&sign()));
+
+        let copy = Schematic::from_chunks(&chunks, Position::new(15, 3, -1), (2, 2, 2));
+        assert_eq!(copy.block(0, 0, 0), Some(stone));
+        assert_eq!(copy.block(1, 0, 0), None);
+        assert_eq!(copy.block(0, 1, 1), Some(stone));
+        assert_eq!(copy.block(1, 1, 1), Some(stairs()));
+        assert_eq!(copy.block_entity(1, 1, 1), Some(&sign()));
+    }
+}
@@ -0,0 +1,271 @@
+//! The format of the Litematica mod, which can hold several boxes ("regions")
+//! placed relative to each other.
+
+use serde::Deserialize;
+use snafu::prelude::*;
+
+use crate::{
+    anvil::BlockStateNbt,
+    nbt::{self, LongArray, Tag},
+    packets::Position,
+    schematic::{
+        Error, InvalidBlockEntitySnafu, InvalidPaletteIndexSnafu, Schematic,
+        UnsupportedVersionSnafu, WrongBlockCountSnafu, block_count,
+    },
+};
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+struct Vec3 {
+    x: i32,
+    y: i32,
+    z: i32,
+}
+
+#[derive(Debug, Deserialize)]
+struct RegionNbt {
+    #[serde(rename = "Position")]
+    position: Vec3,
+    /// Negative when the region was selected from its far corner.
+    #[serde(rename = "Size")]
+    size: Vec3,
+    #[serde(rename = "BlockStatePalette")]
+    palette: Vec<BlockStateNbt>,
+    #[serde(rename = "BlockStates")]
+    block_states: LongArray,
+}
+
+struct Region {
+    min: Vec3,
+    size: Vec3,
+    blocks: Vec<i32>,
+    block_entities: Vec<Tag>,
+}
+
+pub(super) fn read(mut tag: Tag) -> Result<Schematic, Error> {
+    let version = match tag.get("Version") {
+        Some(&Tag::Int(version)) => version,
+        _ => 0,
+    };
+    ensure!(
+        (2..=7).contains(&version),
+        UnsupportedVersionSnafu {
+            format: "Litematica schematic",
+            version,
+        }
+    );
+
+    let regions = match tag.remove("Regions") {
+        Some(Tag::Compound(regions)) => regions,
+        _ => Vec::new(),
+    };
+    let regions = regions
+        .into_iter()
+        .map(|(_, region)| read_region(region))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let min = |axis: fn(Vec3) -> i32| regions.iter().map(|r| axis(r.min)).min().unwrap_or(0);
+    let max = |axis: fn(Vec3) -> i32| {
+        regions
+            .iter()
+            .map(|r| axis(r.min) as i64 + axis(r.size) as i64)
+            .max()
+            .unwrap_or(0)
+    };
+    let origin = Vec3 {
+        x: min(|v| v.x),
+        y: min(|v| v.y),
+        z: min(|v| v.z),
+    };
+    let mut schematic = Schematic::with_size(
+        max(|v| v.x) - origin.x as i64,
+        max(|v| v.y) - origin.y as i64,
+        max(|v| v.z) - origin.z as i64,
+    )?;
+
+    for region in regions {
+        let offset = Vec3 {
+            x: region.min.x - origin.x,
+            y: region.min.y - origin.y,
+            z: region.min.z - origin.z,
+        };
+        for (i, &state_id) in region.blocks.iter().enumerate() {
+            let (width, length) = (region.size.x as usize, region.size.z as usize);
+            let (x, y, z) = (i % width, i / (width * length), i / width % length);
+            schematic.set_block(
+                (offset.x + x as i32) as u32,
+                (offset.y + y as i32) as u32,
+                (offset.z + z as i32) as u32,
+                Some(state_id),
+            );
+        }
+
+        for mut block_entity in region.block_entities {
+            let (Some(Tag::Int(x)), Some(Tag::Int(y)), Some(Tag::Int(z))) = (
+                block_entity.remove("x"),
+                block_entity.remove("y"),
+                block_entity.remove("z"),
+            ) else {
+                return InvalidBlockEntitySnafu.fail();
+            };
+            // Older versions don't save the id
+            let Some(Tag::String(id)) = block_entity.remove("id") else {
+                continue;
+            };
+            let position = Position::new(offset.x + x, offset.y + y, offset.z + z);
+            schematic.insert_block_entity(position, &id, block_entity)?;
+        }
+    }
+
+    Ok(schematic)
+}
+
+fn read_region(mut tag: Tag) -> Result<Region, Error> {
+    let block_entities = match tag.remove("TileEntities") {
+        Some(Tag::List(block_entities)) => block_entities,
+        _ => Vec::new(),
+    };
+    for key in ["Entities", "PendingBlockTicks", "PendingFluidTicks"] {
+        tag.remove(key);
+    }
+    let region: RegionNbt = nbt::from_tag(tag)?;
+
+    // The position is the corner the selection started from
+    let corner = |position: i32, size: i32| {
+        if size < 0 {
+            position.saturating_add(size + 1)
+        } else {
+            position
+        }
+    };
+    let (position, size) = (region.position, region.size);
+    let min = Vec3 {
+        x: corner(position.x, size.x),
+        y: corner(position.y, size.y),
+        z: corner(position.z, size.z),
+    };
+    let size = Vec3 {
+        x: size.x.saturating_abs(),
+        y: size.y.saturating_abs(),
+        z: size.z.saturating_abs(),
+    };
+
+    let palette = region
+        .palette
+        .iter()
+        .map(BlockStateNbt::state_id)
+        .collect::<Result<Vec<_>, _>>()?;
+    let bits = (usize::BITS - palette.len().saturating_sub(1).leading_zeros()).max(2);
+    // The longs hold exactly enough bits for the region, so a size that needs
+    // more is corrupt, and is caught before unpacking allocates for it
+    let available = region.block_states.0.len() as u64 * 64 / bits as u64;
+    let len = block_count(size.x.into(), size.y.into(), size.z.into()).unwrap_or(u64::MAX);
+    ensure!(
+        len <= available,
+        WrongBlockCountSnafu {
+            expected: len as usize,
+            found: available as usize,
+        }
+    );
+    let blocks = unpack(bits, &region.block_states.0, len as usize)
+        .into_iter()
+        .map(|index| {
+            let index = index as i32;
+            palette
+                .get(index as usize)
+                .copied()
+                .context(InvalidPaletteIndexSnafu { index })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Region {
+        min,
+        size,
+        blocks,
+        block_entities,
+    })
+}
+
+/// Unlike chunk palettes, Litematica packs values tightly, so a value can
+/// span two longs.
+fn unpack(bits: u32, longs: &[i64], len: usize) -> Vec<u64> {
+    let mask = u64::MAX >> (64 - bits);
+    let long = |i: usize| longs.get(i).copied().unwrap_or(0) as u64;
+    (0..len)
+        .map(|i| {
+            let start = i * bits as usize;
+            let (index, offset) = (start / 64, start % 64);
+            let mut value = long(index) >> offset;
+            if offset + bits as usize > 64 {
+                value |= long(index + 1) << (64 - offset);
+            }
+            value & mask
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chunk::AIR,
+        nbt::Tag,
+        registries::blocks::{self, OakSign},
+        schematic::{Schematic, litematic::unpack},
+    };
+
+    #[test]
+    fn test_unpack_spanning() {
+        // Three 24-bit values, the last spanning both longs
+        let longs = [0x3333_2222_2211_1111, 0x33];
+        assert_eq!(unpack(24, &longs, 3), [0x111111, 0x222222, 0x333333]);
+    }
+
+    #[test]
+    fn test_read_litematic() {
+        let stone = blocks::Stone.state_id();
+        // Two regions: a 2x1x1 row selected backwards along x, and a single
+        // block above it holding a sign
+        let tag: Tag = r#"{
+            Version: 6,
+            Regions: {
+                a: {
+                    Position: {x: 1, y: 0, z: 0},
+                    Size: {x: -2, y: 1, z: 1},
+                    BlockStatePalette: [{Name: "minecraft:air"}, {Name: "minecraft:stone"}],
+                    BlockStates: [L; 4L],
+                    TileEntities: []
+                },
+                b: {
+                    Position: {x: 1, y: 1, z: 0},
+                    Size: {x: 1, y: 1, z: 1},
+                    BlockStatePalette: [
+                        {Name: "minecraft:air"},
+                        {Name: "minecraft:oak_sign", Properties: {rotation: "4"}}
+                    ],
+                    BlockStates: [L; 1L],
+                    TileEntities: [{x: 0, y: 0, z: 0, id: "minecraft:sign", is_waxed: 1b}]
+                }
+            }
+        }"#
+        .parse()
+        .unwrap();
+
+        let schematic = Schematic::from_nbt(tag).unwrap();
+        assert_eq!(
+            (schematic.width(), schematic.height(), schematic.length()),
+            (2, 2, 1)
+        );
+        assert_eq!(schematic.block(0, 0, 0), Some(AIR));
+        assert_eq!(schematic.block(1, 0, 0), Some(stone));
+        assert_eq!(schematic.block(0, 1, 0), None);
+        let sign = OakSign {
+            rotation: 4,
+            waterlogged: false,
+        }
+        .state_id();
+        assert_eq!(schematic.block(1, 1, 0), Some(sign));
+        assert_eq!(
+            schematic.block_entity(1, 1, 0).unwrap().data,
+            Tag::Compound(vec![("is_waxed".to_owned(), Tag::Byte(1))])
+        );
+    }
+}
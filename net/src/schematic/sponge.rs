@@ -0,0 +1,190 @@
+//! https://github.com/SpongePowered/Schematic-Specification
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use snafu::prelude::*;
+
+use crate::{
+    anvil::DATA_VERSION,
+    chunk::AIR,
+    nbt::{self, ByteArray, IntArray, Tag},
+    packets::{Position, deserialize::Deserializer, serialize::Serializer},
+    registries::block_entity_types::BlockEntityType,
+    schematic::{
+        Error, InvalidBlockEntitySnafu, InvalidBlockEntityTypeSnafu, InvalidPaletteIndexSnafu,
+        Schematic, UnsupportedVersionSnafu, WrongBlockCountSnafu, block_count, format_block_state,
+        parse_block_state,
+    },
+};
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Header {
+    #[serde(rename = "Version")]
+    version: i32,
+    #[serde(rename = "DataVersion", default)]
+    data_version: i32,
+    #[serde(rename = "Width")]
+    width: i16,
+    #[serde(rename = "Height")]
+    height: i16,
+    #[serde(rename = "Length")]
+    length: i16,
+    #[serde(rename = "Offset", default)]
+    offset: Option<IntArray>,
+}
+
+/// The block palette and data, which are at the top level in version 2 and
+/// in `Blocks` in version 3.
+#[derive(Debug, Deserialize, Serialize)]
+struct Blocks {
+    #[serde(rename = "Palette")]
+    palette: BTreeMap<String, i32>,
+    #[serde(rename = "Data", alias = "BlockData")]
+    data: ByteArray,
+}
+
+pub(super) fn read(mut tag: Tag) -> Result<Schematic, Error> {
+    let header: Header = nbt::from_tag(tag.clone())?;
+    let (blocks, block_entities) = match header.version {
+        2 => {
+            let block_entities = tag.remove("BlockEntities");
+            (tag, block_entities)
+        }
+        3 => {
+            let mut blocks = tag.remove("Blocks").unwrap_or(Tag::Compound(Vec::new()));
+            let block_entities = blocks.remove("BlockEntities");
+            (blocks, block_entities)
+        }
+        version => {
+            return UnsupportedVersionSnafu {
+                format: "Sponge schematic",
+                version,
+            }
+            .fail();
+        }
+    };
+
+    let (width, height, length) = (
+        header.width as u16 as i64,
+        header.height as u16 as i64,
+        header.length as u16 as i64,
+    );
+    // Version 3 allows leaving out the blocks entirely
+    if blocks.get("Palette").is_none() {
+        return Schematic::with_size(width, height, length);
+    }
+    let Blocks { palette, data } = nbt::from_tag(blocks)?;
+
+    // Each varint ends with a byte without the continuation bit, so the block
+    // count can be checked before allocating for it
+    let data = data.0.iter().map(|&b| b as u8).collect::<Vec<_>>();
+    let count = data.iter().filter(|&&b| b & 0x80 == 0).count();
+    let expected = block_count(width, height, length).unwrap_or(u64::MAX);
+    ensure!(
+        count as u64 == expected,
+        WrongBlockCountSnafu {
+            expected: expected as usize,
+            found: count,
+        }
+    );
+    let mut schematic = Schematic::with_size(width, height, length)?;
+
+    let palette = palette
+        .into_iter()
+        .map(|(state, index)| Ok((index, parse_block_state(&state)?)))
+        .collect::<Result<BTreeMap<_, _>, Error>>()?;
+    let mut d = Deserializer::new(&data);
+    let mut values = Vec::with_capacity(count);
+    while d.remaining() > 0 {
+        let index = d.deserialize_varint()?;
+        let state_id = palette
+            .get(&index)
+            .context(InvalidPaletteIndexSnafu { index })?;
+        values.push(Some(*state_id));
+    }
+    schematic.blocks = values;
+
+    let block_entities = match block_entities {
+        Some(Tag::List(block_entities)) => block_entities,
+        _ => Vec::new(),
+    };
+    for mut block_entity in block_entities {
+        let (Some(Tag::IntArray(pos)), Some(Tag::String(id))) = (
+            block_entity.remove("Pos"),
+            block_entity
+                .remove("Id")
+                .or_else(|| block_entity.remove("id")),
+        ) else {
+            return InvalidBlockEntitySnafu.fail();
+        };
+        let &[x, y, z] = pos.as_slice() else {
+            return InvalidBlockEntitySnafu.fail();
+        };
+        // Version 3 nests the fields in `Data`, version 2 doesn't
+        let data = block_entity.remove("Data").unwrap_or(block_entity);
+        schematic.insert_block_entity(Position::new(x, y, z), &id, data)?;
+    }
+
+    Ok(schematic)
+}
+
+/// Writes version 3, with blocks the schematic leaves unchanged as air.
+pub(super) fn write(schematic: &Schematic) -> Result<Tag, Error> {
+    let mut palette = BTreeMap::new();
+    let mut s = Serializer::new();
+    for block in &schematic.blocks {
+        let state_id = block.unwrap_or(AIR);
+        let next = palette.len() as i32;
+        let index = *palette.entry(state_id).or_insert(next);
+        s.serialize_varint(index);
+    }
+    let data = s.finish().into_iter().map(|b| b as i8).collect();
+
+    let Tag::Compound(mut blocks) = nbt::to_tag(&Blocks {
+        palette: palette
+            .into_iter()
+            .map(|(state_id, index)| Ok((format_block_state(state_id)?, index)))
+            .collect::<Result<_, Error>>()?,
+        data: ByteArray(data),
+    })?
+    else {
+        unreachable!("Structs serialize to compounds");
+    };
+    let block_entities = schematic
+        .block_entities()
+        .map(|(position, block_entity)| {
+            let type_id = block_entity.type_id;
+            let id = BlockEntityType::from_protocol_id(type_id)
+                .context(InvalidBlockEntityTypeSnafu { type_id })?
+                .id();
+            Ok(Tag::Compound(vec![
+                (
+                    "Pos".to_owned(),
+                    Tag::IntArray(vec![position.x, position.y, position.z]),
+                ),
+                ("Id".to_owned(), Tag::from(id)),
+                ("Data".to_owned(), block_entity.data.clone()),
+            ]))
+        })
+        .collect::<Result<_, Error>>()?;
+    blocks.push(("BlockEntities".to_owned(), Tag::List(block_entities)));
+
+    let Tag::Compound(mut root) = nbt::to_tag(&Header {
+        version: 3,
+        data_version: DATA_VERSION,
+        width: schematic.width as i16,
+        height: schematic.height as i16,
+        length: schematic.length as i16,
+        offset: Some(IntArray(vec![0, 0, 0])),
+    })?
+    else {
+        unreachable!("Structs serialize to compounds");
+    };
+    root.push(("Blocks".to_owned(), Tag::Compound(blocks)));
+
+    Ok(Tag::Compound(vec![(
+        "Schematic".to_owned(),
+        Tag::Compound(root),
+    )]))
+}
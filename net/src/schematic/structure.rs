@@ -0,0 +1,149 @@
+//! https://minecraft.wiki/w/Structure_file
+
+use serde::Deserialize;
+use snafu::prelude::*;
+
+use crate::{
+    anvil::BlockStateNbt,
+    nbt::{self, Tag},
+    packets::Position,
+    schematic::{
+        Error, InvalidBlockEntitySnafu, InvalidBlockSnafu, InvalidPaletteIndexSnafu,
+        InvalidSizeSnafu, Schematic,
+    },
+};
+
+#[derive(Debug, Deserialize)]
+struct StructureNbt {
+    size: Vec<i32>,
+    #[serde(default)]
+    palette: Vec<BlockStateNbt>,
+    /// Randomized variants used by shipwrecks, which have no `palette`.
+    #[serde(default)]
+    palettes: Vec<Vec<BlockStateNbt>>,
+}
+
+pub(super) fn read(mut tag: Tag) -> Result<Schematic, Error> {
+    let blocks = tag.remove("blocks");
+    tag.remove("entities");
+    let structure: StructureNbt = nbt::from_tag(tag)?;
+
+    let &[width, height, length] = structure.size.as_slice() else {
+        return InvalidSizeSnafu.fail();
+    };
+    let mut schematic = Schematic::with_size(width.into(), height.into(), length.into())?;
+    let palette = if structure.palette.is_empty() {
+        structure.palettes.into_iter().next().unwrap_or_default()
+    } else {
+        structure.palette
+    };
+    let palette = palette
+        .iter()
+        .map(|state| match state.name.as_str() {
+            "minecraft:structure_void" => Ok(None),
+            _ => state.state_id().map(Some),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let blocks = match blocks {
+        Some(Tag::List(blocks)) => blocks,
+        _ => Vec::new(),
+    };
+    for mut block in blocks {
+        let (Some(Tag::Int(index)), Some(Tag::List(pos))) =
+            (block.remove("state"), block.remove("pos"))
+        else {
+            return InvalidBlockSnafu.fail();
+        };
+        let &[Tag::Int(x), Tag::Int(y), Tag::Int(z)] = pos.as_slice() else {
+            return InvalidBlockSnafu.fail();
+        };
+        let state_id = *palette
+            .get(index as usize)
+            .context(InvalidPaletteIndexSnafu { index })?;
+        let in_bounds =
+            (0..width).contains(&x) && (0..height).contains(&y) && (0..length).contains(&z);
+        if !in_bounds {
+            continue;
+        }
+        schematic.set_block(x as u32, y as u32, z as u32, state_id);
+
+        if let Some(mut nbt) = block.remove("nbt") {
+            let Some(Tag::String(id)) = nbt.remove("id") else {
+                return InvalidBlockEntitySnafu.fail();
+            };
+            schematic.insert_block_entity(Position::new(x, y, z), &id, nbt)?;
+        }
+    }
+
+    Ok(schematic)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chunk::AIR,
+        nbt::Tag,
+        registries::{self, blocks},
+        schematic::{Error, Schematic},
+    };
+
+    #[test]
+    fn test_read_structure() {
+        let stone = blocks::Stone.state_id();
+        let tag: Tag = r#"{
+            DataVersion: 4189,
+            size: [2, 1, 2],
+            palette: [
+                {Name: "minecraft:stone"},
+                {Name: "minecraft:air"},
+                {Name: "minecraft:structure_void"},
+                {Name: "minecraft:chest", Properties: {facing: "north"}}
+            ],
+            blocks: [
+                {state: 0, pos: [0, 0, 0]},
+                {state: 1, pos: [1, 0, 0]},
+                {state: 2, pos: [0, 0, 1]},
+                {state: 3, pos: [1, 0, 1], nbt: {id: "minecraft:chest", Items: []}}
+            ],
+            entities: []
+        }"#
+        .parse()
+        .unwrap();
+
+        let schematic = Schematic::from_nbt(tag).unwrap();
+        assert_eq!(schematic.block(0, 0, 0), Some(stone));
+        assert_eq!(schematic.block(1, 0, 0), Some(AIR));
+        assert_eq!(schematic.block(0, 0, 1), None);
+        let chest = schematic.block_entity(1, 0, 1).unwrap();
+        assert_eq!(
+            chest.type_id,
+            registries::block_entity_type("minecraft:chest")
+                .unwrap()
+                .protocol_id()
+        );
+        assert_eq!(
+            chest.data,
+            Tag::Compound(vec![("Items".to_owned(), Tag::List(vec![]))])
+        );
+    }
+
+    #[test]
+    fn test_invalid_size() {
+        let read = |size: &str| {
+            let tag: Tag = format!("{{DataVersion: 4189, size: {size}, palette: [], blocks: []}}")
+                .parse()
+                .unwrap();
+            Schematic::from_nbt(tag)
+        };
+        assert!(matches!(read("[-1, 1, 1]"), Err(Error::InvalidSize)));
+        assert!(matches!(
+            read("[65536, 65536, 1]"),
+            Err(Error::TooLarge { .. })
+        ));
+        assert!(matches!(
+            read("[2048, 2048, 64]"),
+            Err(Error::TooLarge { .. })
+        ));
+    }
+}
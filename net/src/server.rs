@@ -1,15 +1,17 @@
 use std::{
     io,
+    num::NonZeroU32,
     sync::Arc,
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use async_trait::async_trait;
+use serde::Serialize;
 use snafu::prelude::*;
 use tokio::net::TcpListener;
-use uuid::Uuid;
 
 use crate::{
+    auth::{self, Authenticator},
     connection::{self, Connection, ServerboundPacket},
     nbt,
     packets::{
@@ -17,12 +19,16 @@ use crate::{
             self,
             clientbound::{KnownPack, RegistryEntry},
         },
-        deserialize, handshake, login, play,
+        deserialize, handshake,
+        login::{self, clientbound::GameProfile},
+        play,
         status::{
             self,
             clientbound::{Players, Status, TextComponent, Version},
         },
     },
+    registries::{self, Biomes, SynchronizedRegistry},
+    world::{Dimension, Player, World},
 };
 
 #[async_trait]
@@ -37,24 +43,133 @@ pub trait Callbacks: Send + Sync {
         Players { max: 20, online: 0 }
     }
 
-    fn dimension_data(&self) -> DimensionData;
+    /// Adds or replaces entries of a synchronized registry, such as
+    /// `worldgen/biome`, before it's ever sent. Called once per registry when
+    /// the server is created. New entries should be appended, since the
+    /// ids of the generated ones are used by chunks. Changed entries are sent
+    /// in full, even to clients that have the vanilla core pack.
+    fn registry_entries(&self, _registry_id: &str, _entries: &mut Vec<(String, nbt::Tag)>) {}
 
-    async fn on_login(&self, conn: &mut Connection) -> Result<(), Error>;
-    async fn on_tick(&self, conn: &mut Connection) -> Result<(), Error>;
+    /// Chooses the profile a player logs in with. `profile` is the verified
+    /// profile in online mode, or the vanilla offline profile otherwise.
+    async fn game_profile(&self, profile: GameProfile) -> Result<GameProfile, Error> {
+        Ok(profile)
+    }
+
+    /// Sends the initial play state to a player before they join the world.
+    /// Chunks are streamed by the server once the client reports where it
+    /// is, which it does after the first `PlayerPosition`.
+    async fn on_login(&self, world: &World, player: &Player) -> Result<(), Error>;
+
+    /// Called once the player is visible to the rest of the world.
+    async fn on_join(&self, _world: &World, _player: &Player) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called after the player has been removed from the world.
+    async fn on_leave(&self, _world: &World, _player: &Player) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called whenever a player's client finishes a tick, for client-specific
+    /// work. Runs at the client's frame rate, not the server's tick rate.
+    async fn on_tick(&self, _player: &Player) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called once per server tick, whether or not any players are online.
+    async fn on_world_tick(&self, _world: &World, _tick: u64) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called when the tick loop has fallen `behind` schedule and skips ahead
+    /// rather than trying to catch up.
+    fn on_tick_overrun(&self, behind: Duration, skipped_ticks: u64) {
+        eprintln!(
+            "Can't keep up! Is the server overloaded? Running {}ms or {skipped_ticks} ticks behind",
+            behind.as_millis(),
+        );
+    }
 }
 
 pub struct Server {
     callbacks: Box<dyn Callbacks>,
+    compression_threshold: Option<usize>,
+    authenticator: Option<Authenticator>,
+    world: World,
+    tick_duration: Duration,
+    registries: Vec<RegistryData>,
+}
+
+/// A synchronized registry after [`Callbacks::registry_entries`], ready to
+/// send.
+struct RegistryData {
+    id: &'static str,
+    entries: Vec<RegistryDataEntry>,
+}
+
+struct RegistryDataEntry {
+    id: String,
+    data: nbt::Tag,
+    /// Whether the entry is unchanged from the core pack, so clients that
+    /// have the pack don't need its data.
+    in_core_pack: bool,
 }
 
 struct Client {
     connection: Connection,
-    last_keepalive: Instant,
     server: Arc<Server>,
+    pending_login: Option<PendingLogin>,
+    profile: Option<GameProfile>,
+    player: Option<Player>,
+    view_distance: i32,
+}
+
+/// A login waiting on the client's `Key` packet.
+struct PendingLogin {
+    name: String,
+    verify_token: [u8; 4],
 }
 
+/// The dimension type players are sent, which sets the world's bounds and
+/// how it's lit.
+///
+/// https://minecraft.wiki/w/Dimension_type#JSON_format
+#[derive(Clone, Debug, Serialize)]
 pub struct DimensionData {
+    /// The lowest y blocks can be at. Must be a multiple of 16.
+    pub min_y: i32,
+    /// How many blocks tall the world is. Must be a multiple of 16.
     pub height: i32,
+    /// How high players can be teleported by portals and chorus fruit.
+    pub logical_height: i32,
+    /// How much light there is without any light sources, from 0 to 1.
+    pub ambient_light: f32,
+    pub has_skylight: bool,
+    pub has_ceiling: bool,
+    pub ultrawarm: bool,
+    pub natural: bool,
+    pub coordinate_scale: f64,
+    pub bed_works: bool,
+    pub respawn_anchor_works: bool,
+    pub piglin_safe: bool,
+    pub has_raids: bool,
+    /// The sky and fog, e.g. `minecraft:the_nether`.
+    pub effects: String,
+    /// The block tag that fire burns on forever.
+    pub infiniburn: String,
+    pub monster_spawn_light_level: UniformInt,
+    pub monster_spawn_block_light_limit: i32,
+    /// Freezes the time of day at this tick.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fixed_time: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename = "minecraft:uniform")]
+pub struct UniformInt {
+    pub min_inclusive: i32,
+    pub max_inclusive: i32,
 }
 
 #[derive(Debug, Snafu)]
@@ -63,29 +178,146 @@ pub enum Error {
     IOError { source: io::Error },
     #[snafu(transparent)]
     ConnectionError { source: connection::Error },
+    #[snafu(transparent)]
+    AuthError { source: auth::Error },
+    #[snafu(transparent)]
+    NbtError { source: nbt::ser::Error },
+    #[snafu(display("Unexpected encryption response"))]
+    UnexpectedKey,
 }
 
 // https://minecraft.wiki/w/Java_Edition_protocol?oldid=2874788
 const PROTOCOL_VERSION: i32 = 769;
 const GAME_VERSION: &str = "1.21.4";
+/// The vanilla data pack, whose registry entries clients already have.
+const CORE_PACK: KnownPack = KnownPack {
+    namespace: "minecraft",
+    id: "core",
+    version: GAME_VERSION,
+};
+/// Matches the vanilla `network-compression-threshold` default.
+const DEFAULT_COMPRESSION_THRESHOLD: usize = 256;
+const DEFAULT_TICKS_PER_SECOND: u32 = 20;
+/// How far the tick loop may fall behind before it skips ahead.
+const MAX_TICK_LAG: Duration = Duration::from_secs(2);
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(10);
+/// Matches the view distance sent in `LoginData`.
+const MAX_VIEW_DISTANCE: i32 = 8;
+/// Vanilla's lowest view distance.
+const MIN_VIEW_DISTANCE: i32 = 2;
+
+impl DimensionData {
+    /// An overworld-like dimension with the given bounds.
+    pub fn new(min_y: i32, height: i32) -> Self {
+        Self {
+            min_y,
+            height,
+            logical_height: height,
+            ambient_light: 0.0,
+            has_skylight: true,
+            has_ceiling: false,
+            ultrawarm: false,
+            natural: true,
+            coordinate_scale: 1.0,
+            bed_works: true,
+            respawn_anchor_works: false,
+            piglin_safe: false,
+            has_raids: true,
+            effects: "minecraft:overworld".to_string(),
+            infiniburn: "#minecraft:infiniburn_overworld".to_string(),
+            monster_spawn_light_level: UniformInt {
+                min_inclusive: 0,
+                max_inclusive: 7,
+            },
+            monster_spawn_block_light_limit: 0,
+            fixed_time: None,
+        }
+    }
+
+    /// Vanilla's overworld, from y -64 to 319.
+    pub fn overworld() -> Self {
+        Self::new(-64, 384)
+    }
+
+    /// How many chunk sections tall the world is.
+    pub fn subchunk_count(&self) -> u8 {
+        (self.height / 16) as u8
+    }
+}
 
 impl Server {
-    pub fn new(callbacks: impl Callbacks + 'static) -> Self {
+    /// Players join the world in `spawn_dimension`.
+    pub fn new(callbacks: impl Callbacks + 'static, spawn_dimension: Dimension) -> Self {
+        let registries = registries::synchronized_registries()
+            .iter()
+            .map(|registry| RegistryData::new(registry, &callbacks))
+            .collect::<Vec<_>>();
+        // Chunks must use the biome ids the client is sent
+        let biomes = registries
+            .iter()
+            .find(|r| r.id == "worldgen/biome")
+            .map(|biomes| Biomes::new(biomes.entries.iter().map(|e| e.id.clone()).collect()))
+            .unwrap_or_default();
         Self {
             callbacks: Box::new(callbacks),
+            compression_threshold: Some(DEFAULT_COMPRESSION_THRESHOLD),
+            authenticator: None,
+            world: World::new(spawn_dimension, biomes),
+            tick_duration: Duration::from_secs(1) / DEFAULT_TICKS_PER_SECOND,
+            registries,
         }
     }
 
+    /// Sets how many times per second [`Callbacks::on_world_tick`] runs.
+    pub fn with_tick_rate(mut self, ticks_per_second: NonZeroU32) -> Self {
+        self.tick_duration = Duration::from_secs(1) / ticks_per_second.get();
+        self
+    }
+
+    /// Enables online mode: logins are encrypted and players are verified
+    /// against the authenticator's session server.
+    pub fn with_authenticator(mut self, authenticator: Authenticator) -> Self {
+        self.authenticator = Some(authenticator);
+        self
+    }
+
+    /// Sets the packet size above which packets are compressed, or disables
+    /// compression with `None`.
+    pub fn with_compression_threshold(mut self, threshold: Option<usize>) -> Self {
+        self.compression_threshold = threshold;
+        self
+    }
+
+    /// Adds a dimension players can be moved to with
+    /// [`World::change_dimension`].
+    ///
+    /// # Panics
+    ///
+    /// If there's already a dimension with the same name.
+    pub fn with_dimension(mut self, dimension: Dimension) -> Self {
+        self.world.add_dimension(dimension);
+        self
+    }
+
+    /// The world, for setting it up before any players join.
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
     pub async fn listen(self, addr: &str) -> Result<(), Error> {
         let listener = TcpListener::bind(addr).await?;
         println!("Listening at {addr}");
         let server = Arc::new(self);
+        tokio::spawn(server.clone().tick_loop());
         loop {
             let (stream, _) = listener.accept().await?;
             let client = Client {
                 connection: Connection::new(stream),
-                last_keepalive: Instant::now(),
                 server: server.clone(),
+                pending_login: None,
+                profile: None,
+                player: None,
+                view_distance: MAX_VIEW_DISTANCE,
             };
             tokio::spawn(async move {
                 client.handle_connection().await;
@@ -94,8 +326,65 @@ impl Server {
     }
 }
 
+impl Server {
+    async fn tick_loop(self: Arc<Self>) {
+        let mut next_tick = Instant::now();
+        let mut last_keepalive = Instant::now();
+        let mut tick = 0;
+        loop {
+            tokio::time::sleep_until(next_tick.into()).await;
+
+            if let Err(err) = self.callbacks.on_world_tick(&self.world, tick).await {
+                eprintln!("Error ticking world: {err}");
+            }
+            for dimension in self.world.dimensions() {
+                dimension.flush_block_changes();
+                dimension.flush_light_updates();
+                for player in dimension.players() {
+                    match player.send_chunk_batch(dimension) {
+                        Ok(()) | Err(connection::Error::ConnectionClosed) => {}
+                        Err(err) => eprintln!("Error sending chunks: {err}"),
+                    }
+                }
+            }
+
+            if last_keepalive.elapsed() >= KEEPALIVE_INTERVAL {
+                let keep_alive_id = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as i64;
+                self.world
+                    .broadcast(play::clientbound::Packet::KeepAlive { keep_alive_id });
+                last_keepalive = Instant::now();
+            }
+
+            tick += 1;
+            next_tick += self.tick_duration;
+
+            let behind = Instant::now().saturating_duration_since(next_tick);
+            if behind > MAX_TICK_LAG {
+                let skipped_ticks = (behind.as_nanos() / self.tick_duration.as_nanos()) as u64;
+                self.callbacks.on_tick_overrun(behind, skipped_ticks);
+                next_tick = Instant::now();
+            }
+        }
+    }
+}
+
 impl Client {
     async fn handle_connection(mut self) {
+        self.handle_packets().await;
+
+        if let Some(player) = self.player.take() {
+            let world = &self.server.world;
+            world.remove(&player);
+            if let Err(err) = self.server.callbacks.on_leave(world, &player).await {
+                eprintln!("Error handling leave: {err}");
+            }
+        }
+    }
+
+    async fn handle_packets(&mut self) {
         loop {
             match self.handle_packet().await {
                 Ok(packet) => packet,
@@ -110,7 +399,7 @@ impl Client {
                     eprintln!("{err}");
                 }
                 Err(Error::ConnectionError {
-                    source: connection::Error::ClientTimedOut,
+                    source: connection::Error::ClientTimedOut | connection::Error::ConnectionClosed,
                 }) => return,
                 Err(err) => {
                     eprintln!("Error handling connection: {err}");
@@ -153,38 +442,78 @@ impl Client {
             },
             ServerboundPacket::Login(packet) => match packet {
                 login::serverbound::Packet::Hello { name, .. } => {
-                    let uuid = Uuid::new_v4();
-                    let username = &name;
+                    if let Some(authenticator) = &self.server.authenticator {
+                        let verify_token: [u8; 4] = rand::random();
+                        self.connection
+                            .send(login::clientbound::Packet::Hello {
+                                server_id: "",
+                                public_key: authenticator.public_key(),
+                                verify_token: &verify_token,
+                                should_authenticate: true,
+                            })
+                            .await?;
+                        self.pending_login = Some(PendingLogin { name, verify_token });
+                    } else {
+                        self.finish_login(auth::offline_profile(name)).await?;
+                    }
+                }
+                login::serverbound::Packet::Key {
+                    shared_secret,
+                    verify_token,
+                } => {
+                    let (Some(authenticator), Some(pending_login)) =
+                        (&self.server.authenticator, self.pending_login.take())
+                    else {
+                        return UnexpectedKeySnafu.fail();
+                    };
 
-                    self.connection
-                        .send(login::clientbound::Packet::LoginFinished {
-                            uuid,
-                            username,
-                            properties: &[],
-                        })
+                    authenticator.check_verify_token(&pending_login.verify_token, &verify_token)?;
+                    let shared_secret = authenticator.decrypt(&shared_secret)?;
+                    self.connection.enable_encryption(&shared_secret).await?;
+
+                    let profile = authenticator
+                        .has_joined(&pending_login.name, &shared_secret)
                         .await?;
+                    self.finish_login(profile).await?;
                 }
-                _ => {}
+                login::serverbound::Packet::LoginAcknowledged => {}
             },
             ServerboundPacket::Configuration(packet) => match packet {
-                configuration::serverbound::Packet::ClientInformation { .. } => {
+                configuration::serverbound::Packet::ClientInformation { view_distance, .. } => {
+                    self.view_distance =
+                        (view_distance as i32).clamp(MIN_VIEW_DISTANCE, MAX_VIEW_DISTANCE);
                     self.connection
                         .send(configuration::clientbound::Packet::SelectKnownPacks {
-                            known_packs: &[KnownPack {
-                                namespace: "minecraft",
-                                id: "core",
-                                version: GAME_VERSION,
-                            }],
+                            known_packs: &[CORE_PACK],
                         })
                         .await?;
                 }
                 configuration::serverbound::Packet::FinishConfiguration => {
-                    self.server.callbacks.on_login(&mut self.connection).await?;
+                    let world = &self.server.world;
+                    let profile = self.profile.take().expect("Profile is set during login");
+                    let player = Player::new(
+                        world.allocate_entity_id(),
+                        profile,
+                        self.connection.sender(),
+                        self.view_distance,
+                    );
+                    self.server.callbacks.on_login(world, &player).await?;
+
+                    world.add(player.clone());
+                    self.player = Some(player.clone());
+                    self.server.callbacks.on_join(world, &player).await?;
                 }
-                configuration::serverbound::Packet::SelectKnownPacks { .. } => {
+                configuration::serverbound::Packet::SelectKnownPacks { known_packs } => {
+                    let has_core_pack = known_packs.iter().any(|pack| {
+                        pack.namespace == CORE_PACK.namespace
+                            && pack.id == CORE_PACK.id
+                            && pack.version == CORE_PACK.version
+                    });
                     send_registry_data(
                         &mut self.connection,
-                        self.server.callbacks.dimension_data(),
+                        &self.server.registries,
+                        self.server.world.dimensions(),
+                        has_core_pack,
                     )
                     .await?;
 
@@ -194,209 +523,168 @@ impl Client {
                 }
                 _ => {}
             },
-            ServerboundPacket::Play(packet) => match packet {
-                play::serverbound::Packet::ClientTickEnd => {
-                    if Instant::now() - self.last_keepalive >= Duration::from_secs(10) {
-                        self.connection
-                            .send(play::clientbound::Packet::KeepAlive { keep_alive_id: 0 })
-                            .await?;
-                        self.last_keepalive = Instant::now();
+            ServerboundPacket::Play(packet) => {
+                let Some(player) = &self.player else {
+                    return Ok(());
+                };
+                match packet {
+                    play::serverbound::Packet::ClientTickEnd => {
+                        self.server.callbacks.on_tick(player).await?;
                     }
-
-                    self.server.callbacks.on_tick(&mut self.connection).await?;
+                    play::serverbound::Packet::ClientInformation { view_distance, .. } => {
+                        let view_distance =
+                            (view_distance as i32).clamp(MIN_VIEW_DISTANCE, MAX_VIEW_DISTANCE);
+                        player.set_view_distance(view_distance).await?;
+                    }
+                    play::serverbound::Packet::MovePlayerPos { x, z, .. }
+                    | play::serverbound::Packet::MovePlayerPosRot { x, z, .. } => {
+                        player.move_chunk_view(x, z).await?;
+                    }
+                    play::serverbound::Packet::ChunkBatchReceived { chunks_per_tick } => {
+                        player.chunk_batch_received(chunks_per_tick);
+                    }
+                    _ => {}
                 }
-                _ => {}
-            },
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Client {
+    async fn finish_login(&mut self, profile: GameProfile) -> Result<(), Error> {
+        let profile = self.server.callbacks.game_profile(profile).await?;
+
+        if let Some(threshold) = self.server.compression_threshold {
+            self.connection.set_compression(threshold).await?;
         }
 
+        self.connection
+            .send(login::clientbound::Packet::LoginFinished { profile: &profile })
+            .await?;
+        self.profile = Some(profile);
+
         Ok(())
     }
 }
 
+impl RegistryData {
+    fn new(registry: &SynchronizedRegistry, callbacks: &impl Callbacks) -> Self {
+        let mut entries = registry.entries.clone();
+        callbacks.registry_entries(registry.id, &mut entries);
+        Self {
+            id: registry.id,
+            entries: entries
+                .into_iter()
+                .map(|(id, data)| RegistryDataEntry {
+                    in_core_pack: registry
+                        .entries
+                        .iter()
+                        .any(|(core_id, core_data)| *core_id == id && *core_data == data),
+                    id,
+                    data,
+                })
+                .collect(),
+        }
+    }
+
+    /// The entries to send, leaving out the data of those from the core pack
+    /// if the client has it.
+    fn entries(&self, has_core_pack: bool) -> Vec<RegistryEntry<'_>> {
+        self.entries
+            .iter()
+            .map(|entry| RegistryEntry {
+                entry_id: &entry.id,
+                entry_data: (!(has_core_pack && entry.in_core_pack)).then(|| entry.data.clone()),
+            })
+            .collect()
+    }
+}
+
+/// Sends the registries, leaving out the data of entries from the core pack
+/// if the client has it.
 async fn send_registry_data(
     connection: &mut Connection,
-    dimension_data: DimensionData,
+    registries: &[RegistryData],
+    dimensions: &[Dimension],
+    has_core_pack: bool,
 ) -> Result<(), Error> {
-    let damage_types = [
-        "minecraft:arrow",
-        "minecraft:bad_respawn_point",
-        "minecraft:cactus",
-        "minecraft:campfire",
-        "minecraft:cramming",
-        "minecraft:dragon_breath",
-        "minecraft:drown",
-        "minecraft:dry_out",
-        "minecraft:ender_pearl",
-        "minecraft:explosion",
-        "minecraft:fall",
-        "minecraft:falling_anvil",
-        "minecraft:falling_block",
-        "minecraft:falling_stalactite",
-        "minecraft:fireball",
-        "minecraft:fireworks",
-        "minecraft:fly_into_wall",
-        "minecraft:freeze",
-        "minecraft:generic",
-        "minecraft:generic_kill",
-        "minecraft:hot_floor",
-        "minecraft:in_fire",
-        "minecraft:in_wall",
-        "minecraft:indirect_magic",
-        "minecraft:lava",
-        "minecraft:lightning_bolt",
-        "minecraft:magic",
-        "minecraft:mob_attack",
-        "minecraft:mob_attack_no_aggro",
-        "minecraft:mob_projectile",
-        "minecraft:on_fire",
-        "minecraft:out_of_world",
-        "minecraft:outside_border",
-        "minecraft:player_attack",
-        "minecraft:player_explosion",
-        "minecraft:sonic_boom",
-        "minecraft:spit",
-        "minecraft:stalagmite",
-        "minecraft:starve",
-        "minecraft:sting",
-        "minecraft:sweet_berry_bush",
-        "minecraft:thorns",
-        "minecraft:thrown",
-        "minecraft:trident",
-        "minecraft:unattributed_fireball",
-        "minecraft:wind_charge",
-        "minecraft:wither",
-        "minecraft:wither_skull",
-    ]
-    .into_iter()
-    .map(|entry_id| RegistryEntry {
-        entry_id,
-        entry_data: Some(nbt!(
-            {
-                exhaustion: 0.0,
-                message_id: "onFire",
-                scaling: "when_caused_by_living_non_player"
-            }
-        )),
-    })
-    .collect::<Vec<_>>();
-
-    let registries = [
-        configuration::clientbound::Packet::RegistryData {
-            registry_id: "damage_type",
-            entries: &damage_types,
-        },
-        configuration::clientbound::Packet::RegistryData {
-            registry_id: "dimension_type",
-            entries: &[RegistryEntry {
-                entry_id: "minecraft:overworld",
-                entry_data: Some(nbt!(
-                    {
-                        ambient_light: 0.0,
-                        bed_works: 1,
-                        coordinate_scale: 1.0,
-                        effects: "minecraft:overworld",
-                        has_ceiling: 0,
-                        has_raids: 1,
-                        has_skylight: 1,
-                        height: (dimension_data.height),
-                        infiniburn: "#minecraft:infiniburn_overworld",
-                        logical_height: (dimension_data.height),
-                        min_y: 0,
-                        monster_spawn_block_light_limit: 0,
-                        monster_spawn_light_level: {
-                            max_inclusive: 7,
-                            min_inclusive: 0,
-                            type: "minecraft:uniform"
-                        },
-                        natural: 1,
-                        piglin_safe: 0,
-                        respawn_anchor_works: 0,
-                        ultrawarm: 0,
-                    }
-                )),
-            }],
-        },
-        configuration::clientbound::Packet::RegistryData {
-            registry_id: "painting_variant",
-            entries: &[RegistryEntry {
-                entry_id: "placeholder",
-                entry_data: Some(nbt!(
-                    {
-                        asset_id: "minecraft:alban",
-                        width: 1,
-                        height: 1,
-                    }
-                )),
-            }],
-        },
-        configuration::clientbound::Packet::RegistryData {
-            registry_id: "wolf_variant",
-            entries: &[RegistryEntry {
-                entry_id: "placeholder",
-                entry_data: Some(nbt!(
-                    {
-                        angry_texture: "minecraft:entity/wolf/wolf_ashen_angry",
-                        biomes: "minecraft:snowy_taiga",
-                        tame_texture: "minecraft:entity/wolf/wolf_ashen_tame",
-                        wild_texture: "minecraft:entity/wolf/wolf_ashen",
-                    }
-                )),
-            }],
-        },
-        configuration::clientbound::Packet::RegistryData {
-            registry_id: "worldgen/biome",
-            entries: &[
-                RegistryEntry {
-                    entry_id: "minecraft:snowy_taiga",
-                    entry_data: Some(nbt!(
-                        {
-                            downfall: 0.4000000059604645,
-                            effects: {
-                                fog_color: 12638463,
-                                mood_sound: {
-                                    block_search_extent: 8,
-                                    offset: 2.0,
-                                    sound: "minecraft:ambient.cave",
-                                    tick_delay: 6000
-                                },
-                                sky_color: 8625919,
-                                water_color: 4020182,
-                                water_fog_color: 329011
-                            },
-                            has_precipitation: true,
-                            temperature: (-0.5),
-                        }
-                    )),
-                },
-                RegistryEntry {
-                    entry_id: "minecraft:plains",
-                    entry_data: Some(nbt!(
-                        {
-                            downfall: 0.4000000059604645,
-                            effects: {
-                                fog_color: 12638463,
-                                mood_sound: {
-                                    block_search_extent: 8,
-                                    offset: 2.0,
-                                    sound: "minecraft:ambient.cave",
-                                    tick_delay: 6000
-                                },
-                                sky_color: 8625919,
-                                water_color: 4020182,
-                                water_fog_color: 329011
-                            },
-                            has_precipitation: true,
-                            temperature: 0.8,
-                        }
-                    )),
-                },
-            ],
-        },
-    ];
+    // Each dimension has its own type, so a dimension's id is also its
+    // type's id
+    let dimension_types = dimensions
+        .iter()
+        .map(|dimension| {
+            Ok(RegistryEntry {
+                entry_id: dimension.name(),
+                entry_data: Some(nbt::to_tag(dimension.dimension_data())?),
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
 
+    connection
+        .send(configuration::clientbound::Packet::RegistryData {
+            registry_id: "dimension_type",
+            entries: &dimension_types,
+        })
+        .await?;
     for registry in registries {
-        connection.send(registry).await?;
+        connection
+            .send(configuration::clientbound::Packet::RegistryData {
+                registry_id: registry.id,
+                entries: &registry.entries(has_core_pack),
+            })
+            .await?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+
+    use crate::{
+        nbt::Tag,
+        registries::SynchronizedRegistry,
+        server::{Callbacks, Error, RegistryData},
+        world::{Player, World},
+    };
+
+    struct OverrideDesert;
+
+    #[async_trait]
+    impl Callbacks for OverrideDesert {
+        async fn on_login(&self, _world: &World, _player: &Player) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn registry_entries(&self, registry_id: &str, entries: &mut Vec<(String, Tag)>) {
+            assert_eq!(registry_id, "worldgen/biome");
+            entries[0].1 = Tag::Compound(vec![("downfall".to_owned(), Tag::Float(0.5))]);
+        }
+    }
+
+    #[test]
+    fn test_registry_data() {
+        let biome = |downfall| Tag::Compound(vec![("downfall".to_owned(), Tag::Float(downfall))]);
+        let registry = SynchronizedRegistry {
+            id: "worldgen/biome",
+            entries: vec![
+                ("minecraft:desert".to_owned(), biome(0.0)),
+                ("minecraft:plains".to_owned(), biome(0.4)),
+            ],
+        };
+        let registry = RegistryData::new(&registry, &OverrideDesert);
+
+        let entries = registry.entries(true);
+        assert_eq!(entries[0].entry_id, "minecraft:desert");
+        assert_eq!(entries[0].entry_data, Some(biome(0.5)));
+        assert_eq!(entries[1].entry_id, "minecraft:plains");
+        assert_eq!(entries[1].entry_data, None);
+
+        let entries = registry.entries(false);
+        assert_eq!(entries[0].entry_data, Some(biome(0.5)));
+        assert_eq!(entries[1].entry_data, Some(biome(0.4)));
+    }
+}
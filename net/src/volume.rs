@@ -0,0 +1,511 @@
+//! Scalar volumes, such as CT or MRI scans, turned into blocks by mapping
+//! value ranges to block states.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Read},
+};
+
+use flate2::read::GzDecoder;
+use snafu::prelude::*;
+
+use crate::{
+    chunk::{AIR, Chunk},
+    server::DimensionData,
+};
+
+/// The type of each sample in a volume file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+    F64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Endianness {
+    #[default]
+    Little,
+    Big,
+}
+
+/// The world axis a file axis runs along. The `Neg` variants run backwards,
+/// for scans stored top to bottom or mirrored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+/// How the samples of a raw volume are laid out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VolumeFormat {
+    /// The number of samples along each file axis, fastest varying first.
+    pub sizes: [usize; 3],
+    pub data_type: DataType,
+    pub endianness: Endianness,
+    /// The world axis of each file axis, in the same order as `sizes`.
+    pub axes: [Axis; 3],
+}
+
+/// Samples on a grid, indexed by world x, y and z.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Volume {
+    size_x: usize,
+    size_y: usize,
+    size_z: usize,
+    /// Indexed by `(y * size_z + z) * size_x + x`.
+    values: Vec<f32>,
+}
+
+/// Maps sample values to block states. Each band covers the values from its
+/// threshold up to the next band's, and values below every band are air.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BlockPalette {
+    /// Sorted by threshold.
+    bands: Vec<(f32, i32)>,
+}
+
+/// Chunks holding a volume, with the dimension they fit in.
+#[derive(Debug)]
+pub struct ChunkGrid {
+    pub chunks: BTreeMap<(i32, i32), Chunk>,
+    pub dimension_data: DimensionData,
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(transparent)]
+    IOError { source: io::Error },
+    #[snafu(display("Volume has {found} bytes, expected {expected}"))]
+    WrongLength { expected: usize, found: usize },
+    #[snafu(display("Axes must include each of x, y and z once"))]
+    InvalidAxes,
+    #[snafu(display("Not an NRRD file"))]
+    NotNrrd,
+    #[snafu(display("NRRD header is missing {field}"))]
+    MissingField { field: &'static str },
+    #[snafu(display("Invalid NRRD {field}: {value}"))]
+    InvalidField { field: &'static str, value: String },
+    #[snafu(display("Volume size {sizes:?} is empty or too large"))]
+    InvalidSize { sizes: [usize; 3] },
+    #[snafu(display("Volume is {height} blocks tall, more than a dimension can hold"))]
+    TooTall { height: usize },
+}
+
+/// The tallest dimension the client accepts.
+const MAX_HEIGHT: usize = 4064;
+
+/// The highest top of a dimension the client accepts.
+const MAX_TOP: usize = 2032;
+
+impl DataType {
+    pub fn size(self) -> usize {
+        match self {
+            DataType::U8 | DataType::I8 => 1,
+            DataType::U16 | DataType::I16 => 2,
+            DataType::U32 | DataType::I32 | DataType::F32 => 4,
+            DataType::F64 => 8,
+        }
+    }
+
+    fn read(self, bytes: &[u8], endianness: Endianness) -> f32 {
+        macro_rules! read {
+            ($ty:ty) => {{
+                let bytes = bytes.try_into().unwrap();
+                match endianness {
+                    Endianness::Little => <$ty>::from_le_bytes(bytes) as f32,
+                    Endianness::Big => <$ty>::from_be_bytes(bytes) as f32,
+                }
+            }};
+        }
+        match self {
+            DataType::U8 => bytes[0] as f32,
+            DataType::I8 => bytes[0] as i8 as f32,
+            DataType::U16 => read!(u16),
+            DataType::I16 => read!(i16),
+            DataType::U32 => read!(u32),
+            DataType::I32 => read!(i32),
+            DataType::F32 => read!(f32),
+            DataType::F64 => read!(f64),
+        }
+    }
+
+    /// Parses an NRRD type name.
+    fn from_nrrd(name: &str) -> Option<Self> {
+        Some(match name {
+            "uchar" | "unsigned char" | "uint8" | "uint8_t" => DataType::U8,
+            "signed char" | "int8" | "int8_t" => DataType::I8,
+            "ushort" | "unsigned short" | "unsigned short int" | "uint16" | "uint16_t" => {
+                DataType::U16
+            }
+            "short" | "short int" | "signed short" | "signed short int" | "int16" | "int16_t" => {
+                DataType::I16
+            }
+            "uint" | "unsigned int" | "uint32" | "uint32_t" => DataType::U32,
+            "int" | "signed int" | "int32" | "int32_t" => DataType::I32,
+            "float" => DataType::F32,
+            "double" => DataType::F64,
+            _ => return None,
+        })
+    }
+}
+
+impl Axis {
+    /// Which of x, y and z this is, as 0, 1 or 2.
+    fn index(self) -> usize {
+        match self {
+            Axis::PosX | Axis::NegX => 0,
+            Axis::PosY | Axis::NegY => 1,
+            Axis::PosZ | Axis::NegZ => 2,
+        }
+    }
+
+    fn is_negative(self) -> bool {
+        matches!(self, Axis::NegX | Axis::NegY | Axis::NegZ)
+    }
+}
+
+impl Volume {
+    /// Reads headerless samples.
+    pub fn from_raw(bytes: &[u8], format: &VolumeFormat) -> Result<Self, Error> {
+        let mut seen = [false; 3];
+        for axis in format.axes {
+            seen[axis.index()] = true;
+        }
+        ensure!(seen == [true; 3], InvalidAxesSnafu);
+
+        let sizes = format.sizes;
+        let sample_size = format.data_type.size();
+        let length = sizes
+            .iter()
+            .try_fold(sample_size, |length, &size| length.checked_mul(size))
+            .filter(|&length| length > 0)
+            .context(InvalidSizeSnafu { sizes })?;
+        ensure!(
+            bytes.len() == length,
+            WrongLengthSnafu {
+                expected: length,
+                found: bytes.len(),
+            }
+        );
+        let count = length / sample_size;
+
+        let mut world_size = [0; 3];
+        for (&size, axis) in format.sizes.iter().zip(format.axes) {
+            world_size[axis.index()] = size;
+        }
+        let [size_x, size_y, size_z] = world_size;
+        let mut values = vec![0.0; count];
+        for (i, sample) in bytes.chunks_exact(sample_size).enumerate() {
+            let mut position = [0; 3];
+            let mut rest = i;
+            for (&size, axis) in format.sizes.iter().zip(format.axes) {
+                let coordinate = rest % size;
+                rest /= size;
+                position[axis.index()] = if axis.is_negative() {
+                    size - 1 - coordinate
+                } else {
+                    coordinate
+                };
+            }
+            let [x, y, z] = position;
+            values[(y * size_z + z) * size_x + x] =
+                format.data_type.read(sample, format.endianness);
+        }
+
+        Ok(Self {
+            size_x,
+            size_y,
+            size_z,
+            values,
+        })
+    }
+
+    /// Reads an NRRD file with its data attached, mapping its axes to `axes`.
+    /// Raw and gzip encodings are supported.
+    ///
+    /// https://teem.sourceforge.net/nrrd/format.html
+    pub fn from_nrrd(bytes: &[u8], axes: [Axis; 3]) -> Result<Self, Error> {
+        ensure!(bytes.starts_with(b"NRRD"), NotNrrdSnafu);
+
+        let mut fields = BTreeMap::new();
+        let mut rest = bytes;
+        loop {
+            let end = rest
+                .iter()
+                .position(|&b| b == b'\n')
+                .context(MissingFieldSnafu { field: "data" })?;
+            let line = String::from_utf8_lossy(&rest[..end]);
+            let line = line.trim_end_matches('\r');
+            rest = &rest[end + 1..];
+            if line.is_empty() {
+                break;
+            }
+            if let Some((key, value)) = line.split_once(": ") {
+                fields.insert(key.trim().to_owned(), value.trim().to_owned());
+            }
+        }
+        let field = |field: &'static str| {
+            fields
+                .get(field)
+                .map(String::as_str)
+                .context(MissingFieldSnafu { field })
+        };
+        let invalid = |field: &'static str, value: &str| InvalidFieldSnafu {
+            field,
+            value: value.to_owned(),
+        };
+
+        let data_type = field("type")?;
+        let data_type = DataType::from_nrrd(data_type).context(invalid("type", data_type))?;
+        let dimension = field("dimension")?;
+        ensure!(dimension == "3", invalid("dimension", dimension));
+        let sizes_field = field("sizes")?;
+        let sizes = sizes_field
+            .split_whitespace()
+            .map(|size| size.parse::<usize>().ok())
+            .collect::<Option<Vec<_>>>()
+            .and_then(|sizes| <[usize; 3]>::try_from(sizes).ok())
+            .context(invalid("sizes", sizes_field))?;
+        let endianness = match fields.get("endian").map(String::as_str) {
+            None | Some("little") => Endianness::Little,
+            Some("big") => Endianness::Big,
+            Some(endian) => return invalid("endian", endian).fail(),
+        };
+
+        let mut decompressed = Vec::new();
+        let data = match field("encoding")? {
+            "raw" => rest,
+            "gzip" | "gz" => {
+                GzDecoder::new(rest).read_to_end(&mut decompressed)?;
+                &decompressed
+            }
+            encoding => return invalid("encoding", encoding).fail(),
+        };
+
+        Self::from_raw(
+            data,
+            &VolumeFormat {
+                sizes,
+                data_type,
+                endianness,
+                axes,
+            },
+        )
+    }
+
+    /// The size along world x, y and z.
+    pub fn size(&self) -> (usize, usize, usize) {
+        (self.size_x, self.size_y, self.size_z)
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> f32 {
+        self.values[(y * self.size_z + z) * self.size_x + x]
+    }
+
+    /// Builds the chunks covering the volume, with its minimum corner at the
+    /// world origin, and a dimension just tall enough to hold it. Volumes
+    /// taller than the client allows above y = 0 start lower, at the
+    /// dimension's `min_y`.
+    pub fn to_chunks(&self, palette: &BlockPalette) -> Result<ChunkGrid, Error> {
+        let height = self.size_y.next_multiple_of(16).max(16);
+        ensure!(height <= MAX_HEIGHT, TooTallSnafu { height });
+        let subchunk_count = (height / 16) as u8;
+        let min_y = (MAX_TOP as i32 - height as i32).min(0);
+
+        let mut chunks = BTreeMap::new();
+        for chunk_z in 0..self.size_z.div_ceil(16) {
+            for chunk_x in 0..self.size_x.div_ceil(16) {
+                let chunk = Chunk::from_fn(min_y, subchunk_count, |x, y, z| {
+                    let x = chunk_x * 16 + x as usize;
+                    let y = (y - min_y) as usize;
+                    let z = chunk_z * 16 + z as usize;
+                    if x < self.size_x && y < self.size_y && z < self.size_z {
+                        palette.block(self.get(x, y, z))
+                    } else {
+                        AIR
+                    }
+                });
+                chunks.insert((chunk_x as i32, chunk_z as i32), chunk);
+            }
+        }
+
+        Ok(ChunkGrid {
+            chunks,
+            dimension_data: DimensionData::new(min_y, height as i32),
+        })
+    }
+}
+
+impl BlockPalette {
+    /// A palette that turns everything into air.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maps values from `threshold` up to the next band to `state_id`.
+    pub fn with_band(mut self, threshold: f32, state_id: i32) -> Self {
+        let index = self.bands.partition_point(|&(t, _)| t <= threshold);
+        self.bands.insert(index, (threshold, state_id));
+        self
+    }
+
+    /// Splits `min..max` into equal bands, one per state, from low to high
+    /// values. Values above `max` use the last state.
+    pub fn gradient(min: f32, max: f32, state_ids: &[i32]) -> Self {
+        let step = (max - min) / state_ids.len() as f32;
+        state_ids
+            .iter()
+            .enumerate()
+            .fold(Self::new(), |palette, (i, &state_id)| {
+                palette.with_band(min + step * i as f32, state_id)
+            })
+    }
+
+    pub fn block(&self, value: f32) -> i32 {
+        let index = self
+            .bands
+            .partition_point(|&(threshold, _)| threshold <= value);
+        match index {
+            0 => AIR,
+            _ => self.bands[index - 1].1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chunk::AIR,
+        registries::blocks,
+        volume::{Axis, BlockPalette, DataType, Endianness, Error, Volume, VolumeFormat},
+    };
+
+    #[test]
+    fn test_axes() {
+        // Stored z fastest, then x, then y from the top down
+        let bytes = (0..2 * 3 * 4).map(|i| i as u8).collect::<Vec<_>>();
+        let volume = Volume::from_raw(
+            &bytes,
+            &VolumeFormat {
+                sizes: [2, 3, 4],
+                data_type: DataType::U8,
+                endianness: Endianness::Little,
+                axes: [Axis::PosZ, Axis::PosX, Axis::NegY],
+            },
+        )
+        .unwrap();
+        assert_eq!(volume.size(), (3, 4, 2));
+        assert_eq!(volume.get(0, 3, 0), 0.0);
+        assert_eq!(volume.get(0, 3, 1), 1.0);
+        assert_eq!(volume.get(1, 3, 0), 2.0);
+        assert_eq!(volume.get(0, 2, 0), 6.0);
+        assert_eq!(volume.get(2, 0, 1), 23.0);
+
+        let format = VolumeFormat {
+            sizes: [2, 3, 4],
+            data_type: DataType::U16,
+            endianness: Endianness::Big,
+            axes: [Axis::PosX, Axis::PosY, Axis::NegX],
+        };
+        assert!(Volume::from_raw(&[0; 48], &format).is_err());
+    }
+
+    #[test]
+    fn test_invalid_size() {
+        let format = |sizes| VolumeFormat {
+            sizes,
+            data_type: DataType::U8,
+            endianness: Endianness::Little,
+            axes: [Axis::PosX, Axis::PosY, Axis::PosZ],
+        };
+        assert!(matches!(
+            Volume::from_raw(&[], &format([1 << 32, 1 << 32, 1])),
+            Err(Error::InvalidSize { .. })
+        ));
+        assert!(matches!(
+            Volume::from_raw(&[], &format([0, 2, 2])),
+            Err(Error::InvalidSize { .. })
+        ));
+    }
+
+    #[test]
+    fn test_nrrd() {
+        let mut bytes = b"NRRD0004\n# A comment\ntype: short\ndimension: 3\nsizes: 2 1 1\nendian: big\nencoding: raw\n\n".to_vec();
+        bytes.extend([0xFF, 0xFE, 0x01, 0x00]);
+        let volume = Volume::from_nrrd(&bytes, [Axis::PosX, Axis::PosY, Axis::PosZ]).unwrap();
+        assert_eq!(volume.get(0, 0, 0), -2.0);
+        assert_eq!(volume.get(1, 0, 0), 256.0);
+
+        let bytes = b"NRRD0004\ntype: short\ndimension: 3\nsizes: 2 1\nencoding: raw\n\n";
+        assert!(Volume::from_nrrd(bytes, [Axis::PosX, Axis::PosY, Axis::PosZ]).is_err());
+    }
+
+    #[test]
+    fn test_to_chunks() {
+        let stone = blocks::Stone.state_id();
+        let glass = blocks::Glass.state_id();
+        let palette = BlockPalette::gradient(0.2, 0.6, &[glass, stone]);
+        assert_eq!(palette.block(0.1), AIR);
+        assert_eq!(palette.block(0.3), glass);
+        assert_eq!(palette.block(0.45), stone);
+        assert_eq!(palette.block(9.0), stone);
+
+        let bytes = [0.0f32, 0.3, 0.5]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<_>>();
+        let volume = Volume::from_raw(
+            &bytes,
+            &VolumeFormat {
+                sizes: [1, 1, 3],
+                data_type: DataType::F32,
+                endianness: Endianness::Little,
+                axes: [Axis::PosX, Axis::PosY, Axis::PosZ],
+            },
+        )
+        .unwrap();
+        let grid = volume.to_chunks(&palette).unwrap();
+        assert_eq!(grid.dimension_data.height, 16);
+        assert_eq!(grid.chunks.len(), 1);
+        let chunk = &grid.chunks[&(0, 0)];
+        assert_eq!(chunk.block(0, 0, 0), AIR);
+        assert_eq!(chunk.block(0, 0, 1), glass);
+        assert_eq!(chunk.block(0, 0, 2), stone);
+        assert_eq!(chunk.block(1, 0, 2), AIR);
+    }
+
+    #[test]
+    fn test_to_chunks_tall() {
+        let stone = blocks::Stone.state_id();
+        let bytes = (0..2100).map(|y| (y == 0) as u8).collect::<Vec<_>>();
+        let volume = Volume::from_raw(
+            &bytes,
+            &VolumeFormat {
+                sizes: [1, 2100, 1],
+                data_type: DataType::U8,
+                endianness: Endianness::Little,
+                axes: [Axis::PosX, Axis::PosY, Axis::PosZ],
+            },
+        )
+        .unwrap();
+        let grid = volume
+            .to_chunks(&BlockPalette::new().with_band(1.0, stone))
+            .unwrap();
+        assert_eq!(grid.dimension_data.min_y, -80);
+        assert_eq!(grid.dimension_data.height, 2112);
+        let chunk = &grid.chunks[&(0, 0)];
+        assert_eq!(chunk.block(0, -80, 0), stone);
+        assert_eq!(chunk.block(0, -79, 0), AIR);
+    }
+}
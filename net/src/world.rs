@@ -0,0 +1,254 @@
+mod dimension;
+mod view;
+
+pub use dimension::Dimension;
+use view::ChunkView;
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicI32, Ordering},
+    },
+};
+
+use crate::{
+    connection::{self, Sender},
+    packets::{
+        login::clientbound::GameProfile,
+        play::{self, clientbound::GameEvent},
+    },
+    registries::Biomes,
+};
+
+/// Players that have reached the play state and the dimensions they can be
+/// in, shared by every connection.
+pub struct World {
+    players: Mutex<BTreeMap<i32, Player>>,
+    next_entity_id: AtomicI32,
+    /// Indexed by dimension id. Players join the first.
+    dimensions: Vec<Dimension>,
+    biomes: Biomes,
+}
+
+/// A connected player. Cloning gives another handle to the same outbound
+/// queue.
+#[derive(Clone)]
+pub struct Player {
+    entity_id: i32,
+    profile: Arc<GameProfile>,
+    sender: Sender,
+    chunk_view: Arc<Mutex<ChunkView>>,
+}
+
+impl World {
+    pub(crate) fn new(mut spawn_dimension: Dimension, biomes: Biomes) -> Self {
+        spawn_dimension.biomes = biomes.clone();
+        Self {
+            players: Mutex::new(BTreeMap::new()),
+            next_entity_id: AtomicI32::new(1),
+            dimensions: vec![spawn_dimension],
+            biomes,
+        }
+    }
+
+    /// Adds a dimension, giving it the next dimension id.
+    ///
+    /// # Panics
+    ///
+    /// If there's already a dimension with the same name.
+    pub(crate) fn add_dimension(&mut self, mut dimension: Dimension) {
+        assert!(
+            self.dimension(dimension.name()).is_none(),
+            "Duplicate dimension {}",
+            dimension.name()
+        );
+        dimension.id = self.dimensions.len() as i32;
+        dimension.biomes = self.biomes.clone();
+        self.dimensions.push(dimension);
+    }
+
+    /// The biomes the server sends, whose ids chunks in this world use.
+    pub fn biomes(&self) -> &Biomes {
+        &self.biomes
+    }
+
+    /// Reserves an entity id that is not used by any player.
+    pub fn allocate_entity_id(&self) -> i32 {
+        self.next_entity_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub fn players(&self) -> Vec<Player> {
+        self.players.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn player_count(&self) -> usize {
+        self.players.lock().unwrap().len()
+    }
+
+    pub fn player(&self, entity_id: i32) -> Option<Player> {
+        self.players.lock().unwrap().get(&entity_id).cloned()
+    }
+
+    /// Every dimension, in the order they were added.
+    pub fn dimensions(&self) -> &[Dimension] {
+        &self.dimensions
+    }
+
+    /// The names of every dimension, as sent in `Login`.
+    pub fn dimension_names(&self) -> Vec<String> {
+        self.dimensions
+            .iter()
+            .map(|dimension| dimension.name().to_owned())
+            .collect()
+    }
+
+    pub fn dimension(&self, name: &str) -> Option<&Dimension> {
+        self.dimensions
+            .iter()
+            .find(|dimension| dimension.name() == name)
+    }
+
+    /// The dimension players are in when they join.
+    pub fn spawn_dimension(&self) -> &Dimension {
+        &self.dimensions[0]
+    }
+
+    /// The dimension the player is in, if they're still in the world.
+    pub fn dimension_of(&self, player: &Player) -> Option<&Dimension> {
+        self.dimensions
+            .iter()
+            .find(|dimension| dimension.contains(player))
+    }
+
+    /// Moves a player to another dimension with a `Respawn`, which makes the
+    /// client drop its chunks and entities. Chunks in the new dimension are
+    /// streamed once the client reports its position, so the caller should
+    /// follow this with a `PlayerPosition` and the dimension's entities.
+    /// Returns false if there's no dimension with that name, and
+    /// `ConnectionClosed` if the player left meanwhile.
+    pub async fn change_dimension(
+        &self,
+        player: &Player,
+        name: &str,
+        game_mode: u8,
+    ) -> Result<bool, connection::Error> {
+        let Some(dimension) = self.dimension(name) else {
+            return Ok(false);
+        };
+        for dimension in &self.dimensions {
+            dimension.remove(player);
+        }
+        player.reset_chunk_view(dimension);
+
+        player
+            .send(play::clientbound::Packet::Respawn {
+                spawn: dimension.spawn_info(game_mode),
+                data_kept: 0,
+            })
+            .await?;
+        player
+            .send(play::clientbound::Packet::GameEvent {
+                event: GameEvent::StartChunks,
+                value: 0.0,
+            })
+            .await?;
+
+        // The player may have left while the packets were queued
+        let players = self.players.lock().unwrap();
+        if !players.contains_key(&player.entity_id) {
+            return Err(connection::Error::ConnectionClosed);
+        }
+        dimension.add(player.clone());
+        Ok(true)
+    }
+
+    /// Queues a packet for every player, in every dimension.
+    pub fn broadcast(&self, packet: play::clientbound::Packet) {
+        broadcast_filtered(&self.players.lock().unwrap(), packet, |_| true);
+    }
+
+    /// Queues a packet for every player except `except`.
+    pub fn broadcast_except(&self, except: &Player, packet: play::clientbound::Packet) {
+        broadcast_filtered(&self.players.lock().unwrap(), packet, |player| {
+            player.entity_id != except.entity_id
+        });
+    }
+
+    /// Adds a player to the world, in the spawn dimension.
+    pub(crate) fn add(&self, player: Player) {
+        let mut players = self.players.lock().unwrap();
+        self.spawn_dimension().add(player.clone());
+        players.insert(player.entity_id, player);
+    }
+
+    /// Removes a player from the world and their dimension. Holds the
+    /// players lock throughout, so [`World::change_dimension`] can't put
+    /// them back.
+    pub(crate) fn remove(&self, player: &Player) {
+        let mut players = self.players.lock().unwrap();
+        players.remove(&player.entity_id);
+        for dimension in &self.dimensions {
+            dimension.remove(player);
+        }
+    }
+}
+
+/// Serializes the packet once and queues it without waiting. Players whose
+/// queue is full are disconnected instead of stalling the rest.
+fn broadcast_filtered(
+    players: &BTreeMap<i32, Player>,
+    packet: play::clientbound::Packet,
+    filter: impl FnMut(&Player) -> bool,
+) {
+    broadcast_raw_filtered(players, connection::serialize(packet), filter);
+}
+
+/// Like [`broadcast_filtered`], for an already serialized packet.
+fn broadcast_raw_filtered(
+    players: &BTreeMap<i32, Player>,
+    raw: Arc<[u8]>,
+    mut filter: impl FnMut(&Player) -> bool,
+) {
+    for player in players.values() {
+        if !filter(player) {
+            continue;
+        }
+        match player.sender.try_send_raw(raw.clone()) {
+            Ok(()) | Err(connection::Error::ConnectionClosed) => {}
+            Err(err) => eprintln!("Dropping {}: {err}", player.profile.username),
+        }
+    }
+}
+
+impl Player {
+    pub(crate) fn new(
+        entity_id: i32,
+        profile: GameProfile,
+        sender: Sender,
+        view_distance: i32,
+    ) -> Self {
+        Self {
+            entity_id,
+            profile: Arc::new(profile),
+            sender,
+            chunk_view: Arc::new(Mutex::new(ChunkView::new(view_distance))),
+        }
+    }
+
+    pub fn entity_id(&self) -> i32 {
+        self.entity_id
+    }
+
+    pub fn profile(&self) -> &GameProfile {
+        &self.profile
+    }
+
+    /// Queues a packet for this player, waiting if their queue is full.
+    pub async fn send(
+        &self,
+        packet: play::clientbound::Packet<'_>,
+    ) -> Result<(), connection::Error> {
+        self.sender.send(packet).await
+    }
+}
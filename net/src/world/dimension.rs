@@ -0,0 +1,592 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, btree_map},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::{
+    chunk::Chunk,
+    connection,
+    generator::ChunkGenerator,
+    light,
+    packets::{
+        Position,
+        play::{
+            self,
+            clientbound::{SectionPosition, SpawnInfo},
+        },
+    },
+    registries::Biomes,
+    server::DimensionData,
+    world::{Player, broadcast_filtered, broadcast_raw_filtered},
+};
+
+type Chunks = BTreeMap<(i32, i32), Chunk>;
+
+/// How many chunks each dimension generates and lights at once on the
+/// blocking pool.
+const MAX_CHUNKS_PREPARING: usize = 16;
+
+/// A world of its own, with its own chunks, dimension type and the players
+/// currently in it.
+pub struct Dimension {
+    name: String,
+    /// The dimension's index, which is also its dimension type's registry
+    /// id.
+    pub(crate) id: i32,
+    dimension_data: DimensionData,
+    /// The world's biomes, set when the dimension is added to it.
+    pub(crate) biomes: Biomes,
+    is_flat: bool,
+    players: Mutex<BTreeMap<i32, Player>>,
+    chunks: Arc<Mutex<Chunks>>,
+    /// Locked after `chunks` when both are needed.
+    chunk_packets: Arc<Mutex<ChunkPackets>>,
+    generator: Option<Arc<dyn ChunkGenerator>>,
+}
+
+/// What generating and lighting chunks on the blocking pool needs from a
+/// dimension.
+struct ChunkSource {
+    chunks: Arc<Mutex<Chunks>>,
+    chunk_packets: Arc<Mutex<ChunkPackets>>,
+    generator: Option<Arc<dyn ChunkGenerator>>,
+    min_y: i32,
+    subchunk_count: u8,
+    has_skylight: bool,
+    biomes: Biomes,
+}
+
+/// Serialized `LevelChunkWithLight` packets, shared by every player until
+/// the chunk or one of its neighbours changes. They're generated and lit on
+/// the blocking pool, so a chunk is only sent once its packet is ready.
+#[derive(Default)]
+struct ChunkPackets {
+    packets: BTreeMap<(i32, i32), ChunkPacket>,
+    /// Chunks whose packet was dropped when a neighbour was added, so players
+    /// who have them are sent their new light.
+    stale: BTreeSet<(i32, i32)>,
+    /// `LightUpdate` packets for stale chunks that have been lit again.
+    light_updates: BTreeMap<(i32, i32), Arc<[u8]>>,
+    next_token: u64,
+    preparing: usize,
+}
+
+/// A chunk's packets from [`ChunkSource::light`].
+struct LitChunk {
+    packet: Arc<[u8]>,
+    light_update: Option<Arc<[u8]>>,
+}
+
+enum ChunkPacket {
+    /// Being lit by a task, whose result is kept if the token still matches.
+    Preparing(u64),
+    Ready(Arc<[u8]>),
+}
+
+impl Dimension {
+    /// An empty dimension, named like `minecraft:overworld`.
+    pub fn new(name: &str, dimension_data: DimensionData) -> Self {
+        Self {
+            name: name.to_owned(),
+            id: 0,
+            dimension_data,
+            biomes: Biomes::default(),
+            is_flat: false,
+            players: Mutex::new(BTreeMap::new()),
+            chunks: Arc::new(Mutex::new(BTreeMap::new())),
+            chunk_packets: Arc::new(Mutex::new(ChunkPackets::default())),
+            generator: None,
+        }
+    }
+
+    /// Generates chunks that haven't been inserted into the dimension when
+    /// they are first needed.
+    pub fn with_generator(mut self, generator: impl ChunkGenerator + 'static) -> Self {
+        self.generator = Some(Arc::new(generator));
+        self
+    }
+
+    /// Marks the dimension as superflat, which moves the client's horizon
+    /// down to the bottom of the world.
+    pub fn with_flat(mut self, is_flat: bool) -> Self {
+        self.is_flat = is_flat;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn dimension_data(&self) -> &DimensionData {
+        &self.dimension_data
+    }
+
+    /// The dimension as sent in `Login` and `Respawn`.
+    pub fn spawn_info(&self, game_mode: u8) -> SpawnInfo {
+        SpawnInfo {
+            dimension_type: self.id,
+            dimension_name: self.name.clone(),
+            game_mode,
+            is_flat: self.is_flat,
+        }
+    }
+
+    /// The players in this dimension.
+    pub fn players(&self) -> Vec<Player> {
+        self.players.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn contains(&self, player: &Player) -> bool {
+        self.players.lock().unwrap().contains_key(&player.entity_id)
+    }
+
+    /// Queues a packet for every player in this dimension.
+    pub fn broadcast(&self, packet: play::clientbound::Packet) {
+        broadcast_filtered(&self.players.lock().unwrap(), packet, |_| true);
+    }
+
+    /// Queues a packet for every player in this dimension except `except`.
+    pub fn broadcast_except(&self, except: &Player, packet: play::clientbound::Packet) {
+        broadcast_filtered(&self.players.lock().unwrap(), packet, |player| {
+            player.entity_id != except.entity_id
+        });
+    }
+
+    /// Adds or replaces a chunk. Players who already have the chunk are not
+    /// sent the new one, but those who have its neighbours are sent their
+    /// new light.
+    pub fn insert_chunk(&self, chunk_x: i32, chunk_z: i32, mut chunk: Chunk) -> Option<Chunk> {
+        chunk.take_changes();
+        let mut chunks = self.chunks.lock().unwrap();
+        self.chunk_packets
+            .lock()
+            .unwrap()
+            .forget(chunk_x, chunk_z, true);
+        chunks.insert((chunk_x, chunk_z), chunk)
+    }
+
+    /// Locks the loaded chunks, keyed by chunk x and z. Blocks set through
+    /// the guard are sent like those set with [`Dimension::set_block`], but
+    /// players aren't sent chunks added through it or the light around them.
+    pub fn chunks(&self) -> MutexGuard<'_, Chunks> {
+        let chunks = self.chunks.lock().unwrap();
+        // The guard can change anything
+        self.chunk_packets.lock().unwrap().packets.clear();
+        chunks
+    }
+
+    /// Makes sure a chunk is loaded, generating it if it isn't. Returns false
+    /// if the chunk is missing and there's no generator.
+    pub fn load_chunk(&self, chunk_x: i32, chunk_z: i32) -> bool {
+        self.chunk_source().load((chunk_x, chunk_z))
+    }
+
+    /// Sends a chunk with its light, generating it and its neighbours on the
+    /// blocking pool first if needed. Returns false if the chunk couldn't be
+    /// loaded.
+    pub async fn send_chunk(
+        &self,
+        player: &Player,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<bool, connection::Error> {
+        let ready = match self
+            .chunk_packets
+            .lock()
+            .unwrap()
+            .packets
+            .get(&(chunk_x, chunk_z))
+        {
+            Some(ChunkPacket::Ready(packet)) => Some(packet.clone()),
+            _ => None,
+        };
+        let packet = match ready {
+            Some(packet) => packet,
+            None => {
+                let source = self.chunk_source();
+                let lit = tokio::task::spawn_blocking(move || {
+                    source.generate_around((chunk_x, chunk_z));
+                    source.light((chunk_x, chunk_z), false)
+                })
+                .await
+                .unwrap();
+                let Some(lit) = lit else {
+                    return Ok(false);
+                };
+                lit.packet
+            }
+        };
+        player.sender.send_raw(packet).await?;
+        Ok(true)
+    }
+
+    /// The serialized `LevelChunkWithLight` for a chunk, if it's been lit
+    /// since it last changed. Otherwise starts generating and lighting it on
+    /// the blocking pool, unless enough chunks already are, and returns
+    /// `None` so it's tried again later.
+    pub(crate) fn chunk_packet(&self, chunk_x: i32, chunk_z: i32) -> Option<Arc<[u8]>> {
+        match self
+            .chunk_packets
+            .lock()
+            .unwrap()
+            .packets
+            .get(&(chunk_x, chunk_z))
+        {
+            Some(ChunkPacket::Ready(packet)) => return Some(packet.clone()),
+            Some(ChunkPacket::Preparing(_)) => return None,
+            None => {}
+        }
+        if self.generator.is_some()
+            || self
+                .chunks
+                .lock()
+                .unwrap()
+                .contains_key(&(chunk_x, chunk_z))
+        {
+            self.prepare_chunk_packet(&mut self.chunk_packets.lock().unwrap(), chunk_x, chunk_z);
+        }
+        None
+    }
+
+    /// Generates and lights a chunk on the blocking pool, unless it's already
+    /// being lit or too many chunks are. Stale chunks also get a `LightUpdate`
+    /// for the players who have them.
+    fn prepare_chunk_packet(&self, packets: &mut ChunkPackets, chunk_x: i32, chunk_z: i32) {
+        let pos = (chunk_x, chunk_z);
+        if packets.preparing >= MAX_CHUNKS_PREPARING || packets.packets.contains_key(&pos) {
+            return;
+        }
+        let token = packets.next_token;
+        packets.next_token += 1;
+        packets.preparing += 1;
+        packets.packets.insert(pos, ChunkPacket::Preparing(token));
+
+        let source = self.chunk_source();
+        tokio::task::spawn_blocking(move || source.prepare(pos, token));
+    }
+
+    /// Lights stale chunks again and sends their new light to the players
+    /// who have them.
+    pub(crate) fn flush_light_updates(&self) {
+        let light_updates = {
+            let mut packets = self.chunk_packets.lock().unwrap();
+            for (chunk_x, chunk_z) in packets.stale.clone() {
+                self.prepare_chunk_packet(&mut packets, chunk_x, chunk_z);
+            }
+            std::mem::take(&mut packets.light_updates)
+        };
+        if light_updates.is_empty() {
+            return;
+        }
+
+        let players = self.players.lock().unwrap();
+        for ((chunk_x, chunk_z), packet) in light_updates {
+            broadcast_raw_filtered(&players, packet, |player| {
+                player.has_chunk(chunk_x, chunk_z)
+            });
+        }
+    }
+
+    fn chunk_source(&self) -> ChunkSource {
+        ChunkSource {
+            chunks: self.chunks.clone(),
+            chunk_packets: self.chunk_packets.clone(),
+            generator: self.generator.clone(),
+            min_y: self.dimension_data.min_y,
+            subchunk_count: self.dimension_data.subchunk_count(),
+            has_skylight: self.dimension_data.has_skylight,
+            biomes: self.biomes.clone(),
+        }
+    }
+
+    /// Sets a block, to be sent to every player in the dimension at the end
+    /// of the tick. Returns false if the block isn't in a loaded chunk.
+    pub fn set_block(&self, x: i32, y: i32, z: i32, state_id: i32) -> bool {
+        let mut chunks = self.chunks.lock().unwrap();
+        let Some(chunk) = chunks.get_mut(&(x.div_euclid(16), z.div_euclid(16))) else {
+            return false;
+        };
+        if !chunk.y_range().contains(&y) {
+            return false;
+        }
+        chunk.set_block_id(x.rem_euclid(16) as u8, y, z.rem_euclid(16) as u8, state_id);
+        true
+    }
+
+    /// The block state id at the given position, if its chunk is loaded.
+    pub fn block(&self, x: i32, y: i32, z: i32) -> Option<i32> {
+        let chunks = self.chunks.lock().unwrap();
+        let chunk = chunks.get(&(x.div_euclid(16), z.div_euclid(16)))?;
+        chunk
+            .y_range()
+            .contains(&y)
+            .then(|| chunk.block(x.rem_euclid(16) as u8, y, z.rem_euclid(16) as u8))
+    }
+
+    /// Sends the blocks changed since the last flush to the players who have
+    /// their chunk, using a `BlockUpdate` for lone blocks and a
+    /// `SectionBlocksUpdate` for the rest.
+    pub(crate) fn flush_block_changes(&self) {
+        // Collected first so the chunks aren't locked while the players'
+        // views are
+        let mut changes = Vec::new();
+        {
+            let mut chunks = self.chunks.lock().unwrap();
+            for (&(chunk_x, chunk_z), chunk) in chunks.iter_mut() {
+                let chunk_changes = chunk.take_changes();
+                if !chunk_changes.is_empty() {
+                    self.chunk_packets
+                        .lock()
+                        .unwrap()
+                        .forget(chunk_x, chunk_z, false);
+                    changes.push(((chunk_x, chunk_z), chunk_changes));
+                }
+            }
+        }
+
+        let players = self.players.lock().unwrap();
+        for ((chunk_x, chunk_z), chunk_changes) in changes {
+            for (section_y, blocks) in chunk_changes {
+                let packet = match blocks.as_slice() {
+                    [block] => play::clientbound::Packet::BlockUpdate {
+                        location: Position::new(
+                            chunk_x * 16 + block.x as i32,
+                            section_y * 16 + block.y as i32,
+                            chunk_z * 16 + block.z as i32,
+                        ),
+                        block_id: block.state_id,
+                    },
+                    blocks => play::clientbound::Packet::SectionBlocksUpdate {
+                        section: SectionPosition {
+                            x: chunk_x,
+                            y: section_y,
+                            z: chunk_z,
+                        },
+                        blocks,
+                    },
+                };
+                broadcast_filtered(&players, packet, |player| {
+                    player.has_chunk(chunk_x, chunk_z)
+                });
+            }
+        }
+    }
+
+    pub(crate) fn add(&self, player: Player) {
+        self.players
+            .lock()
+            .unwrap()
+            .insert(player.entity_id, player);
+    }
+
+    /// Returns whether the player was in this dimension.
+    pub(crate) fn remove(&self, player: &Player) -> bool {
+        self.players
+            .lock()
+            .unwrap()
+            .remove(&player.entity_id)
+            .is_some()
+    }
+}
+
+impl ChunkPackets {
+    /// Drops the cached packets of a chunk and its neighbours, whose light
+    /// may have changed with it. If the chunk was added, neighbours that may
+    /// have been sent are marked stale. Called while holding `chunks`.
+    fn forget(&mut self, chunk_x: i32, chunk_z: i32, added: bool) {
+        for dz in -1..=1 {
+            for dx in -1..=1 {
+                let pos = (chunk_x + dx, chunk_z + dz);
+                let removed = self.packets.remove(&pos);
+                if added && pos != (chunk_x, chunk_z) && removed.is_some() {
+                    self.stale.insert(pos);
+                }
+            }
+        }
+    }
+}
+
+impl ChunkSource {
+    /// Makes sure a chunk is loaded, generating it without holding `chunks`
+    /// and keeping the first chunk if another task generated it meanwhile.
+    fn load(&self, pos: (i32, i32)) -> bool {
+        if self.chunks.lock().unwrap().contains_key(&pos) {
+            return true;
+        }
+        let Some(generator) = &self.generator else {
+            return false;
+        };
+
+        let chunk = generator.generate(pos.0, pos.1, self.min_y, self.subchunk_count, &self.biomes);
+        let mut chunks = self.chunks.lock().unwrap();
+        if let btree_map::Entry::Vacant(entry) = chunks.entry(pos) {
+            entry.insert(chunk);
+            self.chunk_packets
+                .lock()
+                .unwrap()
+                .forget(pos.0, pos.1, true);
+        }
+        true
+    }
+
+    /// Generates a chunk and its neighbours, so its light won't change as
+    /// more chunks are generated around it.
+    fn generate_around(&self, (chunk_x, chunk_z): (i32, i32)) {
+        if self.generator.is_none() {
+            return;
+        }
+        for dz in -1..=1 {
+            for dx in -1..=1 {
+                self.load((chunk_x + dx, chunk_z + dz));
+            }
+        }
+    }
+
+    /// Generates and lights a chunk for [`Dimension::prepare_chunk_packet`],
+    /// keeping the result if the chunk hasn't changed meanwhile.
+    fn prepare(&self, pos: (i32, i32), token: u64) {
+        self.generate_around(pos);
+        // Generating the neighbours drops this chunk's entry, which is taken
+        // back since the neighbourhood is only copied after
+        let stale = {
+            let mut packets = self.chunk_packets.lock().unwrap();
+            match packets.packets.get(&pos) {
+                None => {
+                    packets.packets.insert(pos, ChunkPacket::Preparing(token));
+                }
+                Some(ChunkPacket::Preparing(t)) if *t == token => {}
+                Some(_) => {
+                    packets.preparing -= 1;
+                    return;
+                }
+            }
+            packets.stale.contains(&pos)
+        };
+        let lit = self.light(pos, stale);
+
+        let mut packets = self.chunk_packets.lock().unwrap();
+        packets.preparing -= 1;
+        // Dropped if the chunk changed meanwhile, to be lit again
+        if !matches!(packets.packets.get(&pos), Some(ChunkPacket::Preparing(t)) if *t == token) {
+            return;
+        }
+        packets.stale.remove(&pos);
+        match lit {
+            Some(LitChunk {
+                packet,
+                light_update,
+            }) => {
+                packets.packets.insert(pos, ChunkPacket::Ready(packet));
+                if let Some(light_update) = light_update {
+                    packets.light_updates.insert(pos, light_update);
+                }
+            }
+            None => {
+                packets.packets.remove(&pos);
+            }
+        }
+    }
+
+    /// Lights a chunk with its loaded neighbours and serializes it, and its
+    /// light in a `LightUpdate` too if `light_update` is set. The
+    /// neighbourhood is copied so `chunks` isn't held while it's lit. `None`
+    /// if the chunk isn't loaded.
+    fn light(&self, (chunk_x, chunk_z): (i32, i32), light_update: bool) -> Option<LitChunk> {
+        let neighbours = {
+            let chunks = self.chunks.lock().unwrap();
+            if !chunks.contains_key(&(chunk_x, chunk_z)) {
+                return None;
+            }
+            chunks
+                .range((chunk_x - 1, chunk_z - 1)..=(chunk_x + 1, chunk_z + 1))
+                .filter(|&(&(_, z), _)| (chunk_z - 1..=chunk_z + 1).contains(&z))
+                .map(|(&pos, chunk)| (pos, chunk.clone()))
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        let mut light = light::compute(
+            neighbours.iter().map(|(&pos, chunk)| (pos, chunk)),
+            self.has_skylight,
+        );
+        let light = light.remove(&(chunk_x, chunk_z)).unwrap();
+        let light_update = light_update.then(|| {
+            connection::serialize(play::clientbound::Packet::LightUpdate {
+                chunk_x,
+                chunk_z,
+                light: light.clone(),
+            })
+        });
+        let packet = connection::serialize(play::clientbound::Packet::LevelChunkWithLight {
+            chunk_x,
+            chunk_z,
+            data: neighbours[&(chunk_x, chunk_z)].to_chunk_data(&self.biomes),
+            light,
+        });
+        Some(LitChunk {
+            packet,
+            light_update,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use crate::{
+        chunk::Chunk,
+        generator::{PRESETS, Superflat},
+        server::DimensionData,
+        world::Dimension,
+    };
+
+    /// Waits for a chunk's packet to be lit on the blocking pool.
+    async fn ready_packet(dimension: &Dimension, chunk_x: i32, chunk_z: i32) -> Arc<[u8]> {
+        loop {
+            if let Some(packet) = dimension.chunk_packet(chunk_x, chunk_z) {
+                return packet;
+            }
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chunk_packets() {
+        let dimension = Dimension::new("minecraft:overworld", DimensionData::new(0, 16));
+        dimension.insert_chunk(0, 0, Chunk::empty(0, 1));
+        assert!(dimension.chunk_packet(0, 0).is_none());
+        let packet = ready_packet(&dimension, 0, 0).await;
+        assert!(Arc::ptr_eq(&packet, &dimension.chunk_packet(0, 0).unwrap()));
+        // Without a generator, missing chunks are never ready
+        assert!(dimension.chunk_packet(5, 5).is_none());
+
+        // A new neighbour makes the chunk stale, and it's lit again
+        dimension.insert_chunk(1, 0, Chunk::empty(0, 1));
+        let stale = |dimension: &Dimension| {
+            let packets = dimension.chunk_packets.lock().unwrap();
+            (
+                packets.stale.contains(&(0, 0)),
+                packets.light_updates.contains_key(&(0, 0)),
+            )
+        };
+        assert_eq!(stale(&dimension), (true, false));
+        dimension.flush_light_updates();
+        let relit = ready_packet(&dimension, 0, 0).await;
+        assert!(!Arc::ptr_eq(&packet, &relit));
+        assert_eq!(stale(&dimension), (false, true));
+    }
+
+    #[tokio::test]
+    async fn test_generated_chunk_packets() {
+        let superflat = Superflat::from_preset(PRESETS[0].1).unwrap();
+        let dimension = Dimension::new("minecraft:overworld", DimensionData::new(0, 16))
+            .with_generator(superflat);
+        assert!(dimension.chunk_packet(3, 3).is_none());
+        ready_packet(&dimension, 3, 3).await;
+
+        // Its neighbours are generated before it's lit
+        let chunks = dimension.chunks.lock().unwrap();
+        assert_eq!(chunks.len(), 9);
+        assert!(chunks.contains_key(&(2, 4)));
+    }
+}
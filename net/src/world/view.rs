@@ -0,0 +1,319 @@
+//! Sends players the chunks around them as they move, in batches paced by
+//! the client.
+//!
+//! https://minecraft.wiki/w/Java_Edition_protocol/Chunk_batching
+
+use std::collections::BTreeSet;
+
+use crate::{
+    connection,
+    packets::play,
+    world::{Dimension, Player},
+};
+
+/// The chunks a player has been sent, which follow them as they move.
+pub(crate) struct ChunkView {
+    /// The chunk the player is in, once they've reported a position.
+    center: Option<(i32, i32)>,
+    /// The id of the dimension the chunks are from. Players join in the
+    /// spawn dimension, which is always the first.
+    dimension: i32,
+    view_distance: i32,
+    sent: BTreeSet<(i32, i32)>,
+    /// How many chunks may be sent in the next batch. Grows each tick up to
+    /// a tick's worth.
+    batch_quota: f32,
+    /// How many chunks the client last said it can handle per tick.
+    desired_chunks_per_tick: f32,
+    unacknowledged_batches: u32,
+    /// Only one batch at a time until the client first reports its rate.
+    max_unacknowledged_batches: u32,
+}
+
+// Vanilla's limits
+const START_CHUNKS_PER_TICK: f32 = 9.0;
+const MIN_CHUNKS_PER_TICK: f32 = 0.01;
+const MAX_CHUNKS_PER_TICK: f32 = 64.0;
+const MAX_UNACKNOWLEDGED_BATCHES: u32 = 10;
+
+impl ChunkView {
+    pub(crate) fn new(view_distance: i32) -> Self {
+        Self {
+            center: None,
+            dimension: 0,
+            view_distance,
+            sent: BTreeSet::new(),
+            batch_quota: 0.0,
+            desired_chunks_per_tick: START_CHUNKS_PER_TICK,
+            unacknowledged_batches: 0,
+            max_unacknowledged_batches: 1,
+        }
+    }
+
+    fn in_range(&self, (chunk_x, chunk_z): (i32, i32)) -> bool {
+        self.center.is_some_and(|(center_x, center_z)| {
+            (chunk_x - center_x).abs() <= self.view_distance
+                && (chunk_z - center_z).abs() <= self.view_distance
+        })
+    }
+
+    /// Drops the chunks that are out of range, returning them.
+    fn forget_out_of_range(&mut self) -> Vec<(i32, i32)> {
+        let forgotten = self
+            .sent
+            .iter()
+            .copied()
+            .filter(|&pos| !self.in_range(pos))
+            .collect::<Vec<_>>();
+        for pos in &forgotten {
+            self.sent.remove(pos);
+        }
+        forgotten
+    }
+
+    /// Forgets every chunk and waits for a new position in another
+    /// dimension, for when the client has dropped its chunks. The client's
+    /// rate is kept.
+    fn reset(&mut self, dimension: i32) {
+        self.center = None;
+        self.dimension = dimension;
+        self.sent.clear();
+        self.batch_quota = 0.0;
+        self.unacknowledged_batches = 0;
+    }
+
+    /// Moves the center to a chunk, returning the chunks that are now out of
+    /// range, or `None` if the center didn't change.
+    fn move_to(&mut self, center: (i32, i32)) -> Option<Vec<(i32, i32)>> {
+        if self.center == Some(center) {
+            return None;
+        }
+        self.center = Some(center);
+        Some(self.forget_out_of_range())
+    }
+
+    /// Returns the chunks that are now out of range.
+    fn set_view_distance(&mut self, view_distance: i32) -> Vec<(i32, i32)> {
+        self.view_distance = view_distance;
+        self.forget_out_of_range()
+    }
+
+    fn batch_received(&mut self, chunks_per_tick: f32) {
+        self.unacknowledged_batches = self.unacknowledged_batches.saturating_sub(1);
+        self.desired_chunks_per_tick = if chunks_per_tick.is_nan() {
+            MIN_CHUNKS_PER_TICK
+        } else {
+            chunks_per_tick.clamp(MIN_CHUNKS_PER_TICK, MAX_CHUNKS_PER_TICK)
+        };
+        if self.unacknowledged_batches == 0 {
+            self.batch_quota = 1.0;
+        }
+        self.max_unacknowledged_batches = MAX_UNACKNOWLEDGED_BATCHES;
+    }
+
+    /// Takes the nearest chunks that haven't been sent and `packet` has a
+    /// packet for, as many as the quota allows, and counts them as sent. The
+    /// rest are tried again in later batches.
+    fn next_batch<T>(&mut self, packet: impl FnMut((i32, i32)) -> Option<T>) -> Vec<T> {
+        let Some((center_x, center_z)) = self.center else {
+            return Vec::new();
+        };
+        if self.unacknowledged_batches >= self.max_unacknowledged_batches {
+            return Vec::new();
+        }
+        self.batch_quota = (self.batch_quota + self.desired_chunks_per_tick)
+            .min(self.desired_chunks_per_tick.max(1.0));
+        if self.batch_quota < 1.0 {
+            return Vec::new();
+        }
+
+        let radius = self.view_distance;
+        let mut missing = (-radius..=radius)
+            .flat_map(|dz| (-radius..=radius).map(move |dx| (center_x + dx, center_z + dz)))
+            .filter(|pos| !self.sent.contains(pos))
+            .collect::<Vec<_>>();
+        missing.sort_by_key(|&(chunk_x, chunk_z)| {
+            (chunk_x - center_x).pow(2) + (chunk_z - center_z).pow(2)
+        });
+        let mut packet = packet;
+        let (sent, batch): (Vec<_>, Vec<_>) = missing
+            .into_iter()
+            .filter_map(|pos| Some((pos, packet(pos)?)))
+            .take(self.batch_quota as usize)
+            .unzip();
+        if batch.is_empty() {
+            return batch;
+        }
+
+        self.sent.extend(sent);
+        self.batch_quota -= batch.len() as f32;
+        self.unacknowledged_batches += 1;
+        batch
+    }
+}
+
+impl Player {
+    /// Moves the player's view to the chunk containing the given block
+    /// position, forgetting the chunks that fell out of range.
+    pub(crate) async fn move_chunk_view(&self, x: f64, z: f64) -> Result<(), connection::Error> {
+        let center = ((x / 16.0).floor() as i32, (z / 16.0).floor() as i32);
+        let Some(forgotten) = self.chunk_view.lock().unwrap().move_to(center) else {
+            return Ok(());
+        };
+        self.send(play::clientbound::Packet::SetChunkCacheCenter {
+            chunk_x: center.0,
+            chunk_z: center.1,
+        })
+        .await?;
+        self.forget_chunks(forgotten).await
+    }
+
+    pub(crate) async fn set_view_distance(
+        &self,
+        view_distance: i32,
+    ) -> Result<(), connection::Error> {
+        let forgotten = self
+            .chunk_view
+            .lock()
+            .unwrap()
+            .set_view_distance(view_distance);
+        self.forget_chunks(forgotten).await
+    }
+
+    /// Starts over with no chunks sent from `dimension`, for when the client
+    /// has dropped them all. Batches already taken are not sent.
+    pub(crate) fn reset_chunk_view(&self, dimension: &Dimension) {
+        self.chunk_view.lock().unwrap().reset(dimension.id);
+    }
+
+    /// Whether the player has been sent a chunk.
+    pub(crate) fn has_chunk(&self, chunk_x: i32, chunk_z: i32) -> bool {
+        self.chunk_view
+            .lock()
+            .unwrap()
+            .sent
+            .contains(&(chunk_x, chunk_z))
+    }
+
+    pub(crate) fn chunk_batch_received(&self, chunks_per_tick: f32) {
+        self.chunk_view
+            .lock()
+            .unwrap()
+            .batch_received(chunks_per_tick);
+    }
+
+    /// Queues the next batch of chunks from the dimension the player is in,
+    /// if the client is ready for one. Never waits: chunks that are still
+    /// being generated or lit are left for a later batch, and players whose
+    /// queue couldn't take a whole batch are skipped until it drains.
+    pub(crate) fn send_chunk_batch(&self, dimension: &Dimension) -> Result<(), connection::Error> {
+        // A batch is at most a tick's worth of chunks, plus its start and end
+        if self.sender.capacity() < MAX_CHUNKS_PER_TICK as usize + 2 {
+            return Ok(());
+        }
+        // Queued while holding the view, so the batch is either ahead of a
+        // reset's `Respawn` or not taken at all
+        let mut view = self.chunk_view.lock().unwrap();
+        if view.dimension != dimension.id {
+            return Ok(());
+        }
+        let batch = view.next_batch(|(chunk_x, chunk_z)| dimension.chunk_packet(chunk_x, chunk_z));
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let batch_size = batch.len() as i32;
+        self.sender.try_send_raw(connection::serialize(
+            play::clientbound::Packet::ChunkBatchStart,
+        ))?;
+        for packet in batch {
+            self.sender.try_send_raw(packet)?;
+        }
+        self.sender.try_send_raw(connection::serialize(
+            play::clientbound::Packet::ChunkBatchFinished { batch_size },
+        ))
+    }
+
+    async fn forget_chunks(&self, chunks: Vec<(i32, i32)>) -> Result<(), connection::Error> {
+        for (chunk_x, chunk_z) in chunks {
+            self.send(play::clientbound::Packet::ForgetLevelChunk { chunk_z, chunk_x })
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::view::{ChunkView, START_CHUNKS_PER_TICK};
+
+    #[test]
+    fn test_batches() {
+        let mut view = ChunkView::new(2);
+        assert!(view.next_batch(Some).is_empty());
+
+        assert_eq!(view.move_to((10, -3)), Some(Vec::new()));
+        assert_eq!(view.move_to((10, -3)), None);
+        let batch = view.next_batch(Some);
+        assert_eq!(batch.len(), START_CHUNKS_PER_TICK as usize);
+        assert_eq!(batch[0], (10, -3));
+        // Waits for the first batch to be acknowledged
+        assert!(view.next_batch(Some).is_empty());
+
+        view.batch_received(20.0);
+        assert_eq!(view.next_batch(Some).len(), 16);
+        assert!(view.next_batch(Some).is_empty());
+
+        // Only the column at x = 8 is out of range after moving one chunk
+        let mut forgotten = view.move_to((11, -3)).unwrap();
+        forgotten.sort();
+        assert_eq!(forgotten, (-5..=-1).map(|z| (8, z)).collect::<Vec<_>>());
+        assert_eq!(view.next_batch(Some).len(), 5);
+    }
+
+    #[test]
+    fn test_unavailable_chunks() {
+        let mut view = ChunkView::new(1);
+        view.move_to((0, 0));
+        // Only the chunks at x = 1 are ready yet
+        let batch = view.next_batch(|pos @ (chunk_x, _)| (chunk_x == 1).then_some(pos));
+        assert_eq!(batch.len(), 3);
+        view.batch_received(20.0);
+        assert!(
+            view.next_batch(|pos @ (chunk_x, _)| (chunk_x == 1).then_some(pos))
+                .is_empty()
+        );
+
+        // The rest are sent once they're available
+        assert_eq!(view.next_batch(Some).len(), 6);
+    }
+
+    #[test]
+    fn test_slow_client() {
+        let mut view = ChunkView::new(2);
+        view.move_to((0, 0));
+        view.next_batch(Some);
+        view.batch_received(0.5);
+        // The quota is topped up to one chunk after an acknowledgement
+        assert_eq!(view.next_batch(Some).len(), 1);
+        assert_eq!(view.next_batch(Some).len(), 0);
+        view.batch_received(0.5);
+        assert_eq!(view.next_batch(Some).len(), 1);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut view = ChunkView::new(2);
+        view.move_to((0, 0));
+        view.next_batch(Some);
+        view.batch_received(30.0);
+        view.next_batch(Some);
+        view.reset(1);
+        assert_eq!(view.dimension, 1);
+        assert!(view.next_batch(Some).is_empty());
+
+        // Everything is sent again, at the rate the client last asked for
+        assert_eq!(view.move_to((0, 0)), Some(Vec::new()));
+        assert_eq!(view.next_batch(Some).len(), 25);
+    }
+}